@@ -0,0 +1,13 @@
+-- State of the competition that's changed while it runs, as opposed to the
+-- times it's configured with. There's only ever one row.
+CREATE TABLE competition_state (
+    id boolean PRIMARY KEY NOT NULL DEFAULT true,
+
+    -- When the admins lifted the scoreboard freeze, if they have
+    scoreboard_unfrozen_at timestamp(0) without time zone,
+
+    updated_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    CONSTRAINT competition_state_single_row CHECK (id)
+);
+INSERT INTO competition_state DEFAULT VALUES;
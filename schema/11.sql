@@ -0,0 +1,6 @@
+-- Teams can be put in a division (e.g. high school, university, open), which
+-- gets its own scoreboard.
+ALTER TABLE teams ADD COLUMN division varchar(255);
+
+CREATE INDEX teams_division_idx ON teams USING btree (division);
+CREATE INDEX teams_affiliation_idx ON teams USING btree (lower(affiliation));
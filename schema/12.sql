@@ -0,0 +1,6 @@
+-- Banned users and teams can't submit flags or unlock hints, and banned or
+-- hidden teams are left off of the scoreboard.
+ALTER TABLE users ADD COLUMN banned boolean NOT NULL DEFAULT false;
+
+ALTER TABLE teams ADD COLUMN banned boolean NOT NULL DEFAULT false;
+ALTER TABLE teams ADD COLUMN hidden boolean NOT NULL DEFAULT false;
@@ -0,0 +1,19 @@
+-- Deleted challenges are archived here along with their links, the same way
+-- deleted_solves archives solves. These mirror the columns of the live tables,
+-- so any columns added to those need adding here and to archive_challenge too.
+CREATE TABLE deleted_challenges (
+    LIKE challenges,
+
+    deleted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX deleted_chall_id_idx ON deleted_challenges USING btree (id);
+
+CREATE TABLE deleted_challenge_links (
+    LIKE challenge_links,
+
+    deleted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX deleted_cl_chalid_idx ON deleted_challenge_links USING btree (challenge_id);
+
+-- Removing a deleted challenge's deployment goes through the outbox.
+ALTER TYPE outbox_target ADD VALUE 'deploy';
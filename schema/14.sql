@@ -0,0 +1,34 @@
+CREATE TYPE auth_token AS ENUM (
+    'frontend',
+    'deploy',
+    'oauth'
+);
+CREATE TYPE audit_outcome AS ENUM (
+    'success',
+    'error'
+);
+
+-- A record of every request that changed something, with any secrets in its
+-- params redacted.
+CREATE TABLE audit_log (
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+
+    -- The token the request was authenticated with
+    token auth_token NOT NULL,
+    -- e.g. `sql.team.create`
+    operation varchar(255) NOT NULL,
+    params jsonb NOT NULL,
+
+    -- The admin making the request, for admin queries
+    actor_id uuid,
+    affected_ids uuid[] NOT NULL DEFAULT ARRAY[]::uuid[],
+
+    outcome audit_outcome NOT NULL,
+    status_code smallint NOT NULL,
+
+    inserted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX audit_log_inserted_idx ON audit_log USING btree (inserted_at, id);
+CREATE INDEX audit_log_operation_idx ON audit_log USING btree (operation);
+CREATE INDEX audit_log_affected_idx ON audit_log USING gin (affected_ids);
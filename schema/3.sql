@@ -0,0 +1,15 @@
+CREATE TYPE scoring_type AS ENUM (
+    'static',
+    'linear',
+    'parabolic'
+);
+
+ALTER TABLE challenges
+    ADD COLUMN scoring_type scoring_type DEFAULT 'static' NOT NULL,
+    ADD COLUMN min_points integer DEFAULT 0 NOT NULL,
+    ADD COLUMN decay integer DEFAULT 0 NOT NULL,
+    ADD COLUMN current_points integer DEFAULT 0 NOT NULL;
+
+UPDATE challenges SET
+    min_points = points,
+    current_points = points;
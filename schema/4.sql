@@ -0,0 +1,29 @@
+CREATE TYPE outbox_target AS ENUM (
+    'discord',
+    'frontend'
+);
+CREATE TYPE outbox_status AS ENUM (
+    'pending',
+    'sent',
+    'failed'
+);
+
+CREATE TABLE outbox (
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+
+    target outbox_target NOT NULL,
+    payload jsonb NOT NULL,
+
+    status outbox_status DEFAULT 'pending' NOT NULL,
+    attempts integer DEFAULT 0 NOT NULL,
+    last_error text,
+
+    next_attempt_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at timestamp(0) without time zone,
+
+    inserted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX outbox_pending_idx ON outbox USING btree (next_attempt_at) WHERE status = 'pending';
+CREATE INDEX outbox_inserted_idx ON outbox USING btree (inserted_at);
@@ -0,0 +1,3 @@
+-- Speeds up the sliding window used to rate limit solve attempts
+CREATE INDEX solves_userid_inserted_idx ON solve_attempts USING btree (user_id, inserted_at);
+CREATE INDEX solves_teamid_inserted_idx ON solve_attempts USING btree (team_id, challenge_id, inserted_at);
@@ -0,0 +1,15 @@
+CREATE TYPE flag_type AS ENUM (
+    'exact',
+    'case_insensitive',
+    'regex'
+);
+
+-- `flag` stays the primary flag, and `accepted_flags` holds any others. For
+-- regex challenges, these are all patterns that have to match the whole guess.
+ALTER TABLE challenges
+    ADD COLUMN flag_type flag_type NOT NULL DEFAULT 'exact',
+    ADD COLUMN accepted_flags varchar(255)[] NOT NULL DEFAULT ARRAY[]::varchar(255)[];
+
+-- Flags are now checked by the webhook, which passes whether the guess was
+-- correct to `do_solve_attempt`.
+DROP FUNCTION IF EXISTS do_solve_attempt(uuid, uuid, uuid, varchar);
@@ -0,0 +1,39 @@
+-- Per-team flags are templates with a `{}` placeholder that is filled in with
+-- an HMAC of the team's id, keyed with the challenge's `flag_secret`.
+ALTER TYPE flag_type ADD VALUE 'per_team';
+
+ALTER TABLE challenges ADD COLUMN flag_secret varchar(255);
+
+CREATE TABLE flag_sharing_incidents (
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+
+    challenge_id uuid NOT NULL,
+    -- The attempt, or NULL if it's since been deleted. The incident is kept as
+    -- evidence either way, so the guess is copied into it.
+    attempt_id uuid,
+
+    -- The team (and user, unless they've since been deleted) that submitted
+    -- the flag
+    team_id uuid NOT NULL,
+    user_id uuid,
+    -- The team the flag was generated for
+    owner_team_id uuid NOT NULL,
+
+    flag_guess varchar(255) NOT NULL,
+
+    inserted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX flag_sharing_chalid_idx ON flag_sharing_incidents USING btree (challenge_id);
+CREATE INDEX flag_sharing_teamid_idx ON flag_sharing_incidents USING btree (team_id);
+CREATE INDEX flag_sharing_ownerid_idx ON flag_sharing_incidents USING btree (owner_team_id);
+
+ALTER TABLE ONLY flag_sharing_incidents ADD
+    CONSTRAINT fkey_fsi_chalid FOREIGN KEY (challenge_id) REFERENCES challenges(id) ON DELETE CASCADE;
+ALTER TABLE ONLY flag_sharing_incidents ADD
+    CONSTRAINT fkey_fsi_attemptid FOREIGN KEY (attempt_id) REFERENCES solve_attempts(id) ON DELETE SET NULL;
+ALTER TABLE ONLY flag_sharing_incidents ADD
+    CONSTRAINT fkey_fsi_teamid FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE;
+ALTER TABLE ONLY flag_sharing_incidents ADD
+    CONSTRAINT fkey_fsi_userid FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE ONLY flag_sharing_incidents ADD
+    CONSTRAINT fkey_fsi_ownerid FOREIGN KEY (owner_team_id) REFERENCES teams(id) ON DELETE CASCADE;
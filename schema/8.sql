@@ -0,0 +1,49 @@
+-- Hints are hidden until a team unlocks them, which costs the team the hint's
+-- cost in points.
+CREATE TABLE challenge_hints (
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    challenge_id uuid NOT NULL,
+    -- 1-based order of the hint within its challenge
+    position integer NOT NULL,
+
+    content text NOT NULL,
+    cost integer NOT NULL DEFAULT 0,
+
+    inserted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX hints_chalid_position_idx ON challenge_hints USING btree (challenge_id, position);
+ALTER TABLE ONLY challenge_hints ADD
+    CONSTRAINT fkey_h_chalid FOREIGN KEY (challenge_id) REFERENCES challenges(id) ON DELETE CASCADE;
+
+CREATE TABLE hint_unlocks (
+    id uuid PRIMARY KEY NOT NULL DEFAULT uuid_generate_v4(),
+    -- NULL if the hint's challenge has since been deleted. The team still paid
+    -- for it either way.
+    hint_id uuid,
+    team_id uuid NOT NULL,
+    -- Who unlocked it, or NULL if they've since been deleted. The team still
+    -- paid for it either way.
+    user_id uuid,
+
+    -- What the team paid for the hint. This follows the hint's cost if it's
+    -- changed later, see set_challenge_hints.
+    cost integer NOT NULL,
+
+    inserted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE UNIQUE INDEX hint_unlocks_hintid_teamid_idx ON hint_unlocks USING btree (hint_id, team_id);
+CREATE INDEX hint_unlocks_teamid_idx ON hint_unlocks USING btree (team_id);
+ALTER TABLE ONLY hint_unlocks ADD
+    CONSTRAINT fkey_hu_hintid FOREIGN KEY (hint_id) REFERENCES challenge_hints(id) ON DELETE SET NULL;
+ALTER TABLE ONLY hint_unlocks ADD
+    CONSTRAINT fkey_hu_teamid FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE;
+ALTER TABLE ONLY hint_unlocks ADD
+    CONSTRAINT fkey_hu_userid FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL;
+
+-- Existing hints become free hints
+INSERT INTO challenge_hints (challenge_id, position, content)
+SELECT challenges.id, hint.position, hint.content
+FROM challenges, unnest(challenges.hints) WITH ORDINALITY AS hint(content, position);
+
+ALTER TABLE challenges DROP COLUMN hints;
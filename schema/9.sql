@@ -0,0 +1,16 @@
+-- A challenge is locked for a team until the team has solved every one of its
+-- prerequisites.
+CREATE TABLE challenge_prerequisites (
+    challenge_id uuid NOT NULL,
+    prerequisite_id uuid NOT NULL,
+
+    inserted_at timestamp(0) without time zone NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (challenge_id, prerequisite_id),
+    CONSTRAINT prereq_not_self CHECK (challenge_id <> prerequisite_id)
+);
+CREATE INDEX prereqs_prereqid_idx ON challenge_prerequisites USING btree (prerequisite_id);
+ALTER TABLE ONLY challenge_prerequisites ADD
+    CONSTRAINT fkey_p_chalid FOREIGN KEY (challenge_id) REFERENCES challenges(id) ON DELETE CASCADE;
+ALTER TABLE ONLY challenge_prerequisites ADD
+    CONSTRAINT fkey_p_prereqid FOREIGN KEY (prerequisite_id) REFERENCES challenges(id) ON DELETE CASCADE;
@@ -2,8 +2,14 @@ CREATE OR REPLACE FUNCTION get_solves_chall(challenge_id uuid) RETURNS bigint AS
     SELECT COUNT(attempt_id) AS result FROM solve_successes WHERE challenge_id = $1;
 $$ LANGUAGE SQL STABLE;
 
+CREATE OR REPLACE FUNCTION get_hint_costs_team(team_id uuid) RETURNS bigint AS $$
+    SELECT COALESCE(SUM(cost), 0) AS result
+    FROM hint_unlocks
+    WHERE hint_unlocks.team_id = $1;
+$$ LANGUAGE SQL STABLE;
+
 CREATE OR REPLACE FUNCTION get_score_team(team_id uuid) RETURNS bigint AS $$
-    SELECT COALESCE(SUM(challenges.points), 0) AS result
+    SELECT COALESCE(SUM(challenges.current_points), 0) - get_hint_costs_team($1) AS result
     FROM challenges
         INNER JOIN solve_successes
         ON challenges.id = solve_successes.challenge_id
@@ -11,7 +17,7 @@ CREATE OR REPLACE FUNCTION get_score_team(team_id uuid) RETURNS bigint AS $$
 $$ LANGUAGE SQL STABLE;
 
 CREATE OR REPLACE FUNCTION get_score_user(user_id uuid) RETURNS bigint AS $$
-    SELECT COALESCE(SUM(challenges.points), 0) AS result
+    SELECT COALESCE(SUM(challenges.current_points), 0) AS result
     FROM challenges
         INNER JOIN solve_successes
         ON challenges.id = solve_successes.challenge_id
@@ -41,9 +47,23 @@ CREATE OR REPLACE FUNCTION update_scores_with_last_solve(success_id uuid) RETURN
     WHERE challenges.id = (SELECT challenge_id FROM solve_successes WHERE id = $1);
 $$ LANGUAGE SQL VOLATILE;
 
-CREATE OR REPLACE FUNCTION do_solve_attempt(att_user_id uuid, att_team_id uuid, att_challenge_id uuid, att_guess varchar(255)) RETURNS RECORD AS $$
+CREATE OR REPLACE FUNCTION update_scores_for_challenge(chall_id uuid) RETURNS void AS $$
+    UPDATE users
+    SET
+        score = get_score_user(users.id),
+        updated_at = CURRENT_TIMESTAMP
+    WHERE users.id IN (SELECT user_id FROM solve_successes WHERE challenge_id = $1);
+
+    UPDATE teams
+    SET
+        score = get_score_team(teams.id),
+        updated_at = CURRENT_TIMESTAMP
+    WHERE teams.id IN (SELECT team_id FROM solve_successes WHERE challenge_id = $1);
+$$ LANGUAGE SQL VOLATILE;
+
+CREATE OR REPLACE FUNCTION do_solve_attempt(att_user_id uuid, att_team_id uuid, att_challenge_id uuid, att_guess varchar(255), att_correct boolean) RETURNS RECORD AS $$
     DECLARE
-        guess_correct boolean := (SELECT flag = att_guess AS correct FROM challenges WHERE id = att_challenge_id);
+        guess_correct boolean := att_correct;
         already_solved boolean := (SELECT COUNT(id) FROM solve_successes WHERE challenge_id=att_challenge_id AND team_id=att_team_id);
         attempt_id uuid := uuid_generate_v4();
         success_entry_id uuid := uuid_generate_v4();
@@ -66,11 +86,19 @@ CREATE OR REPLACE FUNCTION do_solve_attempt(att_user_id uuid, att_team_id uuid,
 $$ LANGUAGE plpgsql VOLATILE;
 
 
+-- Last solves are recomputed along with scores, since solves may have been
+-- deleted since, and they break ties on the scoreboard.
 CREATE OR REPLACE FUNCTION update_all_user_scores() RETURNS void AS $$
-    UPDATE users SET score = get_score_user(id);
+    UPDATE users
+    SET
+        score = get_score_user(id),
+        last_solve = (SELECT MAX(solve.solved_at) FROM solve_successes AS solve WHERE solve.user_id = users.id);
 $$ LANGUAGE SQL VOLATILE;
 CREATE OR REPLACE FUNCTION update_all_team_scores() RETURNS void AS $$
-    UPDATE teams SET score = get_score_team(id);
+    UPDATE teams
+    SET
+        score = get_score_team(id),
+        last_solve = (SELECT MAX(solve.solved_at) FROM solve_successes AS solve WHERE solve.team_id = teams.id);
 $$ LANGUAGE SQL VOLATILE;
 CREATE OR REPLACE FUNCTION update_all_chall_solves() RETURNS void AS $$
     UPDATE challenges SET solve_count = get_solves_chall(id);
@@ -136,11 +164,200 @@ CREATE OR REPLACE FUNCTION get_top_n_teams(n integer) RETURNS SETOF uuid AS $$
     SELECT id FROM teams ORDER BY score DESC, last_solve ASC, inserted_at ASC LIMIT n;
 $$ LANGUAGE SQL STABLE;
 
-CREATE OR REPLACE FUNCTION get_team_score_at(team_id uuid, at_time timestamp(0) without time zone) RETURNS bigint AS $$
-    SELECT COALESCE(SUM(chall.points), 0) AS result
-    FROM solve_successes as solve
-    INNER JOIN challenges as chall ON solve.challenge_id = chall.id
-    WHERE solve.team_id = $1 AND solve.solved_at < at_time;
+-- The value of every challenge as of `at_time`, from the number of teams that
+-- had solved it by then. This mirrors `ScoringParams::value` in
+-- src/handlers/sql/scoring.rs, so that the frozen scoreboard doesn't move when
+-- later solves decay a challenge. With no `at_time`, this is the challenges'
+-- `current_points`, which is what `teams.score` is summed from.
+CREATE OR REPLACE FUNCTION get_challenge_points_at(at_time timestamp(0) without time zone) RETURNS TABLE(challenge_id uuid, points integer) AS $$
+    SELECT chall.id, CASE
+        WHEN at_time IS NULL THEN chall.current_points
+        WHEN chall.decay <= 0 OR chall.scoring_type = 'static' THEN chall.points
+        ELSE GREATEST(LEAST(ceil(
+            chall.points::float8 - (chall.points::float8 - LEAST(chall.min_points, chall.points)::float8) *
+                CASE chall.scoring_type
+                    WHEN 'linear' THEN counted.solves / chall.decay
+                    ELSE (counted.solves * counted.solves) / (chall.decay::float8 * chall.decay)
+                END
+        ), chall.points), LEAST(chall.min_points, chall.points))::integer
+    END
+    FROM challenges AS chall
+        LEFT JOIN (
+            SELECT solve.challenge_id, COUNT(*) AS count
+            FROM solve_successes AS solve
+            WHERE solve.solved_at <= at_time
+            GROUP BY solve.challenge_id
+        ) AS solved ON solved.challenge_id = chall.id
+        CROSS JOIN LATERAL (
+            SELECT GREATEST(COALESCE(solved.count, 0) - 1, 0)::float8 AS solves
+        ) AS counted;
+$$ LANGUAGE SQL STABLE;
+
+-- Every team's score and last solve as of `at_time`, with solves priced by
+-- get_challenge_points_at as of then. Times are only stored to the second, so
+-- everything that happened in the same second as `at_time` is counted as
+-- having happened by then. With no `at_time` there are no rows, since the
+-- current standings are just what's stored in `teams`.
+CREATE OR REPLACE FUNCTION get_team_standings_at(at_time timestamp(0) without time zone) RETURNS TABLE(team_id uuid, score bigint, last_solve timestamp(0) without time zone) AS $$
+    WITH solved AS (
+        SELECT
+            solve.team_id,
+            SUM(price.points)::bigint AS points,
+            MAX(solve.solved_at) AS last_solve
+        FROM solve_successes AS solve
+            INNER JOIN get_challenge_points_at(at_time) AS price ON price.challenge_id = solve.challenge_id
+        WHERE solve.solved_at <= at_time
+        GROUP BY solve.team_id
+    ), unlocked AS (
+        SELECT unlock.team_id, SUM(unlock.cost)::bigint AS cost
+        FROM hint_unlocks AS unlock
+        WHERE unlock.inserted_at <= at_time
+        GROUP BY unlock.team_id
+    )
+    SELECT
+        team.id,
+        COALESCE(solved.points, 0) - COALESCE(unlocked.cost, 0),
+        solved.last_solve
+    FROM teams AS team
+        LEFT JOIN solved ON solved.team_id = team.id
+        LEFT JOIN unlocked ON unlocked.team_id = team.id;
+$$ LANGUAGE SQL STABLE STRICT;
+
+-- The score history of the teams: their score at `start_time`, then their
+-- score after each second in which a solve or hint unlock changed it, up to
+-- `end_time` if it's given. Every solve is priced as of `priced_at` (see
+-- get_challenge_points_at), the same as the scoreboard the history is shown
+-- with, so the last entry is always the team's score on that scoreboard.
+CREATE OR REPLACE FUNCTION get_team_score_history(
+    team_ids uuid[],
+    start_time timestamp(0) without time zone,
+    end_time timestamp(0) without time zone,
+    priced_at timestamp(0) without time zone
+) RETURNS TABLE(team_id uuid, score bigint, event_time timestamp(0) without time zone) AS $$
+    WITH event AS (
+        SELECT team.id AS team_id, start_time AS event_time, 0::bigint AS change
+        FROM teams AS team
+        WHERE team.id = ANY(team_ids)
+        UNION ALL
+        SELECT solve.team_id, solve.solved_at, price.points::bigint
+        FROM solve_successes AS solve
+            INNER JOIN get_challenge_points_at(priced_at) AS price ON price.challenge_id = solve.challenge_id
+        WHERE solve.team_id = ANY(team_ids)
+        UNION ALL
+        SELECT unlock.team_id, unlock.inserted_at, -unlock.cost::bigint
+        FROM hint_unlocks AS unlock
+        WHERE unlock.team_id = ANY(team_ids)
+    ), per_second AS (
+        -- Everything before `start_time` is part of the starting score
+        SELECT event.team_id, GREATEST(event.event_time, start_time) AS event_time, SUM(event.change) AS change
+        FROM event
+        WHERE end_time IS NULL OR event.event_time <= end_time
+        GROUP BY event.team_id, GREATEST(event.event_time, start_time)
+    )
+    SELECT
+        per_second.team_id,
+        (SUM(per_second.change) OVER (PARTITION BY per_second.team_id ORDER BY per_second.event_time))::bigint,
+        per_second.event_time
+    FROM per_second;
+$$ LANGUAGE SQL STABLE;
+
+CREATE OR REPLACE FUNCTION get_team_last_solve_at(team_id uuid, at_time timestamp(0) without time zone) RETURNS timestamp(0) without time zone AS $$
+    SELECT MAX(solve.solved_at) AS result
+    FROM solve_successes AS solve
+    WHERE solve.team_id = $1 AND solve.solved_at <= at_time;
+$$ LANGUAGE SQL STABLE;
+
+-- Sets the hints of a challenge. A hint that keeps its position and content
+-- keeps its id and unlocks, and if its cost changed, teams that unlocked it
+-- now pay the new cost. A hint that's removed or whose content changed is
+-- deleted, so teams that unlocked it get its cost back and have to unlock the
+-- new hint again. Either way, the scores of those teams are recomputed.
+CREATE OR REPLACE FUNCTION set_challenge_hints(chall uuid, contents text[], costs integer[]) RETURNS void AS $$
+    DECLARE
+        affected_teams uuid[] := ARRAY(
+            SELECT DISTINCT unlock.team_id
+            FROM hint_unlocks AS unlock
+                INNER JOIN challenge_hints AS hint ON hint.id = unlock.hint_id
+                LEFT JOIN unnest(contents, costs) WITH ORDINALITY AS updated(content, cost, position)
+                    ON updated.position = hint.position
+            WHERE hint.challenge_id = chall AND (
+                updated.content IS DISTINCT FROM hint.content OR
+                updated.cost IS DISTINCT FROM unlock.cost
+            )
+        );
+    BEGIN
+        -- Deleting a hint only detaches its unlocks (see archive_challenge), so
+        -- they're deleted along with it here
+        WITH removed AS (
+            DELETE FROM challenge_hints AS hint
+            WHERE hint.challenge_id = chall AND NOT EXISTS (
+                SELECT 1
+                FROM unnest(contents) WITH ORDINALITY AS updated(content, position)
+                WHERE updated.position = hint.position AND updated.content = hint.content
+            )
+            RETURNING hint.id
+        )
+        DELETE FROM hint_unlocks AS unlock
+        USING removed
+        WHERE unlock.hint_id = removed.id;
+
+        INSERT INTO challenge_hints (challenge_id, position, content, cost)
+        SELECT chall, hint.position, hint.content, hint.cost
+        FROM unnest(contents, costs) WITH ORDINALITY AS hint(content, cost, position)
+        ON CONFLICT (challenge_id, position) DO UPDATE SET
+            cost = EXCLUDED.cost,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE challenge_hints.cost <> EXCLUDED.cost;
+
+        UPDATE hint_unlocks AS unlock
+        SET cost = hint.cost
+        FROM challenge_hints AS hint
+        WHERE hint.id = unlock.hint_id AND hint.challenge_id = chall AND unlock.cost <> hint.cost;
+
+        UPDATE teams
+        SET
+            score = get_score_team(teams.id),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE teams.id = ANY(affected_teams);
+    END;
+$$ LANGUAGE plpgsql VOLATILE;
+
+-- Whether the team has solved every prerequisite of the challenge.
+CREATE OR REPLACE FUNCTION challenge_unlocked_for_team(chall_id uuid, team_id uuid) RETURNS boolean AS $$
+    SELECT NOT EXISTS (
+        SELECT 1 FROM challenge_prerequisites AS prereq
+        WHERE prereq.challenge_id = $1 AND NOT EXISTS (
+            SELECT 1 FROM solve_successes AS solve
+            WHERE solve.challenge_id = prereq.prerequisite_id AND solve.team_id = $2
+        )
+    );
+$$ LANGUAGE SQL STABLE;
+
+-- Replaces the prerequisites of a challenge. Ids that aren't challenges are
+-- ignored, and returned so that the caller can reject them.
+CREATE OR REPLACE FUNCTION set_challenge_prerequisites(chall uuid, prereqs uuid[]) RETURNS SETOF uuid AS $$
+    DELETE FROM challenge_prerequisites WHERE challenge_id = chall;
+
+    INSERT INTO challenge_prerequisites (challenge_id, prerequisite_id)
+    SELECT DISTINCT chall, challenges.id
+    FROM challenges
+    WHERE challenges.id = ANY(prereqs);
+
+    SELECT DISTINCT prereq FROM unnest(prereqs) AS prereq
+    WHERE NOT EXISTS (SELECT 1 FROM challenges WHERE challenges.id = prereq);
+$$ LANGUAGE SQL VOLATILE;
+
+-- Whether the challenge (transitively) requires itself, which would leave it
+-- locked forever.
+CREATE OR REPLACE FUNCTION challenge_prerequisites_cyclic(chall uuid) RETURNS boolean AS $$
+    WITH RECURSIVE required(id) AS (
+        SELECT prerequisite_id FROM challenge_prerequisites WHERE challenge_id = chall
+        UNION
+        SELECT prereq.prerequisite_id
+        FROM challenge_prerequisites AS prereq
+            INNER JOIN required ON prereq.challenge_id = required.id
+    )
+    SELECT EXISTS (SELECT 1 FROM required WHERE id = chall);
 $$ LANGUAGE SQL STABLE;
 
 CREATE OR REPLACE FUNCTION delete_solve(solve_id uuid) RETURNS uuid AS $$
@@ -158,6 +375,37 @@ CREATE OR REPLACE FUNCTION delete_solve(solve_id uuid) RETURNS uuid AS $$
     SELECT solve_id AS result;
 $$ LANGUAGE SQL VOLATILE;
 
+-- Moves a solve back out of deleted_solves. If the solve was correct, it counts
+-- again unless the team solved the challenge before it. A later solve of the
+-- same challenge by the team stops counting in its place.
+CREATE OR REPLACE FUNCTION restore_solve(solve_id uuid) RETURNS uuid AS $$
+    INSERT INTO solve_attempts (id, flag_guess, correct, user_id, challenge_id, team_id, inserted_at, updated_at)
+    SELECT id, flag_guess, correct, user_id, challenge_id, team_id, inserted_at, CURRENT_TIMESTAMP
+    FROM deleted_solves
+    WHERE id = solve_id;
+
+    DELETE FROM solve_successes AS success
+    USING deleted_solves AS deleted
+    WHERE
+        deleted.id = solve_id AND deleted.correct AND
+        success.challenge_id = deleted.challenge_id AND
+        success.team_id = deleted.team_id AND
+        success.solved_at > deleted.inserted_at;
+
+    INSERT INTO solve_successes (attempt_id, user_id, challenge_id, team_id, solved_at)
+    SELECT id, user_id, challenge_id, team_id, inserted_at
+    FROM deleted_solves AS deleted
+    WHERE deleted.id = solve_id AND deleted.correct AND NOT EXISTS (
+        SELECT 1 FROM solve_successes AS success
+        WHERE success.challenge_id = deleted.challenge_id AND success.team_id = deleted.team_id
+    );
+
+    DELETE FROM deleted_solves
+    WHERE id = solve_id;
+
+    SELECT solve_id AS result;
+$$ LANGUAGE SQL VOLATILE;
+
 CREATE OR REPLACE FUNCTION delete_solves_for_challenge(chall_id uuid) RETURNS SETOF uuid AS $$
     SELECT delete_solve(solve_attempts.id) as id
     FROM solve_attempts
@@ -167,3 +415,47 @@ CREATE OR REPLACE FUNCTION delete_solves_for_challenge(chall_id uuid) RETURNS SE
 
     SELECT id FROM deleted_solves WHERE challenge_id = chall_id;
 $$ LANGUAGE SQL VOLATILE;
+
+CREATE OR REPLACE FUNCTION delete_solves_for_user(del_user_id uuid) RETURNS SETOF uuid AS $$
+    SELECT delete_solve(solve_attempts.id) as id
+    FROM solve_attempts
+    WHERE user_id = del_user_id;
+
+    SELECT id FROM deleted_solves WHERE user_id = del_user_id;
+$$ LANGUAGE SQL VOLATILE;
+
+CREATE OR REPLACE FUNCTION delete_solves_for_team(del_team_id uuid) RETURNS SETOF uuid AS $$
+    SELECT delete_solve(solve_attempts.id) as id
+    FROM solve_attempts
+    WHERE team_id = del_team_id;
+
+    SELECT id FROM deleted_solves WHERE team_id = del_team_id;
+$$ LANGUAGE SQL VOLATILE;
+
+-- Moves a challenge and its links to the archive tables. Its solves should be
+-- archived with delete_solves_for_challenge first, since they'd otherwise be
+-- deleted along with it. Unlocks of its hints are kept with their hint_id set
+-- to NULL, so teams keep paying for the hints they unlocked.
+CREATE OR REPLACE FUNCTION archive_challenge(chall_id uuid) RETURNS void AS $$
+    INSERT INTO deleted_challenge_links (id, challenge_id, url, type, deleted_at)
+    SELECT id, challenge_id, url, type, CURRENT_TIMESTAMP
+    FROM challenge_links
+    WHERE challenge_id = chall_id;
+
+    INSERT INTO deleted_challenges (
+        id, name, description, flag, points, authors, categories, tags,
+        solve_count, visible, source_folder, inserted_at, updated_at,
+        scoring_type, min_points, decay, current_points,
+        flag_type, accepted_flags, flag_secret, deleted_at
+    )
+    SELECT
+        id, name, description, flag, points, authors, categories, tags,
+        solve_count, visible, source_folder, inserted_at, updated_at,
+        scoring_type, min_points, decay, current_points,
+        flag_type, accepted_flags, flag_secret, CURRENT_TIMESTAMP
+    FROM challenges
+    WHERE id = chall_id;
+
+    DELETE FROM challenges
+    WHERE id = chall_id;
+$$ LANGUAGE SQL VOLATILE;
@@ -17,10 +17,9 @@ mod env {
     }
     mod len_vars {
         use super::str_vars::{ frontend_token, webhook_token, deploy_token, oauth_token };
+        use super::super::TOKEN_LEN;
         use arcs_env_rs::*;
 
-        const TOKEN_LEN: usize = 64;
-
         lazy_static::lazy_static! {
             pub static ref FRONTEND_AUTH: Result<[u8; TOKEN_LEN], &'static str> = (&frontend_token().as_bytes().to_owned()[..])
                 .try_into()
@@ -62,6 +61,9 @@ mod env {
     /// - `ALLOWED_OAUTH_TOKEN`
     /// - `WEBHOOK_AUTH_TOKEN`
     /// - `DEPLOY_AUTH_TOKEN`
+    /// 
+    /// More secrets for each token can be added with `AUTH_SECRETS_FILE` (see
+    /// [`super::reload_secrets`]), which is loaded separately.
     pub fn check_env_vars() -> Result<(), EnvVarErr<4>> {
         str_vars::check_str_env_vars()?;
         len_vars::check_len_env_vars()
@@ -73,11 +75,20 @@ mod env {
 pub (crate) use env::webhook_auth;
 pub use env::check_env_vars;
 
+/// The length of every token's secrets, in bytes.
+const TOKEN_LEN: usize = 64;
+
+mod secrets;
+pub use secrets::{ reload as reload_secrets, run_watcher as watch_secrets, SecretsError };
+
 /// These are the types of tokens that can be checked via a header.
 /// 
 /// [`Frontend`][`Self::Frontend`] and [`Deploy`][`Self::Deploy`] are the
 /// bearer-type tokens, with Oauth being transmitted in the body of the request.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "auth_token", rename_all = "snake_case")]
 pub enum Token {
     /// A bearer-type token that authenticates the request as being from the
     /// frontend.
@@ -93,7 +104,7 @@ pub enum Token {
 }
 
 /// This is a crate-public function that will check if any specific bytes match
-/// any of the specified tokens.
+/// any active secret of the specified tokens (see [`reload_secrets`]).
 /// 
 /// This is currently only extenrally used to check for a valid the
 /// `ALLOWED_OAUTH_TOKEN` in the sql handler.
@@ -106,14 +117,8 @@ pub (crate) fn check_matches(list: &[Token], bytes: &[u8]) -> bool {
 
     let mut will_return_true = false;
     
-    for token in list {
-        use self::env::*;
-
-        let bool_return = match token {
-            Token::Frontend => constant_time_eq::constant_time_eq_n(&buffer, &frontend_auth()),
-            Token::Deploy   => constant_time_eq::constant_time_eq_n(&buffer, &deploy_auth()),
-            Token::Oauth    => constant_time_eq::constant_time_eq_n(&buffer, &oauth_auth()),
-        };
+    for secret in secrets::active(list) {
+        let bool_return = constant_time_eq::constant_time_eq_n(&buffer, &secret.key);
         will_return_true = std::hint::black_box(black_box_or(
             std::hint::black_box(bool_return),
             std::hint::black_box(will_return_true),
@@ -122,7 +127,61 @@ pub (crate) fn check_matches(list: &[Token], bytes: &[u8]) -> bool {
     will_return_true
 }
 
-// pub fn authenticate_request()
+mod signing;
+pub use signing::{ RequestParts, SignatureError, SCHEME as SIGNATURE_SCHEME };
+
+fn env_key(token: Token) -> [u8; TOKEN_LEN] {
+    use self::env::*;
+
+    match token {
+        Token::Frontend => frontend_auth(),
+        Token::Deploy   => deploy_auth(),
+        Token::Oauth    => oauth_auth(),
+    }
+}
+
+/// Gets the `Authorization` header for a request to the frontend or deploy
+/// server. The request is signed with the webhook's current secret (see
+/// [`reload_secrets`]) if `SIGN_OUTBOUND_REQUESTS` is set, and otherwise the
+/// secret is sent as-is.
+pub (crate) fn outbound_authorization(method: &str, url: &str, body: &[u8]) -> String {
+    let token = secrets::outbound_key();
+
+    if !crate::env::signing::sign_outbound() {
+        return format!("Bearer {}", String::from_utf8_lossy(&token));
+    }
+
+    let path = reqwest::Url::parse(url)
+        .map(|url| url.path().to_string())
+        .unwrap_or_else(|_| "/".to_string());
+
+    signing::sign(&token, RequestParts { method, path: &path, body })
+}
+
+/// Why a request's `Authorization` header was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    /// The header isn't a bearer token or a signature.
+    Missing,
+    /// The bearer token doesn't match any of the allowed tokens.
+    BadToken,
+    /// Only signed requests are accepted (see `REQUIRE_SIGNED_REQUESTS`).
+    SignatureRequired,
+    /// The signature was rejected.
+    Signature(SignatureError),
+}
+
+impl AuthError {
+    /// A description of the error, for the body of the 401 response.
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Missing => "The authorization header isn't a bearer token or a request signature",
+            Self::BadToken => "The bearer token isn't a frontend or deploy token",
+            Self::SignatureRequired => "Requests have to be signed",
+            Self::Signature(e) => e.message(),
+        }
+    }
+}
 
 
 /// This type allows for authorization tokens to be captured using builtin
@@ -142,6 +201,9 @@ pub (crate) fn check_matches(list: &[Token], bytes: &[u8]) -> bool {
 /// }
 /// ```
 /// 
+/// Callers can also sign their requests instead of sending their token, which
+/// is checked with [`AuthHeader::authenticate`] (see [`SIGNATURE_SCHEME`]).
+/// 
 /// There should *never* be a situation in which the authorization header
 /// content has to be accessed as bytes. The ability to access the data inside
 /// would be a auth liability.
@@ -184,4 +246,37 @@ impl AuthHeader {
 
         check_matches(list, stripped)
     }
+
+    /// Authenticates the request as coming from one of the tokens in `list`,
+    /// either by a bearer token or a signature of the request (see
+    /// [`SIGNATURE_SCHEME`]). Bearer tokens are rejected if
+    /// `REQUIRE_SIGNED_REQUESTS` is set.
+    pub fn authenticate(&self, list: &[Token], parts: RequestParts) -> Result<Token, AuthError> {
+        if self.data.starts_with(b"Bearer ") {
+            if crate::env::signing::required() {
+                return Err(AuthError::SignatureRequired);
+            }
+            return self.matching(list).ok_or(AuthError::BadToken);
+        }
+
+        let params = std::str::from_utf8(&self.data)
+            .ok()
+            .and_then(|header| header.strip_prefix(SIGNATURE_SCHEME))
+            .and_then(|params| params.strip_prefix(' '))
+            .ok_or(AuthError::Missing)?;
+
+        let secrets = secrets::active(list);
+        let index = signing::verify(secrets.iter().map(|secret| &secret.key[..]), params, parts)
+            .map_err(AuthError::Signature)?;
+
+        Ok(secrets[index].token)
+    }
+
+    /// Gets the first token in `list` that the header matches, if any.
+    pub fn matching(&self, list: &[Token]) -> Option<Token> {
+        list
+            .iter()
+            .copied()
+            .find(|token| self.check_matches(std::slice::from_ref(token)))
+    }
 }
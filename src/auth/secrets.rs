@@ -0,0 +1,383 @@
+//! Extra secrets for each [`Token`], so tokens can be rotated without
+//! restarting every server at once.
+//!
+//! The secrets are read from the JSON file at `AUTH_SECRETS_FILE`, if it's
+//! set, which looks like:
+//! ```json
+//! {
+//!     "secrets": [
+//!         { "token": "frontend", "secret": "<64 characters>", "expires_at": "2024-06-01T00:00:00Z" },
+//!         { "token": "deploy", "secret": "<64 characters>" }
+//!     ],
+//!     "webhook_secrets": [
+//!         { "secret": "<64 characters>" }
+//!     ],
+//!     "env_secrets": { "frontend": "2024-06-01T00:00:00Z", "webhook": false }
+//! }
+//! ```
+//! Every key is optional, and a file that's just the `secrets` list is
+//! accepted too. An entry without an `expires_at` never expires.
+//!
+//! - `secrets` are accepted from callers alongside the secrets from the
+//!   environment (e.g. `FRONTEND_AUTH_TOKEN`).
+//! - `webhook_secrets` are what requests to the frontend and deploy server are
+//!   authenticated with (see [`outbound_key`]). The last active one is used,
+//!   falling back to `WEBHOOK_AUTH_TOKEN` if there aren't any.
+//! - `env_secrets` retires the secrets from the environment, by `frontend`,
+//!   `deploy`, `oauth` or `webhook`. Each is `true` (the default), `false` to
+//!   stop using it, or the time it expires at.
+//!
+//! The file is re-read whenever it changes (see [`run_watcher`]), so rotating
+//! a token is:
+//! 1. Adding the new secret to the file
+//! 2. Moving each caller over to the new secret
+//! 3. Removing the old secret, or letting it expire (or retiring it in
+//!    `env_secrets`, if it's the one from the environment)
+//!
+//! Rotating the webhook's own secret is the other way around, since it's the
+//! one calling: the frontend and deploy server start accepting the new secret,
+//! it's added to `webhook_secrets`, and then the old one is dropped from them.
+
+use std::path::{ Path, PathBuf };
+use std::sync::{ PoisonError, RwLock };
+use std::time::{ Duration, SystemTime };
+
+use chrono::{ DateTime, Utc };
+use serde::Deserialize;
+
+use crate::logging::*;
+
+use super::{ Token, TOKEN_LEN };
+
+/// How often the file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SecretEntry {
+    token: Token,
+    secret: String,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WebhookSecretEntry {
+    secret: String,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Whether a secret from the environment is still used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+enum EnvSecret {
+    Enabled(bool),
+    ExpiresAt(DateTime<Utc>),
+}
+
+impl Default for EnvSecret {
+    fn default() -> Self {
+        Self::Enabled(true)
+    }
+}
+
+impl EnvSecret {
+    fn is_active(self, now: DateTime<Utc>) -> bool {
+        match self {
+            Self::Enabled(enabled) => enabled,
+            Self::ExpiresAt(expires_at) => expires_at > now,
+        }
+    }
+
+    fn expires_at(self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Enabled(_) => None,
+            Self::ExpiresAt(expires_at) => Some(expires_at),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct EnvSecrets {
+    frontend: EnvSecret,
+    deploy: EnvSecret,
+    oauth: EnvSecret,
+    webhook: EnvSecret,
+}
+
+impl EnvSecrets {
+    fn get(&self, token: Token) -> EnvSecret {
+        match token {
+            Token::Frontend => self.frontend,
+            Token::Deploy   => self.deploy,
+            Token::Oauth    => self.oauth,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SecretsFile {
+    secrets: Vec<SecretEntry>,
+    webhook_secrets: Vec<WebhookSecretEntry>,
+    env_secrets: EnvSecrets,
+}
+
+/// A secret that authenticates a request as being from its token.
+#[derive(Debug, Clone)]
+pub (super) struct Secret {
+    pub token: Token,
+    pub key: [u8; TOKEN_LEN],
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Secret {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.map_or(true, |expires_at| expires_at > now)
+    }
+}
+
+/// A secret the webhook authenticates its own requests with.
+#[derive(Debug, Clone)]
+struct WebhookSecret {
+    key: [u8; TOKEN_LEN],
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl WebhookSecret {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.map_or(true, |expires_at| expires_at > now)
+    }
+}
+
+/// Everything loaded from the secrets file.
+#[derive(Debug, Clone, Default)]
+struct Loaded {
+    secrets: Vec<Secret>,
+    webhook_secrets: Vec<WebhookSecret>,
+    env_secrets: EnvSecrets,
+}
+
+lazy_static::lazy_static! {
+    static ref LOADED: RwLock<Loaded> = RwLock::new(Loaded::default());
+}
+
+/// An error loading the secrets file. The previously loaded secrets are kept
+/// when this happens.
+#[derive(Debug)]
+pub enum SecretsError {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// The file isn't a list of secrets, or an object of them.
+    Parse(serde_json::Error),
+    /// The secret at this index in this list isn't the right length.
+    BadLength(&'static str, usize),
+}
+
+impl std::fmt::Display for SecretsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Failed to read the secrets file: {e}"),
+            Self::Parse(e) => write!(f, "Failed to parse the secrets file: {e}"),
+            Self::BadLength(list, index) => write!(f, "Secret {index} in `{list}` in the secrets file isn't {TOKEN_LEN} bytes long"),
+        }
+    }
+}
+impl std::error::Error for SecretsError {}
+
+fn parse(contents: &[u8]) -> Result<Loaded, SecretsError> {
+    let value: serde_json::Value = serde_json::from_slice(contents).map_err(SecretsError::Parse)?;
+
+    let file = if value.is_array() {
+        SecretsFile {
+            secrets: serde_json::from_value(value).map_err(SecretsError::Parse)?,
+            ..SecretsFile::default()
+        }
+    } else {
+        serde_json::from_value(value).map_err(SecretsError::Parse)?
+    };
+
+    let key = |list, index, secret: String| -> Result<[u8; TOKEN_LEN], SecretsError> {
+        secret.as_bytes().try_into().map_err(|_| SecretsError::BadLength(list, index))
+    };
+
+    let secrets = file.secrets
+        .into_iter()
+        .enumerate()
+        .map(|(index, SecretEntry { token, secret, expires_at })| Ok(Secret {
+            token,
+            key: key("secrets", index, secret)?,
+            expires_at,
+        }))
+        .collect::<Result<_, SecretsError>>()?;
+
+    let webhook_secrets = file.webhook_secrets
+        .into_iter()
+        .enumerate()
+        .map(|(index, WebhookSecretEntry { secret, expires_at })| Ok(WebhookSecret {
+            key: key("webhook_secrets", index, secret)?,
+            expires_at,
+        }))
+        .collect::<Result<_, SecretsError>>()?;
+
+    Ok(Loaded { secrets, webhook_secrets, env_secrets: file.env_secrets })
+}
+
+fn read(path: &Path) -> Result<Loaded, SecretsError> {
+    let contents = std::fs::read(path).map_err(SecretsError::Io)?;
+    parse(&contents)
+}
+
+fn path() -> Option<PathBuf> {
+    std::env::var_os("AUTH_SECRETS_FILE").map(PathBuf::from)
+}
+
+/// Re-reads the secrets file, returning how many secrets it has. Does nothing
+/// if `AUTH_SECRETS_FILE` isn't set.
+pub fn reload() -> Result<usize, SecretsError> {
+    let Some(path) = path() else { return Ok(0) };
+
+    let loaded = read(&path)?;
+
+    let now = Utc::now();
+    let expired = loaded.secrets.iter().filter(|secret| !secret.is_active(now)).count()
+        + loaded.webhook_secrets.iter().filter(|secret| !secret.is_active(now)).count();
+    if expired > 0 {
+        warn!("{expired} secret(s) in {} have already expired", path.display());
+    }
+    if !loaded.env_secrets.webhook.is_active(now) && !loaded.webhook_secrets.iter().any(|secret| secret.is_active(now)) {
+        warn!("WEBHOOK_AUTH_TOKEN is retired in {}, but there's no active webhook secret to use instead", path.display());
+    }
+
+    let count = loaded.secrets.len() + loaded.webhook_secrets.len();
+    *LOADED.write().unwrap_or_else(PoisonError::into_inner) = loaded;
+
+    info!("Loaded {count} secret(s) from {}", path.display());
+    Ok(count)
+}
+
+/// Gets every active secret for the tokens in `list`, starting with the ones
+/// from the environment that haven't been retired.
+pub (super) fn active(list: &[Token]) -> Vec<Secret> {
+    let now = Utc::now();
+    let loaded = LOADED.read().unwrap_or_else(PoisonError::into_inner);
+
+    let from_env = list
+        .iter()
+        .filter(|&&token| loaded.env_secrets.get(token).is_active(now))
+        .map(|&token| Secret {
+            token,
+            key: super::env_key(token),
+            expires_at: loaded.env_secrets.get(token).expires_at(),
+        });
+
+    let from_file = loaded.secrets
+        .iter()
+        .filter(|secret| list.contains(&secret.token) && secret.is_active(now))
+        .cloned();
+
+    from_env.chain(from_file).collect()
+}
+
+/// Gets the secret the webhook authenticates its own requests with, which is
+/// the last active one in `webhook_secrets`, or else `WEBHOOK_AUTH_TOKEN`.
+///
+/// `WEBHOOK_AUTH_TOKEN` is still used if it's been retired without a
+/// replacement, since requests can't go out unauthenticated.
+pub (super) fn outbound_key() -> [u8; TOKEN_LEN] {
+    let now = Utc::now();
+    let loaded = LOADED.read().unwrap_or_else(PoisonError::into_inner);
+
+    loaded.webhook_secrets
+        .iter()
+        .rev()
+        .find(|secret| secret.is_active(now))
+        .map(|secret| secret.key)
+        .unwrap_or_else(super::webhook_auth)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Reloads the secrets file whenever it changes. Failing to load it is logged,
+/// and the previous secrets are kept until it's fixed.
+pub async fn run_watcher() {
+    let Some(path) = path() else { return };
+    info!("Watching {} for secret changes", path.display());
+
+    let mut last_modified = modified(&path);
+
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+
+        let now_modified = modified(&path);
+        if now_modified == last_modified {
+            continue;
+        }
+        last_modified = now_modified;
+
+        if let Err(e) = reload() {
+            error!("{e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn loaded(json: &str) -> Loaded {
+        match parse(json.as_bytes()) {
+            Ok(loaded) => loaded,
+            Err(e) => panic!("failed to parse {json}: {e}"),
+        }
+    }
+
+    #[test]
+    fn a_bare_list_is_the_token_secrets() {
+        let loaded = loaded(&format!(r#"[{{ "token": "frontend", "secret": "{KEY}" }}]"#));
+
+        assert_eq!(loaded.secrets.len(), 1);
+        assert_eq!(loaded.secrets[0].token, Token::Frontend);
+        assert!(loaded.webhook_secrets.is_empty());
+        assert_eq!(loaded.env_secrets.get(Token::Frontend), EnvSecret::Enabled(true));
+    }
+
+    #[test]
+    fn env_secrets_can_be_disabled_or_expire() {
+        let loaded = loaded(r#"{ "env_secrets": { "frontend": false, "deploy": "2024-06-01T00:00:00Z" } }"#);
+        let Ok(before) = "2024-05-01T00:00:00Z".parse::<DateTime<Utc>>() else { panic!("bad timestamp") };
+        let Ok(after) = "2024-07-01T00:00:00Z".parse::<DateTime<Utc>>() else { panic!("bad timestamp") };
+
+        assert!(!loaded.env_secrets.get(Token::Frontend).is_active(before));
+        assert!(loaded.env_secrets.get(Token::Deploy).is_active(before));
+        assert!(!loaded.env_secrets.get(Token::Deploy).is_active(after));
+        assert!(loaded.env_secrets.get(Token::Oauth).is_active(after));
+        assert!(loaded.env_secrets.webhook.is_active(after));
+    }
+
+    #[test]
+    fn webhook_secrets_are_loaded() {
+        let loaded = loaded(&format!(r#"{{ "webhook_secrets": [{{ "secret": "{KEY}" }}], "env_secrets": {{ "webhook": false }} }}"#));
+
+        assert_eq!(loaded.webhook_secrets.len(), 1);
+        assert_eq!(&loaded.webhook_secrets[0].key[..], KEY.as_bytes());
+        assert_eq!(loaded.env_secrets.webhook, EnvSecret::Enabled(false));
+    }
+
+    #[test]
+    fn bad_secrets_are_rejected() {
+        assert!(matches!(
+            parse(br#"{ "webhook_secrets": [{ "secret": "short" }] }"#),
+            Err(SecretsError::BadLength("webhook_secrets", 0)),
+        ));
+        assert!(matches!(parse(br#"{ "env_secret": {} }"#), Err(SecretsError::Parse(_))));
+        assert!(matches!(parse(br#"{ "env_secrets": { "discord": false } }"#), Err(SecretsError::Parse(_))));
+    }
+}
@@ -0,0 +1,308 @@
+//! Signed requests, as an alternative to sending a token as-is.
+//!
+//! A signed request has an `Authorization` header of the form:
+//! ```text
+//! ARCS-HMAC-SHA256 timestamp=<unix seconds>,nonce=<random string>,signature=<hex>
+//! ```
+//! where the signature is an HMAC-SHA256, keyed with the caller's token, of:
+//! ```text
+//! <METHOD>\n<path>\n<timestamp>\n<nonce>\n<hex SHA-256 of the body>
+//! ```
+//!
+//! The token itself is never sent, and a captured request can't be replayed,
+//! since requests with timestamps too far from now (see
+//! [`crate::env::signing`]) or with a nonce that was already used are
+//! rejected.
+
+use std::collections::HashMap;
+use std::sync::{ Mutex, PoisonError };
+
+use hmac::{ Hmac, Mac };
+use rand::RngCore;
+use sha2::{ Digest, Sha256 };
+
+use crate::env::signing as cfg;
+
+/// The scheme at the start of a signed request's `Authorization` header.
+pub const SCHEME: &str = "ARCS-HMAC-SHA256";
+
+const MIN_NONCE_LEN: usize = 16;
+const MAX_NONCE_LEN: usize = 128;
+
+/// The parts of a request that are signed.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestParts<'a> {
+    /// The HTTP method, e.g. `POST`.
+    pub method: &'a str,
+    /// The path of the URL, without the query.
+    pub path: &'a str,
+    /// The raw body of the request.
+    pub body: &'a [u8],
+}
+
+/// Why a signed request was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The header is missing the timestamp, nonce or signature, or one of them
+    /// is invalid.
+    Malformed,
+    /// The timestamp is too far from now.
+    Stale,
+    /// The signature doesn't match any of the allowed tokens.
+    BadSignature,
+    /// The nonce has been used by an earlier request.
+    ReusedNonce,
+}
+
+impl SignatureError {
+    /// A description of the error, for the body of the 401 response.
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Malformed => "The request signature is malformed",
+            Self::Stale => "The request signature has expired, or its timestamp is in the future",
+            Self::BadSignature => "The request signature doesn't match any allowed token",
+            Self::ReusedNonce => "The request signature's nonce has already been used",
+        }
+    }
+}
+
+fn mac(key: &[u8], parts: RequestParts, timestamp: i64, nonce: &str) -> Hmac<Sha256> {
+    let body_hash = hex::encode(Sha256::digest(parts.body));
+
+    let mut mac = match <Hmac<Sha256>>::new_from_slice(key) {
+        Ok(mac) => mac,
+        Err(_) => unreachable!("HMAC accepts keys of any length"),
+    };
+    mac.update(format!("{}\n{}\n{timestamp}\n{nonce}\n{body_hash}", parts.method.to_ascii_uppercase(), parts.path).as_bytes());
+    mac
+}
+
+/// Signs a request with the key, giving the value of its `Authorization`
+/// header.
+pub fn sign(key: &[u8], parts: RequestParts) -> String {
+    let timestamp = chrono::Utc::now().timestamp();
+
+    let mut nonce = [0; 16];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let nonce = hex::encode(nonce);
+
+    let signature = hex::encode(mac(key, parts, timestamp, &nonce).finalize().into_bytes());
+    format!("{SCHEME} timestamp={timestamp},nonce={nonce},signature={signature}")
+}
+
+struct Signature<'a> {
+    timestamp: i64,
+    nonce: &'a str,
+    signature: Vec<u8>,
+}
+
+fn parse(params: &str) -> Option<Signature<'_>> {
+    let (mut timestamp, mut nonce, mut signature) = (None, None, None);
+
+    for param in params.split(',') {
+        let (key, value) = param.trim().split_once('=')?;
+        match key {
+            "timestamp" => timestamp = Some(value.parse().ok()?),
+            "nonce" => nonce = Some(value),
+            "signature" => signature = Some(hex::decode(value).ok()?),
+            _ => return None,
+        }
+    }
+
+    let nonce = nonce.filter(|nonce| (MIN_NONCE_LEN..=MAX_NONCE_LEN).contains(&nonce.len()))?;
+    Some(Signature { timestamp: timestamp?, nonce, signature: signature? })
+}
+
+lazy_static::lazy_static! {
+    /// The nonces of recent signed requests, along with their timestamps.
+    static ref SEEN_NONCES: Mutex<HashMap<String, i64>> = Mutex::new(HashMap::new());
+}
+
+/// Records the nonce, returning whether it hadn't been used already. Nonces
+/// are forgotten once requests using them would be stale anyway.
+fn use_nonce(nonce: &str, timestamp: i64, now: i64) -> bool {
+    let max_age = i64::from(cfg::max_age_secs());
+
+    let mut seen = SEEN_NONCES.lock().unwrap_or_else(PoisonError::into_inner);
+    seen.retain(|_, seen_at| (now - *seen_at).abs() <= max_age);
+
+    if seen.contains_key(nonce) {
+        return false;
+    }
+    seen.insert(nonce.to_string(), timestamp);
+    true
+}
+
+/// Checks the signed request against each of the keys, returning the index of
+/// the key it was signed with.
+///
+/// `params` is the `Authorization` header after the [`SCHEME`].
+pub fn verify<'k>(keys: impl IntoIterator<Item = &'k [u8]>, params: &str, parts: RequestParts) -> Result<usize, SignatureError> {
+    let Some(Signature { timestamp, nonce, signature }) = parse(params) else {
+        return Err(SignatureError::Malformed);
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    if (now - timestamp).abs() > i64::from(cfg::max_age_secs()) {
+        return Err(SignatureError::Stale);
+    }
+
+    // `verify_slice` compares in constant time. Every key is checked so that
+    // the time taken doesn't depend on which one matched.
+    let matched = keys
+        .into_iter()
+        .map(|key| mac(key, parts, timestamp, nonce).verify_slice(&signature).is_ok())
+        .enumerate()
+        .fold(None, |matched, (index, ok)| matched.or(ok.then_some(index)));
+    let Some(index) = matched else {
+        return Err(SignatureError::BadSignature);
+    };
+
+    if !use_nonce(nonce, timestamp, now) {
+        return Err(SignatureError::ReusedNonce);
+    }
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+    const OTHER_KEY: &[u8] = b"fedcba9876543210fedcba9876543210fedcba9876543210fedcba9876543210";
+
+    const PARTS: RequestParts = RequestParts { method: "POST", path: "/", body: br#"{ "sql": [] }"# };
+
+    /// Strips the scheme off of a header, like [`crate::AuthHeader`] does.
+    fn params(header: &str) -> &str {
+        let Some(params) = header.strip_prefix(SCHEME).and_then(|params| params.strip_prefix(' ')) else {
+            panic!("{header} doesn't start with the scheme")
+        };
+        params
+    }
+
+    /// Signs with a chosen timestamp and nonce, giving the header's params.
+    fn signed_at(key: &[u8], parts: RequestParts, timestamp: i64, nonce: &str) -> String {
+        let signature = hex::encode(mac(key, parts, timestamp, nonce).finalize().into_bytes());
+        format!("timestamp={timestamp},nonce={nonce},signature={signature}")
+    }
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+
+    #[test]
+    fn signed_requests_verify() {
+        let header = sign(KEY, PARTS);
+
+        assert_eq!(verify([OTHER_KEY, KEY], params(&header), PARTS), Ok(1));
+    }
+
+    #[test]
+    fn the_method_is_case_insensitive() {
+        let header = sign(KEY, RequestParts { method: "post", ..PARTS });
+
+        assert_eq!(verify([KEY], params(&header), PARTS), Ok(0));
+    }
+
+    #[test]
+    fn other_keys_dont_verify() {
+        let header = sign(KEY, PARTS);
+
+        assert_eq!(verify([OTHER_KEY], params(&header), PARTS), Err(SignatureError::BadSignature));
+        assert_eq!(verify([], params(&header), PARTS), Err(SignatureError::BadSignature));
+    }
+
+    #[test]
+    fn tampered_requests_dont_verify() {
+        let header = sign(KEY, PARTS);
+        let params = params(&header);
+
+        let tampered = [
+            RequestParts { body: br#"{ "sql": [1] }"#, ..PARTS },
+            RequestParts { body: b"", ..PARTS },
+            RequestParts { path: "/metrics", ..PARTS },
+            RequestParts { method: "GET", ..PARTS },
+        ];
+        for parts in tampered {
+            assert_eq!(verify([KEY], params, parts), Err(SignatureError::BadSignature), "{parts:?}");
+        }
+
+        // Rejected attempts don't use up the nonce
+        assert_eq!(verify([KEY], params, PARTS), Ok(0));
+    }
+
+    #[test]
+    fn tampered_timestamps_dont_verify() {
+        let params = signed_at(KEY, PARTS, now(), "tampered-timestamp-nonce");
+        let tampered = params.replacen("timestamp=", "timestamp=1", 1);
+
+        assert!(matches!(
+            verify([KEY], &tampered, PARTS),
+            Err(SignatureError::BadSignature | SignatureError::Stale),
+        ));
+    }
+
+    #[test]
+    fn stale_requests_are_rejected() {
+        let max_age = i64::from(cfg::max_age_secs());
+
+        let old = signed_at(KEY, PARTS, now() - max_age - 60, "stale-old-nonce-0001");
+        assert_eq!(verify([KEY], &old, PARTS), Err(SignatureError::Stale));
+
+        let future = signed_at(KEY, PARTS, now() + max_age + 60, "stale-future-nonce-01");
+        assert_eq!(verify([KEY], &future, PARTS), Err(SignatureError::Stale));
+    }
+
+    #[test]
+    fn reused_nonces_are_rejected() {
+        let params = signed_at(KEY, PARTS, now(), "reused-nonce-000001");
+
+        assert_eq!(verify([KEY], &params, PARTS), Ok(0));
+        assert_eq!(verify([KEY], &params, PARTS), Err(SignatureError::ReusedNonce));
+
+        // Even when signing a different request
+        let other = RequestParts { path: "/other", ..PARTS };
+        let params = signed_at(KEY, other, now(), "reused-nonce-000001");
+        assert_eq!(verify([KEY], &params, other), Err(SignatureError::ReusedNonce));
+    }
+
+    #[test]
+    fn stale_nonces_are_forgotten() {
+        let max_age = i64::from(cfg::max_age_secs());
+        let now = now();
+
+        assert!(use_nonce("forgotten-nonce-0001", now - 2 * max_age, now));
+        assert!(use_nonce("forgotten-nonce-0001", now - 2 * max_age, now));
+
+        assert!(use_nonce("remembered-nonce-001", now, now));
+        assert!(!use_nonce("remembered-nonce-001", now, now));
+    }
+
+    #[test]
+    fn malformed_params_are_rejected() {
+        let now = now();
+        let valid = signed_at(KEY, PARTS, now, "malformed-nonce-0001");
+        let Some((_, signature)) = valid.rsplit_once("signature=") else { panic!("no signature in {valid}") };
+
+        let malformed = [
+            String::new(),
+            "timestamp".to_string(),
+            format!("nonce=malformed-nonce-0001,signature={signature}"),
+            format!("timestamp={now},signature={signature}"),
+            format!("timestamp={now},nonce=malformed-nonce-0001"),
+            format!("timestamp=soon,nonce=malformed-nonce-0001,signature={signature}"),
+            format!("timestamp={now},nonce=malformed-nonce-0001,signature=not-hex"),
+            format!("timestamp={now},nonce=short,signature={signature}"),
+            format!("timestamp={now},nonce={},signature={signature}", "n".repeat(MAX_NONCE_LEN + 1)),
+            format!("{valid},extra=1"),
+        ];
+        for params in malformed {
+            assert_eq!(verify([KEY], &params, PARTS), Err(SignatureError::Malformed), "{params}");
+        }
+
+        // The params can be spaced out
+        let spaced = valid.replace(',', ", ");
+        assert_eq!(verify([KEY], &spaced, PARTS), Ok(0));
+    }
+}
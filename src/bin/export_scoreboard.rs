@@ -0,0 +1,94 @@
+//! Exports the scoreboard, for submitting to CTFtime at the end of the CTF.
+//!
+//! ```sh
+//! cargo run --bin export_scoreboard -- [ctftime|csv] [--eligible] [--affiliation <name>] [--division <name>]
+//! ```
+//!
+//! Exporting is admin-only, so `EXPORT_ADMIN_ID` and `EXPORT_ADMIN_PASSWORD`
+//! have to be set to an admin's id and password.
+//!
+//! The scoreboard is written to stdout. While the scoreboard is frozen, the
+//! export is of the frozen scoreboard, so lift the freeze first to export the
+//! final one.
+
+use webhook_rs::handlers::Handle as _;
+use webhook_rs::payloads::{
+    incoming::{ ToSql, sql::{ Auth, ExportFormat, ScoreboardFilter, TeamQuery } },
+    outgoing::sql::FromSql,
+};
+
+fn usage() -> ! {
+    eprintln!("Usage: export_scoreboard [ctftime|csv] [--eligible] [--affiliation <name>] [--division <name>]");
+    std::process::exit(2);
+}
+
+fn parse_args() -> (ExportFormat, ScoreboardFilter) {
+    let mut format = ExportFormat::Ctftime;
+    let mut filter = ScoreboardFilter::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "ctftime" => format = ExportFormat::Ctftime,
+            "csv" => format = ExportFormat::Csv,
+            "--eligible" => filter.eligible = Some(true),
+            "--affiliation" => filter.affiliation = Some(args.next().unwrap_or_else(|| usage())),
+            "--division" => filter.division = Some(args.next().unwrap_or_else(|| usage())),
+            _ => usage(),
+        }
+    }
+    (format, filter)
+}
+
+fn admin_credentials() -> (uuid::Uuid, Auth) {
+    let admin_id = std::env::var("EXPORT_ADMIN_ID")
+        .ok()
+        .and_then(|id| id.parse().ok());
+    let password = std::env::var("EXPORT_ADMIN_PASSWORD").ok();
+
+    match (admin_id, password) {
+        (Some(admin_id), Some(password)) => (admin_id, Auth::Pass { password }),
+        _ => {
+            eprintln!("EXPORT_ADMIN_ID and EXPORT_ADMIN_PASSWORD have to be set to an admin's id and password");
+            std::process::exit(2);
+        },
+    }
+}
+
+#[actix_web::main]
+async fn main() {
+    let (format, filter) = parse_args();
+
+    let _ = dotenvy::dotenv();
+    let (admin_id, admin_auth) = admin_credentials();
+
+    if let Err(e) = webhook_rs::env::checks::sql() {
+        eprintln!("Failed to find sql env variables {e}");
+        std::process::exit(1);
+    }
+    if let Err(e) = webhook_rs::start_db_connection().await {
+        eprintln!("Failed to connect to the database: {e}");
+        std::process::exit(1);
+    }
+
+    let query = ToSql::Team(TeamQuery::ExportScoreboard { admin_id, admin_auth, format, filter });
+
+    match query.handle().await {
+        Ok(FromSql::CtftimeScoreboard(scoreboard)) => match serde_json::to_string_pretty(&scoreboard) {
+            Ok(json) => println!("{json}"),
+            Err(e) => {
+                eprintln!("Failed to serialize scoreboard: {e}");
+                std::process::exit(1);
+            },
+        },
+        Ok(FromSql::Csv(csv)) => print!("{csv}"),
+        Ok(other) => {
+            eprintln!("Unexpected response: {other:?}");
+            std::process::exit(1);
+        },
+        Err(e) => {
+            eprintln!("Failed to export scoreboard: {e:?}");
+            std::process::exit(1);
+        },
+    }
+}
@@ -1,6 +1,7 @@
 use webhook_rs::payloads::{
     incoming::Incoming,
     outgoing::Outgoing,
+    outgoing::error::RequestError,
 };
 
 fn main() -> std::io::Result<()> {
@@ -13,6 +14,7 @@ fn main() -> std::io::Result<()> {
 
     let incoming_schema = schema_for!(Incoming);
     let outgoing_schema = schema_for!(Outgoing);
+    let error_schema = schema_for!(RequestError);
 
     create_dir_all(Path::new("./meta/"))?;
 
@@ -26,5 +28,10 @@ fn main() -> std::io::Result<()> {
         to_string_pretty(&outgoing_schema).unwrap(),
     )?;
 
+    write(
+        Path::new("./meta/error.schema.json"),
+        to_string_pretty(&error_schema).unwrap(),
+    )?;
+
     Ok(())
 }
@@ -1,7 +1,8 @@
 //! General purpose environment variables for the webhook server.
 //! 
-//! Check out [discord] and [sql] for more specific environment variables,
-//! and check out [checks] for how to check the variables at runtime.
+//! Check out [discord], [sql], [solve_limits], [competition], and [signing] for more specific
+//! environment variables, and check out [checks] for how to check the
+//! variables at runtime.
 //! 
 //! Auth variables are in an extenally-inaccessible module [crate::auth].
 
@@ -63,6 +64,169 @@ pub (crate) mod sql {
     );
 }
 
+pub (crate) mod solve_limits {
+    //! Limits on how quickly flags can be submitted, to stop teams from
+    //! brute-forcing flags.
+    //! 
+    //! The limits are:
+    //! - Per user ([user], `SOLVE_LIMIT_USER`, default `10/60`)
+    //! - Per team ([team], `SOLVE_LIMIT_TEAM`, default `30/60`)
+    //! - Per team on a single challenge ([team_chall], `SOLVE_LIMIT_TEAM_CHALL`,
+    //!   default `5/60`)
+    //! 
+    //! Each one is optional, and is set as `<attempts>/<seconds>` to allow that
+    //! many attempts in any window of that many seconds, or `off` to disable
+    //! it.
+
+    use crate::logging::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Limit {
+        pub attempts: u32,
+        pub window_secs: u32,
+    }
+
+    fn parse(value: &str) -> Option<Option<Limit>> {
+        if value.trim().eq_ignore_ascii_case("off") {
+            return Some(None);
+        }
+
+        let (attempts, window_secs) = value.split_once('/')?;
+        let limit = Limit {
+            attempts: attempts.trim().parse().ok()?,
+            window_secs: window_secs.trim().parse().ok()?,
+        };
+
+        if limit.attempts == 0 || limit.window_secs == 0 {
+            return None;
+        }
+        Some(Some(limit))
+    }
+
+    fn limit(name: &str, attempts: u32, window_secs: u32) -> Option<Limit> {
+        let default = Limit { attempts, window_secs };
+
+        match std::env::var(name) {
+            Ok(value) => parse(&value).unwrap_or_else(|| {
+                warn!("Invalid value {value:?} for {name}, using the default of {attempts}/{window_secs}");
+                Some(default)
+            }),
+            Err(_) => Some(default),
+        }
+    }
+
+    lazy_static::lazy_static! {
+        static ref USER: Option<Limit> = limit("SOLVE_LIMIT_USER", 10, 60);
+        static ref TEAM: Option<Limit> = limit("SOLVE_LIMIT_TEAM", 30, 60);
+        static ref TEAM_CHALL: Option<Limit> = limit("SOLVE_LIMIT_TEAM_CHALL", 5, 60);
+    }
+
+    pub fn user() -> Option<Limit> { *USER }
+    pub fn team() -> Option<Limit> { *TEAM }
+    pub fn team_chall() -> Option<Limit> { *TEAM_CHALL }
+}
+
+pub (crate) mod competition {
+    //! When the competition runs.
+    //! 
+    //! The times are:
+    //! - The start ([start], `CTF_START`), before which flags aren't accepted
+    //! - The end ([end], `CTF_END`), after which flags aren't accepted
+    //! - The scoreboard freeze ([freeze], `CTF_FREEZE`), after which solves
+    //!   are left out of the public scoreboard
+    //! 
+    //! Each one is optional, and is set as an RFC 3339 timestamp like
+    //! `2024-06-07T16:00:00Z`. The database is expected to store times in UTC.
+
+    use chrono::{ DateTime, NaiveDateTime, Utc };
+
+    use crate::logging::*;
+
+    fn time(name: &str) -> Option<NaiveDateTime> {
+        let value = std::env::var(name).ok()?;
+
+        match DateTime::parse_from_rfc3339(value.trim()) {
+            Ok(time) => Some(time.naive_utc()),
+            Err(e) => {
+                error!("Invalid value {value:?} for {name} ({e}), ignoring it");
+                None
+            },
+        }
+    }
+
+    lazy_static::lazy_static! {
+        static ref START: Option<NaiveDateTime> = time("CTF_START");
+        static ref END: Option<NaiveDateTime> = time("CTF_END");
+        static ref FREEZE: Option<NaiveDateTime> = time("CTF_FREEZE");
+    }
+
+    pub fn start() -> Option<NaiveDateTime> { *START }
+    pub fn end() -> Option<NaiveDateTime> { *END }
+    pub fn freeze() -> Option<NaiveDateTime> { *FREEZE }
+
+    pub fn now() -> NaiveDateTime {
+        Utc::now().naive_utc()
+    }
+
+    /// Whether flags are accepted at `time`.
+    pub fn is_running(time: NaiveDateTime) -> bool {
+        start().map_or(true, |start| time >= start) && end().map_or(true, |end| time < end)
+    }
+
+    /// The time the public scoreboard shows, if it's frozen at `time`.
+    pub fn frozen_at(time: NaiveDateTime) -> Option<NaiveDateTime> {
+        freeze().filter(|freeze| time >= *freeze)
+    }
+}
+
+pub (crate) mod signing {
+    //! Settings for signed requests (see [crate::AuthHeader]).
+    //! 
+    //! These are:
+    //! - Whether incoming requests have to be signed, instead of sending a
+    //!   token as-is ([required], `REQUIRE_SIGNED_REQUESTS`, default `false`)
+    //! - Whether requests to the frontend and deploy servers are signed
+    //!   ([sign_outbound], `SIGN_OUTBOUND_REQUESTS`, default `false`)
+    //! - How old a signed request can be before it's rejected ([max_age_secs],
+    //!   `SIGNATURE_MAX_AGE_SECS`, default `300`)
+
+    use crate::logging::*;
+
+    fn flag(name: &str) -> bool {
+        match std::env::var(name) {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" => true,
+                "false" | "0" | "no" | "" => false,
+                _ => {
+                    warn!("Invalid value {value:?} for {name}, using the default of false");
+                    false
+                },
+            },
+            Err(_) => false,
+        }
+    }
+
+    fn max_age(name: &str, default: u32) -> u32 {
+        match std::env::var(name) {
+            Ok(value) => value.trim().parse().ok().filter(|secs| *secs > 0).unwrap_or_else(|| {
+                warn!("Invalid value {value:?} for {name}, using the default of {default}");
+                default
+            }),
+            Err(_) => default,
+        }
+    }
+
+    lazy_static::lazy_static! {
+        static ref REQUIRED: bool = flag("REQUIRE_SIGNED_REQUESTS");
+        static ref SIGN_OUTBOUND: bool = flag("SIGN_OUTBOUND_REQUESTS");
+        static ref MAX_AGE_SECS: u32 = max_age("SIGNATURE_MAX_AGE_SECS", 300);
+    }
+
+    pub fn required() -> bool { *REQUIRED }
+    pub fn sign_outbound() -> bool { *SIGN_OUTBOUND }
+    pub fn max_age_secs() -> u32 { *MAX_AGE_SECS }
+}
+
 pub mod checks {
     //! Functions to assert the presence and validity of the environment
     //! variables at runtime.
@@ -0,0 +1,381 @@
+//! An audit log of every request that changes something.
+//!
+//! The audited queries of a request are picked out before it's handled, and
+//! written to the `audit_log` table along with their results. SQL queries are
+//! audited with [`PendingSql`], in the same transaction as the query itself so
+//! that an entry is committed exactly when the changes it describes are. Each
+//! step of a sequence gets its own entry. Deploy and Discord queries can't be
+//! undone, so they're audited with [`Pending`] once they're done. Read-only
+//! queries (and frontend syncs, which only refresh the frontend's cache) aren't
+//! audited.
+//!
+//! Each entry holds the query itself, with secrets such as passwords and flags
+//! redacted (see [`REDACTED_KEYS`]), and the ids it mentions or created.
+
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{ Acquire, PgConnection };
+use uuid::Uuid;
+
+use crate::Token;
+use crate::logging::*;
+use crate::payloads::incoming::{ Batchable, Incoming, ToDeploy, ToSql };
+use crate::payloads::incoming::sql::{ AuditOutcome, ChallQuery, OutboxQuery, SolveQuery, TeamQuery, UserQuery };
+use crate::payloads::outgoing::Outgoing;
+use crate::payloads::outgoing::sql::{ FromSql, FromSqlErr };
+
+use super::OutgoingErr;
+use super::permissions::operation_name;
+use super::sql::prepared::audit as queries;
+use queries::AuditInput;
+
+/// The keys whose values are never written to the audit log.
+pub const REDACTED_KEYS: &[&str] = &[
+    "password", "team_pass", "oauth_allow_token",
+    "flag", "accepted_flags", "flag_secret", "flag_guess",
+];
+
+const REDACTED: &str = "[redacted]";
+
+/// Whether the query changes anything, as opposed to only reading.
+fn sql_is_mutating(query: &ToSql) -> bool {
+    match query {
+        ToSql::User(query) => match query {
+            UserQuery::CreateNewUser { .. } | UserQuery::Promote { .. } |
+            UserQuery::UpdateUserAuth { .. } | UserQuery::JoinTeam { .. } |
+            UserQuery::SetBanned { .. } | UserQuery::Delete { .. } => true,
+
+            UserQuery::CheckUsernameAvailability { .. } | UserQuery::CheckUserAuth { .. } |
+            UserQuery::GetUser { .. } | UserQuery::GetAllUsers => false,
+        },
+        ToSql::Team(query) => match query {
+            TeamQuery::CreateNewTeam { .. } | TeamQuery::UpdateTeam { .. } |
+            TeamQuery::SetBanned { .. } | TeamQuery::SetHidden { .. } | TeamQuery::Delete { .. } |
+            TeamQuery::UnfreezeScoreboard { .. } | TeamQuery::RefreezeScoreboard { .. } |
+            TeamQuery::UnlockHint { .. } => true,
+
+            TeamQuery::CheckTeamnameAvailability { .. } | TeamQuery::GetTeam { .. } | TeamQuery::GetAllTeams |
+            TeamQuery::GetTopTeams { .. } | TeamQuery::GetTopTeamsScoreHistory { .. } |
+            TeamQuery::GetLiveTopTeams { .. } | TeamQuery::GetLiveTopTeamsScoreHistory { .. } |
+            TeamQuery::ExportScoreboard { .. } | TeamQuery::GetUnlockedHints { .. } => false,
+        },
+        ToSql::Chall(query) => match query {
+            ChallQuery::CreateChallenge { .. } | ChallQuery::UpdateChallenge { .. } |
+            ChallQuery::DeleteChallenge { .. } => true,
+
+            ChallQuery::GetChallenge { .. } | ChallQuery::GetAllChallenges |
+            ChallQuery::GetAvailableChallenges { .. } | ChallQuery::GetTeamFlag { .. } => false,
+        },
+        ToSql::Solve(query) => match query {
+            SolveQuery::AttemptSolve { .. } | SolveQuery::ClearAllSolvesForChallenge { .. } |
+            SolveQuery::DeleteSolve { .. } | SolveQuery::RestoreSolve { .. } => true,
+
+            SolveQuery::GetAllSolves | SolveQuery::GetSolve { .. } |
+            SolveQuery::GetAllSolvesByChall { .. } | SolveQuery::GetAllSolvesByTeam { .. } |
+            SolveQuery::GetAllSolvesByUser { .. } | SolveQuery::GetDeletedSolves { .. } => false,
+        },
+        ToSql::Outbox(query) => match query {
+            OutboxQuery::Replay { .. } => true,
+            OutboxQuery::List { .. } => false,
+        },
+        ToSql::Audit(_) => false,
+        ToSql::Sequence(queries) => queries.iter().any(sql_is_mutating),
+    }
+}
+
+fn deploy_is_mutating(query: &ToDeploy) -> bool {
+    match query {
+        ToDeploy::Deploy { .. } | ToDeploy::Remove { .. } | ToDeploy::ModifyMeta { .. } => true,
+        ToDeploy::Poll { .. } | ToDeploy::ListChalls => false,
+    }
+}
+
+/// Replaces the values of any [`REDACTED_KEYS`] in the value.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => for (key, value) in map.iter_mut() {
+            if REDACTED_KEYS.contains(&key.as_str()) {
+                *value = REDACTED.into();
+            } else {
+                redact(value);
+            }
+        },
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => (),
+    }
+}
+
+/// Collects every id in the value. The admin making the request is left out,
+/// since it's recorded as the actor instead.
+fn collect_ids(value: &Value, ids: &mut Vec<Uuid>) {
+    match value {
+        Value::Object(map) => for (key, value) in map {
+            if key != "admin_id" {
+                collect_ids(value, ids);
+            }
+        },
+        Value::Array(values) => values.iter().for_each(|value| collect_ids(value, ids)),
+        Value::String(s) => if let Ok(id) = s.parse() {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        },
+        _ => (),
+    }
+}
+
+fn find_actor(value: &Value) -> Option<Uuid> {
+    match value {
+        Value::Object(map) => map
+            .get("admin_id")
+            .and_then(Value::as_str)
+            .and_then(|s| s.parse().ok())
+            .or_else(|| map.values().find_map(find_actor)),
+        Value::Array(values) => values.iter().find_map(find_actor),
+        _ => None,
+    }
+}
+
+/// An audited query that hasn't been handled yet.
+#[derive(Debug, Clone)]
+struct PendingEntry {
+    target: &'static str,
+    /// The index of the query in its target's batch, or of the step in its
+    /// sequence.
+    index: usize,
+
+    operation: String,
+    params: Value,
+    actor_id: Option<Uuid>,
+    affected_ids: Vec<Uuid>,
+}
+
+impl PendingEntry {
+    fn new<T: Serialize>(target: &'static str, index: usize, query: &T) -> Option<Self> {
+        let mut params = match serde_json::to_value(query) {
+            Ok(params) => params,
+            Err(e) => {
+                error!("Failed to serialize {target} query for the audit log: {e}");
+                return None;
+            }
+        };
+        redact(&mut params);
+
+        let mut affected_ids = vec![];
+        collect_ids(&params, &mut affected_ids);
+
+        Some(Self {
+            target,
+            index,
+            operation: operation_name(target, &params),
+            actor_id: find_actor(&params),
+            affected_ids,
+            params,
+        })
+    }
+
+    /// Writes the entry to the audit log, along with the result of its query.
+    async fn insert(self, ctx: &mut PgConnection, token: Token, result: Option<&QueryResult>) -> Result<(), sqlx::Error> {
+        let Self { operation, params, actor_id, mut affected_ids, .. } = self;
+
+        let status_code = result.map_or(500, |result| result.status_code);
+
+        if let Some(id) = result.and_then(|result| result.id) {
+            if !affected_ids.contains(&id) {
+                affected_ids.push(id);
+            }
+        }
+
+        let outcome = if (200..300).contains(&status_code) {
+            AuditOutcome::Success
+        } else {
+            AuditOutcome::Error
+        };
+
+        trace!("Auditing {operation} ({status_code})");
+        queries::insert_entry(ctx, AuditInput {
+            token, operation, params,
+            actor_id, affected_ids,
+            outcome, status_code,
+        }).await?;
+        Ok(())
+    }
+}
+
+/// The audited deploy and Discord queries of a request, waiting on their
+/// results.
+#[derive(Debug, Clone)]
+pub struct Pending(Vec<PendingEntry>);
+
+fn collect_pending<T: Serialize>(
+    entries: &mut Vec<PendingEntry>,
+    target: &'static str,
+    queries: &Option<Batchable<T>>,
+    is_mutating: fn(&T) -> bool,
+) {
+    for (index, query) in queries.iter().flat_map(Batchable::iter).enumerate() {
+        if !is_mutating(query) {
+            continue;
+        }
+        entries.extend(PendingEntry::new(target, index, query));
+    }
+}
+
+/// The status code of a result, and the id of whatever it created or changed.
+struct QueryResult {
+    status_code: u16,
+    id: Option<Uuid>,
+}
+
+impl QueryResult {
+    fn success<T: Serialize>(payload: &T) -> Self {
+        Self {
+            status_code: 200,
+            id: serde_json::to_value(payload)
+                .ok()
+                .and_then(|value| value.get("data")?.get("id")?.as_str()?.parse().ok()),
+        }
+    }
+}
+
+fn results<T: Serialize, E: OutgoingErr>(results: &Option<Batchable<Result<T, E>>>) -> Vec<QueryResult> {
+    results
+        .iter()
+        .flat_map(Batchable::iter)
+        .map(|result| match result {
+            Ok(payload) => QueryResult::success(payload),
+            Err(e) => QueryResult { status_code: e.status_code(), id: None },
+        })
+        .collect()
+}
+
+/// The audited steps of a SQL query, waiting on its result. A query that isn't
+/// a sequence is treated as a sequence of one step.
+#[derive(Debug, Clone)]
+pub (crate) struct PendingSql(Vec<PendingEntry>);
+
+impl PendingSql {
+    /// Picks out the steps of the query that need auditing.
+    pub (crate) fn new(query: &ToSql) -> Self {
+        let steps = match query {
+            ToSql::Sequence(steps) => steps.as_slice(),
+            query => std::slice::from_ref(query),
+        };
+
+        let entries = steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| sql_is_mutating(step))
+            .filter_map(|(index, step)| PendingEntry::new("sql", index, step))
+            .collect();
+        Self(entries)
+    }
+
+    /// Writes the audited steps to the audit log along with the query's result.
+    /// If the query failed, none of its steps were committed, so they're all
+    /// recorded with its error.
+    ///
+    /// This is meant to be run in the query's transaction, and is done in a
+    /// savepoint so that failing to write the entries doesn't abort it.
+    pub (crate) async fn record(self, ctx: &mut PgConnection, token: Token, result: Result<&FromSql, &FromSqlErr>) -> Result<(), sqlx::Error> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        let results = match result {
+            Ok(FromSql::Sequence(payloads)) => payloads.iter().map(QueryResult::success).collect(),
+            Ok(payload) => vec![QueryResult::success(payload)],
+            Err(_) => vec![],
+        };
+        let failed = result.err().map(|e| QueryResult { status_code: e.status_code(), id: None });
+
+        let mut savepoint = ctx.begin().await?;
+        for entry in self.0 {
+            let result = failed.as_ref().or_else(|| results.get(entry.index));
+            entry.insert(&mut savepoint, token, result).await?;
+        }
+        savepoint.commit().await
+    }
+}
+
+impl Pending {
+    /// Picks out the deploy and Discord queries of the request that need
+    /// auditing. SQL queries are audited as they're handled, see
+    /// [`PendingSql`].
+    pub fn new(incoming: &Incoming) -> Self {
+        let mut entries = vec![];
+        collect_pending(&mut entries, "deploy", &incoming.depl, deploy_is_mutating);
+        collect_pending(&mut entries, "discord", &incoming.disc, |_| true);
+        Self(entries)
+    }
+
+    /// Writes the audited queries to the audit log along with their results.
+    /// Failing to write them is logged, but doesn't fail the request.
+    pub async fn record(self, token: Token, outgoing: &Outgoing) {
+        if self.0.is_empty() {
+            return;
+        }
+
+        let deploy = results(&outgoing.depl);
+        let discord = results(&outgoing.disc);
+
+        let write = async {
+            let mut transaction = crate::sql::transaction().await?;
+
+            for entry in self.0 {
+                let result = match entry.target {
+                    "deploy" => deploy.get(entry.index),
+                    "discord" => discord.get(entry.index),
+                    _ => None,
+                };
+                entry.insert(&mut transaction, token, result).await?;
+            }
+
+            transaction.commit().await
+        };
+
+        if let Err(e) = write.await {
+            error!("Failed to write to the audit log: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payloads::incoming::sql::Auth;
+
+    fn admin_auth() -> Auth {
+        Auth::Pass { password: "hunter2".to_string() }
+    }
+
+    #[test]
+    fn sequences_are_audited_step_by_step() {
+        let (first_admin, second_admin, user_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let query = ToSql::Sequence(vec![
+            ToSql::User(UserQuery::GetAllUsers),
+            ToSql::User(UserQuery::Delete { admin_id: first_admin, admin_auth: admin_auth(), id: user_id }),
+            ToSql::Team(TeamQuery::RefreezeScoreboard { admin_id: second_admin, admin_auth: admin_auth(), limit: 10 }),
+        ]);
+
+        let PendingSql(entries) = PendingSql::new(&query);
+        let steps: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.index, entry.operation.as_str(), entry.actor_id))
+            .collect();
+        assert_eq!(steps, [
+            (1, "sql.user.delete", Some(first_admin)),
+            (2, "sql.team.refreeze", Some(second_admin)),
+        ]);
+
+        assert_eq!(entries[0].affected_ids, [user_id]);
+        assert!(!entries[0].params.to_string().contains("hunter2"));
+    }
+
+    #[test]
+    fn read_only_queries_arent_audited() {
+        let PendingSql(entries) = PendingSql::new(&ToSql::User(UserQuery::GetAllUsers));
+        assert!(entries.is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use async_trait::async_trait;
 
 use crate::logging::*;
 use crate::http_client::DEFAULT;
+use reqwest::header::{ AUTHORIZATION, CONTENT_TYPE };
 
 use crate::payloads::incoming::ToDeploy;
 use crate::payloads::incoming::deploy::ChallIdentifier;
@@ -10,6 +11,9 @@ use crate::payloads::outgoing::deploy::{FromDeploy, FromDeployErr};
 
 use super::sql::{ get_chall_id_by_source_folder, get_chall_source_folder_by_id };
 
+use crate::payloads::outgoing::sql::OutboxTarget;
+
+use super::outbox::Deliverable;
 use super::{Handle, ResponseFrom};
 
 #[async_trait]
@@ -91,12 +95,18 @@ impl Handle for ToDeploy {
             "modifications": modifications,
         });
 
+        let url = crate::env::deploy_address();
+        let body = body.to_string().into_bytes();
+
+        let started = std::time::Instant::now();
         let response = DEFAULT
-            .post(crate::env::deploy_address())
-            .bearer_auth(String::from_utf8_lossy(&crate::auth::webhook_auth()))
-            .json(&body)
+            .post(&url)
+            .header(AUTHORIZATION, crate::auth::outbound_authorization("POST", &url, &body))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
             .send()
             .await;
+        crate::metrics::observe_outbound("deploy", started, &response);
 
 
         match response {
@@ -135,4 +145,29 @@ impl Handle for ToDeploy {
             },
         }
     }
-}
\ No newline at end of file
+}
+
+/// Deploy requests only go through the outbox when they're queued as part of a
+/// SQL query, such as removing a deleted challenge's deployment.
+#[async_trait]
+impl Deliverable for ToDeploy {
+    const TARGET: OutboxTarget = OutboxTarget::Deploy;
+
+    type Sent = FromDeploy;
+    type Error = FromDeployErr;
+
+    async fn deliver(self) -> Result<FromDeploy, FromDeployErr> {
+        self.handle().await
+    }
+
+    /// A bad response means the deploy server did get the request, so only
+    /// requests that didn't make it or that the deploy server failed on are
+    /// retried.
+    fn is_retryable(error: &FromDeployErr) -> bool {
+        match error {
+            FromDeployErr::BadSend => true,
+            FromDeployErr::DeployServer { code, .. } => *code == 429 || *code >= 500,
+            FromDeployErr::BadResponse | FromDeployErr::DbError => false,
+        }
+    }
+}
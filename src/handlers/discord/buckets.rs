@@ -0,0 +1,110 @@
+//! Per-webhook rate limit buckets for discord.
+//!
+//! Discord reports how many requests are left for a webhook with the
+//! `X-RateLimit-*` headers on every response, and how long to back off for in
+//! the body of a 429. Both are recorded here, keyed by webhook url, so that a
+//! send can wait for its bucket to reset instead of being rejected.
+
+use std::collections::HashMap;
+use std::sync::{ Mutex, MutexGuard, PoisonError };
+use std::time::{ Duration, Instant };
+
+use reqwest::header::HeaderMap;
+
+use crate::payloads::outgoing::discord::BucketState;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Bucket {
+    limit: Option<u32>,
+    remaining: Option<u32>,
+    reset_at: Option<Instant>,
+}
+
+impl Bucket {
+    fn wait_time(&self, now: Instant) -> Option<Duration> {
+        match (self.remaining, self.reset_at) {
+            (Some(0), Some(reset_at)) if reset_at > now => Some(reset_at - now),
+            _ => None,
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref BUCKETS: Mutex<HashMap<String, Bucket>> = Mutex::new(HashMap::new());
+    static ref GLOBAL_RESET: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// A poisoned lock only means that another thread panicked mid-update, and the
+/// bucket state is still usable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn header<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// How long a message to `url` has to wait before being sent, if at all.
+pub fn wait_time(url: &str) -> Option<Duration> {
+    let now = Instant::now();
+
+    let bucket_wait = lock(&BUCKETS).get(url).and_then(|bucket| bucket.wait_time(now));
+    let global_wait = lock(&GLOBAL_RESET)
+        .filter(|reset_at| *reset_at > now)
+        .map(|reset_at| reset_at - now);
+
+    bucket_wait.max(global_wait)
+}
+
+/// Updates the bucket for `url` from the `X-RateLimit-*` headers of a response.
+pub fn update(url: &str, headers: &HeaderMap) {
+    let limit = header(headers, "X-RateLimit-Limit");
+    let remaining = header(headers, "X-RateLimit-Remaining");
+    let reset_after: Option<f64> = header(headers, "X-RateLimit-Reset-After");
+
+    if limit.is_none() && remaining.is_none() && reset_after.is_none() {
+        return;
+    }
+
+    let reset_at = reset_after
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(|secs| Instant::now() + Duration::from_secs_f64(secs));
+
+    let mut buckets = lock(&BUCKETS);
+    let bucket = buckets.entry(url.to_string()).or_default();
+
+    bucket.limit = limit.or(bucket.limit);
+    bucket.remaining = remaining;
+    bucket.reset_at = reset_at;
+}
+
+/// Marks the bucket for `url` (or every bucket, if `global`) as exhausted for
+/// `retry_after`, after discord responded with a 429.
+pub fn limited(url: &str, retry_after: Duration, global: bool) {
+    let reset_at = Instant::now() + retry_after;
+
+    if global {
+        let mut global_reset = lock(&GLOBAL_RESET);
+        *global_reset = (*global_reset).max(Some(reset_at));
+    }
+
+    let mut buckets = lock(&BUCKETS);
+    let bucket = buckets.entry(url.to_string()).or_default();
+
+    bucket.remaining = Some(0);
+    bucket.reset_at = bucket.reset_at.max(Some(reset_at));
+}
+
+/// Gets the last known state of the bucket for `url`.
+pub fn state(url: &str) -> Option<BucketState> {
+    let now = Instant::now();
+
+    lock(&BUCKETS).get(url).map(|bucket| BucketState {
+        limit: bucket.limit,
+        remaining: bucket.remaining,
+        reset_after: bucket.reset_at.map(|reset_at| reset_at.saturating_duration_since(now).as_secs_f64()),
+    })
+}
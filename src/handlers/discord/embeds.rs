@@ -0,0 +1,118 @@
+//! Rendering of discord messages as embeds.
+//!
+//! Embeds are subject to discord's length limits, so everything put into them
+//! is truncated to fit. See [discord's docs][limits] for the limits.
+//!
+//! [limits]: https://discord.com/developers/docs/resources/channel#embed-object-embed-limits
+
+use serde_json::{ json, Value };
+
+use crate::payloads::incoming::discord::AlertLevel;
+
+const MAX_TITLE_LEN: usize = 256;
+const MAX_DESCRIPTION_LEN: usize = 4096;
+const MAX_FIELDS: usize = 25;
+const MAX_FIELD_NAME_LEN: usize = 256;
+const MAX_FIELD_VALUE_LEN: usize = 1024;
+
+/// String values at most this long are shown side by side.
+const MAX_INLINE_LEN: usize = 40;
+
+const FIRST_BLOOD_COLOUR: u32 = 0xB71C1C;
+
+fn level_colour(level: &AlertLevel) -> u32 {
+    match level {
+        AlertLevel::Info => 0x3498DB,
+        AlertLevel::Warn => 0xF1C40F,
+        AlertLevel::Erro => 0xE74C3C,
+    }
+}
+
+/// Cuts `s` down to at most `max` characters, marking it with an ellipsis if
+/// anything was cut.
+fn truncate(s: &str, max: usize) -> String {
+    if s.chars().count() <= max {
+        return s.to_string();
+    }
+    let mut truncated: String = s.chars().take(max.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn timestamp() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+fn field(name: &str, value: &str, inline: bool) -> Value {
+    // Discord rejects fields with empty names or values
+    let name = if name.is_empty() { "\u{200b}" } else { name };
+    let value = if value.is_empty() { "\u{200b}" } else { value };
+
+    json!({
+        "name": truncate(name, MAX_FIELD_NAME_LEN),
+        "value": truncate(value, MAX_FIELD_VALUE_LEN),
+        "inline": inline,
+    })
+}
+
+/// Turns the `data` of a developer message into embed fields. Objects get one
+/// field per key, and anything else (other than `null`) gets a single field.
+fn data_fields(data: &Value) -> Vec<Value> {
+    let entries: Vec<(&str, &Value)> = match data {
+        Value::Null => vec![],
+        Value::Object(map) => map.iter().map(|(key, value)| (key.as_str(), value)).collect(),
+        other => vec![("Data", other)],
+    };
+
+    entries
+        .into_iter()
+        .take(MAX_FIELDS)
+        .map(|(name, value)| match value {
+            Value::String(s) => field(name, s, s.chars().count() <= MAX_INLINE_LEN),
+            Value::Null | Value::Bool(_) | Value::Number(_) => field(name, &value.to_string(), true),
+            Value::Array(_) | Value::Object(_) => {
+                let pretty = serde_json::to_string_pretty(value).unwrap_or_default();
+                // Leave room for the code block around it
+                let pretty = truncate(&pretty, MAX_FIELD_VALUE_LEN - 16);
+                field(name, &format!("```json\n{pretty}\n```"), false)
+            },
+        })
+        .collect()
+}
+
+/// Renders a developer alert.
+pub fn developer(level: &AlertLevel, message: &str, data: &Value) -> Value {
+    json!({
+        "title": truncate(&format!("{} alert", level.to_string().trim()), MAX_TITLE_LEN),
+        "description": truncate(message, MAX_DESCRIPTION_LEN),
+        "color": level_colour(level),
+        "fields": data_fields(data),
+        "timestamp": timestamp(),
+    })
+}
+
+/// Renders a first blood announcement. The link is omitted if it isn't known.
+pub fn first_blood(
+    chall_name: &str, team: &str, user: &str,
+    points: Option<i32>, categories: &[String], link: Option<String>,
+) -> Value {
+    let mut fields = Vec::with_capacity(2);
+    if let Some(points) = points {
+        fields.push(field("Points", &points.to_string(), true));
+    }
+    if !categories.is_empty() {
+        fields.push(field("Categories", &categories.join(", "), true));
+    }
+
+    let mut embed = json!({
+        "title": truncate(&format!("First 🩸 on {chall_name}!"), MAX_TITLE_LEN),
+        "description": truncate(&format!("Solved by `{user}` from `{team}`"), MAX_DESCRIPTION_LEN),
+        "color": FIRST_BLOOD_COLOUR,
+        "fields": fields,
+        "timestamp": timestamp(),
+    });
+    if let Some(link) = link {
+        embed["url"] = link.into();
+    }
+    embed
+}
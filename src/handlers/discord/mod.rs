@@ -0,0 +1,372 @@
+use async_trait::async_trait;
+
+use crate::http_client::DEFAULT;
+use crate::payloads::incoming::{
+    ToDiscord,
+    discord::ParticipantMessage,
+};
+use crate::payloads::outgoing::discord::{ FromDiscord, FromDiscordErr };
+
+use crate::env::discord as disc_env;
+
+use crate::logging::*;
+
+use crate::payloads::outgoing::sql::OutboxTarget;
+
+use super::outbox::{ self, Deliverable, Outcome };
+use super::{Handle, ResponseFrom};
+
+use std::borrow::Cow;
+use std::concat;
+use std::fmt::Display;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+mod buckets;
+mod embeds;
+
+/// The longest a send will wait for a rate limit to reset before leaving the
+/// message to the outbox instead.
+const MAX_INLINE_WAIT: Duration = Duration::from_secs(2);
+
+/// The number of requests made for a message before giving up on it, if
+/// discord keeps rate limiting it.
+const MAX_SEND_ATTEMPTS: u32 = 2;
+
+/// Contains the details for a generic message to be sent to discord.
+#[derive(Debug, Clone)]
+struct PayloadDetails {
+    /// The webhook URL to send the message to
+    url: Cow<'static, str>,
+
+    /// The username the message should be sent with.
+    username: Cow<'static, str>,
+    
+    /// The content of the message, as plain text
+    message: String,
+
+    /// A richer version of the message. This is sent in place of `message`
+    /// when present, and `message` is only sent if discord rejects it.
+    rich: Option<RichMessage>,
+}
+
+/// A message rendered as an embed.
+#[derive(Debug, Clone)]
+struct RichMessage {
+    /// Text sent alongside the embed. Pings only work from here.
+    content: String,
+    embed: serde_json::Value,
+}
+
+/// Gets the link to a challenge on the frontend.
+/// 
+/// This uses `DISCORD_CHALL_LINK_BASE` if it's set, since the frontend address
+/// the webhook talks to isn't necessarily the one participants use.
+fn chall_link(chall_id: uuid::Uuid) -> String {
+    let base = std::env::var("DISCORD_CHALL_LINK_BASE")
+        .unwrap_or_else(|_| crate::env::frontend_address().to_string());
+
+    format!("{}/challenges/{chall_id}", base.trim_end_matches('/'))
+}
+
+/// A list of role IDs to send the messages to. When formatted with `Display`,
+/// it prints the formatted pings using its internal IDs.
+struct Pings(Vec<&'static str>);
+
+impl Display for Pings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for role_id in self.0.iter() {
+            write!(f, "<@&{role_id}> ")?;
+        }
+        Ok(())
+    }
+}
+
+impl ToDiscord {
+    /// Gets the webhook url the message will be sent to.
+    fn webhook_url(&self) -> Cow<'static, str> {
+        match self {
+            ToDiscord::Developer(dev_message) => if dev_message.include_chall_writers {
+                disc_env::chall_writer_url().into()
+            } else {
+                disc_env::admin_url().into()
+            },
+            ToDiscord::Participant(_) => disc_env::participant_url().into(),
+        }
+    }
+
+    /// Get a [`PayloadDetails`] struct containing the:
+    /// - Webhook url
+    /// - "Bot" username
+    /// - Message content
+    /// - Embed, if the message has a rich form
+    fn get_payload_details(self) -> PayloadDetails {
+        let url = self.webhook_url();
+
+        match self {
+            ToDiscord::Developer(dev_message) => {
+                debug!("Discord req is a developer req");
+
+                let pings = if dev_message.include_chall_writers {
+                    vec![disc_env::chall_writer_role(), disc_env::admin_role()]
+                } else {
+                    vec![disc_env::admin_role()]
+                };
+
+                let pings = Pings(pings);
+
+                let rich = RichMessage {
+                    content: pings.to_string(),
+                    embed: embeds::developer(&dev_message.level, &dev_message.message, &dev_message.data),
+                };
+
+                let message = format!(
+                    concat!(
+                        "-------------------", '\n',
+                        "# Urgency: {}", '\n',
+                        "{}", '\n',
+                        "{}",
+                    ),
+                    dev_message.level,
+                    pings,
+                    dev_message.message,
+                );
+
+                PayloadDetails {
+                    url,
+                    username: "ARCS Alerts".into(),
+                    message,
+                    rich: Some(rich),
+                }
+            }
+            ToDiscord::Participant(message) => {
+                debug!("Discord req is a developer req");
+
+                let username = std::env::var("DISCORD_BOT_NAME").ok().map(Into::into);
+                let username = username.unwrap_or("CTF Updates".into());
+                
+                let (message, rich) = match message {
+                    ParticipantMessage::Alert { message } => (message, None),
+                    ParticipantMessage::FirstBlood { chall_name, team, user, chall_id, points, categories } => {
+                        let team = team.replace('`', "'");
+                        let user = user.replace('`', "'");
+
+                        let rich = RichMessage {
+                            content: String::new(),
+                            embed: embeds::first_blood(
+                                &chall_name, &team, &user,
+                                points, &categories, chall_id.map(chall_link),
+                            ),
+                        };
+                        let message = format!("First :drop_of_blood: by `{user}` from `{team}` on challenge `{chall_name}`!");
+
+                        (message, Some(rich))
+                    }
+                };
+                PayloadDetails {
+                    url,
+                    username,
+                    message,
+                    rich,
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Handle for ToDiscord {
+    type SuccessPayload = FromDiscord;
+    type ErrorPayload = FromDiscordErr;
+    async fn handle(self) -> ResponseFrom<ToDiscord> {
+        trace!("Handling discord webhook req");
+
+        let url = self.webhook_url();
+
+        match outbox::send_now(self).await {
+            Outcome::Sent(delay) => Ok(FromDiscord::Sent {
+                delayed_secs: delay.as_secs_f64(),
+                bucket: buckets::state(&url),
+            }),
+            Outcome::Queued(outbox_id) => Ok(FromDiscord::Queued {
+                outbox_id,
+                bucket: buckets::state(&url),
+            }),
+            Outcome::Failed(err) => Err(err),
+        }
+    }
+}
+
+/// The body discord responds with alongside a 429.
+#[derive(Debug, Clone, Deserialize)]
+struct RateLimitBody {
+    retry_after: f64,
+    #[serde(default)]
+    global: bool,
+}
+
+/// Makes a single request to the discord webhook at `url`, recording the rate
+/// limit headers of the response.
+async fn send(url: &str, body: &serde_json::Value) -> Result<(), FromDiscordErr> {
+    let started = std::time::Instant::now();
+    let response = DEFAULT
+        .post(url)
+        .json(body)
+        .send()
+        .await;
+    crate::metrics::observe_outbound("discord", started, &response);
+
+
+    match response {
+        Ok(response) => {
+            buckets::update(url, response.headers());
+
+            if response.status().is_success() {
+                info!("Discord webhook req successful");
+                return Ok(());
+            }
+
+            warn!("Discord webhook req failed");
+
+            let status_code = response.status().as_u16();
+            let status_message = response
+                .headers()
+                .get("Reason-Phrase")
+                .and_then(|v|  v.to_str().ok())
+                .map(str::to_string)
+                .or_else(|| response.status().canonical_reason().map(str::to_string))
+                .unwrap_or_default();
+            let retry_after_header = response
+                .headers()
+                .get("Retry-After")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<f64>().ok());
+
+            let err = match response.bytes().await {
+                Ok(body) => {
+                    let retry_after = if status_code == 429 {
+                        let parsed = serde_json::from_slice::<RateLimitBody>(&body).ok();
+                        let global = parsed.as_ref().map_or(false, |parsed| parsed.global);
+                        let retry_after = parsed
+                            .map(|parsed| parsed.retry_after)
+                            .or(retry_after_header)
+                            .filter(|secs| secs.is_finite() && *secs >= 0.0);
+
+                        if let Some(secs) = retry_after {
+                            warn!("Rate limited by discord for {secs:.2}s{}", if global { " (global)" } else { "" });
+                            buckets::limited(url, Duration::from_secs_f64(secs), global);
+                        }
+                        retry_after
+                    } else {
+                        None
+                    };
+
+                    FromDiscordErr {
+                        status_code,
+                        status_message,
+                        body: body.to_vec(),
+                        retry_after,
+                    }
+                },
+                Err(_) => FromDiscordErr {
+                    status_code: 500,
+                    status_message: "Failed to read discord response".into(),
+                    body: "".into(),
+                    retry_after: None,
+                }
+            };
+            Err(err)
+        },
+        Err(_) => {
+            error!("Sending request to discord failed. This could signal a major issue.");
+
+            let err = FromDiscordErr {
+                status_code: 500,
+                status_message: "Failed to send request to discord".into(),
+                body: "".into(),
+                retry_after: None,
+            };
+
+            Err(err)
+        },
+    }
+}
+
+#[async_trait]
+impl Deliverable for ToDiscord {
+    const TARGET: OutboxTarget = OutboxTarget::Discord;
+
+    /// How long the message was held back for to wait out a rate limit.
+    type Sent = Duration;
+    type Error = FromDiscordErr;
+
+    /// Sends the message, falling back to plain text if discord rejects the
+    /// rich form.
+    ///
+    /// Waits for the webhook's rate limit bucket to reset first if that doesn't
+    /// take too long. Longer rate limits are returned as a retryable 429, so
+    /// that the message is queued in the outbox instead.
+    async fn deliver(self) -> Result<Duration, FromDiscordErr> {
+        let PayloadDetails { url, username, message, rich } = self.get_payload_details();
+
+        let plain_body = serde_json::json!({
+            "username": username,
+            "content": message,
+        });
+        let mut body = match &rich {
+            Some(RichMessage { content, embed }) => serde_json::json!({
+                "username": username,
+                "content": content,
+                "embeds": [embed],
+            }),
+            None => plain_body.clone(),
+        };
+        let mut fell_back = rich.is_none();
+
+        let mut delayed = Duration::ZERO;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+
+            if let Some(wait) = buckets::wait_time(&url) {
+                if wait > MAX_INLINE_WAIT {
+                    debug!("Discord rate limit resets in {:.2}s, not waiting", wait.as_secs_f64());
+                    return Err(FromDiscordErr {
+                        status_code: 429,
+                        status_message: "Rate limited by discord".into(),
+                        body: "".into(),
+                        retry_after: Some(wait.as_secs_f64()),
+                    });
+                }
+
+                debug!("Waiting {:.2}s for discord rate limit to reset", wait.as_secs_f64());
+                tokio::time::sleep(wait).await;
+                delayed += wait;
+            }
+
+            match send(&url, &body).await {
+                Ok(()) => return Ok(delayed),
+                Err(err) if err.status_code == 429 && attempts < MAX_SEND_ATTEMPTS => {
+                    debug!("Trying discord webhook req again after rate limit");
+                },
+                Err(err) if err.status_code == 400 && !fell_back => {
+                    warn!("Discord rejected embed, falling back to plain text");
+                    debug!("Discord err: {err:?}");
+
+                    body = plain_body.clone();
+                    fell_back = true;
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn is_retryable(error: &FromDiscordErr) -> bool {
+        error.status_code == 429 || error.status_code >= 500
+    }
+
+    fn retry_after(error: &FromDiscordErr) -> Option<f64> {
+        error.retry_after
+    }
+}
@@ -4,10 +4,13 @@ use serde_json::json;
 use crate::logging::*;
 
 use crate::http_client::DEFAULT;
+use reqwest::header::{ AUTHORIZATION, CONTENT_TYPE };
 use crate::payloads::incoming::frontend::SyncType;
 use crate::payloads::incoming::ToFrontend;
 use crate::payloads::outgoing::frontend::{FromFrontend, FromFrontendErr};
+use crate::payloads::outgoing::sql::OutboxTarget;
 
+use super::outbox::{ self, Deliverable, Outcome };
 use super::{Handle, ResponseFrom};
 
 #[async_trait]
@@ -15,6 +18,24 @@ impl Handle for ToFrontend {
     type SuccessPayload = FromFrontend;
     type ErrorPayload = FromFrontendErr;
     async fn handle(self) -> ResponseFrom<Self> {
+        match outbox::send_now(self).await {
+            Outcome::Sent(synced) => Ok(synced),
+            Outcome::Queued(outbox_id) => match self {
+                Self::Sync(sync_type) => Ok(FromFrontend::Queued { sync_type, outbox_id }),
+            },
+            Outcome::Failed(err) => Err(err),
+        }
+    }
+}
+
+#[async_trait]
+impl Deliverable for ToFrontend {
+    const TARGET: OutboxTarget = OutboxTarget::Frontend;
+
+    type Sent = FromFrontend;
+    type Error = FromFrontendErr;
+
+    async fn deliver(self) -> Result<FromFrontend, FromFrontendErr> {
         let payload = match self {
             Self::Sync(sync_type) => match sync_type {
                 SyncType::All       => json!({ "__type": "all" }),
@@ -39,12 +60,18 @@ impl Handle for ToFrontend {
             }
         };
 
+        let url = format!("{}/api/sync", crate::env::frontend_address());
+        let payload = payload.to_string().into_bytes();
+
+        let started = std::time::Instant::now();
         let response = DEFAULT
-            .post(format!("{}/api/sync", crate::env::frontend_address()))
-            .bearer_auth(String::from_utf8_lossy(&crate::auth::webhook_auth()))
-            .json(&payload)
+            .post(&url)
+            .header(AUTHORIZATION, crate::auth::outbound_authorization("POST", &url, &payload))
+            .header(CONTENT_TYPE, "application/json")
+            .body(payload)
             .send()
             .await;
+        crate::metrics::observe_outbound("frontend", started, &response);
 
         match response {
             Ok(response) => if response.status().is_success() {
@@ -54,13 +81,15 @@ impl Handle for ToFrontend {
                     Self::Sync(sync_type) => Ok(FromFrontend::Synced(sync_type)),
                 }
             } else {
+                let code = response.status().as_u16();
+
                 if let Ok(bytes) = response.bytes().await {
                     debug!("{}", String::from_utf8_lossy(&bytes));
                 }
                 warn!("Frontend req returned error");
                 
                 match self {
-                    Self::Sync(sync_type) => Err(FromFrontendErr::FailedToSync(sync_type)),
+                    Self::Sync(sync_type) => Err(FromFrontendErr::FailedToSync { sync_type, code }),
                 }
             },
             Err(e) => {
@@ -70,4 +99,14 @@ impl Handle for ToFrontend {
             }
         }
     }
+
+    /// Failing to reach the frontend is generally it being down or
+    /// restarting, so that's retried along with 429s and 5xxs. Other errors
+    /// mean the frontend rejected the sync, and would just reject it again.
+    fn is_retryable(error: &FromFrontendErr) -> bool {
+        match error {
+            FromFrontendErr::WebhookServerError(_) => true,
+            FromFrontendErr::FailedToSync { code, .. } => *code == 429 || *code >= 500,
+        }
+    }
 }
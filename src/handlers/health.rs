@@ -0,0 +1,139 @@
+//! Liveness and readiness checks for orchestrators.
+//!
+//! The server is live as long as it can respond at all. It's ready once every
+//! dependency is, which is checked by [`readiness`]:
+//! - Postgres, by running a query on a pooled connection
+//! - The deploy server and frontend, by sending them a request (any response
+//!   counts, since the address is only checked for being reachable)
+//! - Discord, by checking that the webhook URLs and role ids are set
+//!
+//! Readiness is reported without auth, so the result is reused for
+//! [`CACHED_FOR`] rather than sending requests to other services on every
+//! probe.
+
+use std::future::Future;
+use std::time::{ Duration, Instant };
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::http_client::DEFAULT;
+use crate::logging::*;
+
+/// How long a dependency has to respond before it's considered down.
+const TIMEOUT: Duration = Duration::from_secs(3);
+/// How long a readiness result is reused for.
+pub const CACHED_FOR: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    /// The last readiness result and when it was checked.
+    static ref LAST_CHECKED: Mutex<Option<(Instant, Readiness)>> = Mutex::new(None);
+}
+
+/// The status of one of the dependencies.
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyStatus {
+    /// Whether the dependency is usable.
+    pub ok: bool,
+    /// How long the check took, in milliseconds.
+    pub latency_ms: f64,
+    /// Why the dependency isn't usable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The status of every dependency.
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    /// Whether every dependency is usable.
+    pub ready: bool,
+    /// The database.
+    pub postgres: DependencyStatus,
+    /// The deploy server, at `DEPLOY_ADDRESS`.
+    pub deploy: DependencyStatus,
+    /// The frontend, at `FRONTEND_ADDRESS`.
+    pub frontend: DependencyStatus,
+    /// The Discord webhook config.
+    pub discord: DependencyStatus,
+}
+
+/// Times the check, failing it if it takes longer than [`TIMEOUT`].
+async fn timed(check: impl Future<Output = Result<(), String>>) -> DependencyStatus {
+    let start = Instant::now();
+
+    let result = match tokio::time::timeout(TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(format!("Timed out after {}s", TIMEOUT.as_secs())),
+    };
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    match result {
+        Ok(()) => DependencyStatus { ok: true, latency_ms, error: None },
+        Err(error) => DependencyStatus { ok: false, latency_ms, error: Some(error) },
+    }
+}
+
+async fn check_postgres() -> Result<(), String> {
+    let mut connection = crate::sql::connection().await.map_err(|e| e.to_string())?;
+
+    sqlx::query("SELECT 1")
+        .execute(&mut connection)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_reachable(address: &str) -> Result<(), String> {
+    DEFAULT
+        .get(address)
+        .send()
+        .await
+        .map(|_| ())
+        // The address is left out, since readiness is reported without auth
+        .map_err(|e| e.without_url().to_string())
+}
+
+async fn check_discord() -> Result<(), String> {
+    crate::env::checks::discord().map_err(|e| format!("Missing Discord env variables {e}"))
+}
+
+/// Gets the readiness of the dependencies, checking them again if the last
+/// result is older than [`CACHED_FOR`]. Concurrent probes wait for the same
+/// check instead of starting their own.
+pub async fn readiness() -> Readiness {
+    let mut last_checked = LAST_CHECKED.lock().await;
+
+    if let Some((checked_at, readiness)) = &*last_checked {
+        if checked_at.elapsed() < CACHED_FOR {
+            return readiness.clone();
+        }
+    }
+
+    let readiness = check_all().await;
+    *last_checked = Some((Instant::now(), readiness.clone()));
+    readiness
+}
+
+/// Checks every dependency concurrently.
+async fn check_all() -> Readiness {
+    let deploy_address = crate::env::deploy_address();
+    let frontend_address = crate::env::frontend_address();
+
+    let (postgres, deploy, frontend, discord) = tokio::join!(
+        timed(check_postgres()),
+        timed(check_reachable(&deploy_address)),
+        timed(check_reachable(&frontend_address)),
+        timed(check_discord()),
+    );
+
+    let ready = [&postgres, &deploy, &frontend, &discord]
+        .into_iter()
+        .all(|status| status.ok);
+
+    if !ready {
+        warn!("Not ready: postgres {}, deploy {}, frontend {}, discord {}", postgres.ok, deploy.ok, frontend.ok, discord.ok);
+    }
+
+    Readiness { ready, postgres, deploy, frontend, discord }
+}
@@ -12,9 +12,14 @@ mod discord;
 mod frontend;
 mod sql;
 
+pub mod audit;
+pub mod health;
+pub mod outbox;
+pub mod permissions;
+
 use async_trait::async_trait;
 
-use crate::payloads::{incoming::Incoming, outgoing::Outgoing};
+use crate::payloads::{incoming::{Incoming, Batchable}, outgoing::Outgoing};
 
 /// This is a utility type getting the response type from a value implementing
 /// `Handle`.
@@ -90,16 +95,52 @@ where Self: Sized {
 }
 
 #[async_trait]
-impl Handle for Incoming {
-    type SuccessPayload = Outgoing;
+impl<T> Handle for Batchable<T>
+where
+    T: Handle + Send,
+    T::SuccessPayload: Send,
+    T::ErrorPayload: Send,
+{
+    type SuccessPayload = Batchable<ResponseFrom<T>>;
     type ErrorPayload = std::convert::Infallible;
-    async fn handle(self) -> Result<Outgoing, std::convert::Infallible> {
+    async fn handle(self) -> ResponseFrom<Self> {
+        let responses = match self {
+            Batchable::Single(query) => Batchable::Single(query.handle().await),
+            Batchable::Batch(queries) => {
+                let responses = futures::future::join_all(
+                    queries.into_iter().map(Handle::handle)
+                ).await;
+                Batchable::Batch(responses)
+            },
+        };
+        Ok(responses)
+    }
+}
+
+fn into_ok<T>(res: Result<T, std::convert::Infallible>) -> T {
+    match res {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
+
+impl Incoming {
+    /// Handles the request like [`Handle::handle`], but writes the audit log
+    /// entries of its SQL queries along with them (see [`audit`]). The
+    /// request's other queries are audited with [`audit::Pending`].
+    pub async fn handle_audited(self, token: crate::Token) -> Outgoing {
+        self.handle_with(Some(token)).await
+    }
+
+    async fn handle_with(self, token: Option<crate::Token>) -> Outgoing {
         use futures::future::OptionFuture;
 
-        let depl: OptionFuture<_> = self.depl.map(Handle::handle).into();
-        let disc: OptionFuture<_> = self.disc.map(Handle::handle).into();
-        let fron: OptionFuture<_> = self.fron.map(Handle::handle).into();
-        let sqll: OptionFuture<_> = self.sqll.map(Handle::handle).into();
+        use crate::metrics::Measured;
+
+        let depl: OptionFuture<_> = self.depl.map(|q| q.map(|q| Measured::new("deploy", q)).handle()).into();
+        let disc: OptionFuture<_> = self.disc.map(|q| q.map(|q| Measured::new("discord", q)).handle()).into();
+        let fron: OptionFuture<_> = self.fron.map(|q| q.map(|q| Measured::new("frontend", q)).handle()).into();
+        let sqll: OptionFuture<_> = self.sqll.map(|q| q.map(|q| Measured::new("sql", sql::Audited::new(token, q))).handle()).into();
 
         let (
             depl,
@@ -113,11 +154,20 @@ impl Handle for Incoming {
             sqll
         );
 
-        Ok(Outgoing {
-            depl,
-            disc,
-            fron,
-            sqll,
-        })
+        Outgoing {
+            depl: depl.map(into_ok),
+            disc: disc.map(into_ok),
+            fron: fron.map(into_ok),
+            sqll: sqll.map(into_ok),
+        }
+    }
+}
+
+#[async_trait]
+impl Handle for Incoming {
+    type SuccessPayload = Outgoing;
+    type ErrorPayload = std::convert::Infallible;
+    async fn handle(self) -> Result<Outgoing, std::convert::Infallible> {
+        Ok(self.handle_with(None).await)
     }
 }
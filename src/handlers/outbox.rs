@@ -0,0 +1,308 @@
+//! A Postgres-backed outbox for the messages the webhook sends to discord, the
+//! frontend, and the deploy server.
+//!
+//! Every outbound message is recorded in the `outbox` table. Messages that
+//! fail to send with a retryable error are retried with exponential backoff by
+//! a background worker (see [`run_worker`]), which means that they survive a
+//! restart of the webhook server.
+//!
+//! Messages can be sent in two ways:
+//! - [`send_now`] records the message, tries to send it immediately, and leaves
+//!   it for the worker if that fails.
+//! - [`enqueue`] records the message as part of an ongoing SQL transaction, so
+//!   that it is only ever sent if the transaction commits.
+
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{ de::DeserializeOwned, Serialize };
+use tokio::sync::Notify;
+use uuid::Uuid;
+
+use crate::logging::*;
+use crate::payloads::incoming::{ ToDeploy, ToDiscord, ToFrontend };
+use crate::payloads::outgoing::sql::{ FromSqlErr, OutboxTarget };
+
+use super::sql::Ctx;
+use super::sql::prepared::outbox as queries;
+use queries::DueRow;
+
+/// How long to wait between checks for due messages if the worker isn't woken
+/// up earlier.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The maximum number of due messages handled in one pass of the worker.
+const BATCH_SIZE: u32 = 16;
+
+/// The number of attempts after which a message is given up on.
+const MAX_ATTEMPTS: i32 = 10;
+
+const BASE_BACKOFF_SECS: f64 = 5.0;
+const MAX_BACKOFF_SECS: f64 = 60.0 * 60.0;
+
+/// How long a message being sent immediately is hidden from the worker for. If
+/// the webhook dies mid-send, the worker picks the message back up after this.
+const IN_FLIGHT_TIMEOUT_SECS: f64 = 60.0;
+
+/// How long the messages claimed by the worker are hidden from other workers
+/// for. This needs to cover sending a whole batch one after another, including
+/// waiting on rate limits.
+const CLAIM_LEASE_SECS: f64 = 10.0 * 60.0;
+
+static WAKE: Notify = Notify::const_new();
+static ENQUEUED: AtomicBool = AtomicBool::new(false);
+
+/// A message that can be stored in the outbox and delivered by the worker.
+#[async_trait]
+pub (super) trait Deliverable: Serialize + DeserializeOwned + Send + Sized {
+    /// The target column the message is stored with.
+    const TARGET: OutboxTarget;
+
+    /// The value returned from a successful delivery.
+    type Sent: Send;
+    /// The error returned from a failed delivery.
+    type Error: Send + std::fmt::Debug;
+
+    /// Makes a single attempt to deliver the message.
+    async fn deliver(self) -> Result<Self::Sent, Self::Error>;
+
+    /// Whether trying to deliver the message again later could succeed.
+    fn is_retryable(error: &Self::Error) -> bool;
+
+    /// How long the target asked us to wait before trying again, in seconds.
+    /// This overrides the usual backoff for the next retry.
+    fn retry_after(_error: &Self::Error) -> Option<f64> {
+        None
+    }
+}
+
+/// The result of [`send_now`].
+#[derive(Debug, Clone)]
+pub (super) enum Outcome<T, E> {
+    /// The message was delivered immediately.
+    Sent(T),
+    /// The message couldn't be delivered yet, and will be retried by the
+    /// worker.
+    Queued(Uuid),
+    /// The message couldn't be delivered, and won't be retried.
+    Failed(E),
+}
+
+/// What should happen to an outbox entry after an attempt to deliver it.
+#[derive(Debug, Clone)]
+enum Resolution {
+    Sent,
+    Retry { error: String, delay_secs: f64 },
+    Failed { error: String },
+}
+
+fn backoff_secs(attempts: i32) -> f64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16);
+    (BASE_BACKOFF_SECS * 2f64.powi(exponent)).min(MAX_BACKOFF_SECS)
+}
+
+async fn resolve(ctx: &mut Ctx, id: Uuid, resolution: Resolution) -> Result<u64, sqlx::Error> {
+    match resolution {
+        Resolution::Sent => queries::mark_sent(ctx, id).await,
+        Resolution::Retry { error, delay_secs } => queries::schedule_retry(ctx, id, &error, delay_secs).await,
+        Resolution::Failed { error } => queries::mark_failed(ctx, id, &error).await,
+    }
+}
+
+async fn resolve_standalone(id: Uuid, resolution: Resolution) {
+    let result = async {
+        let mut transaction = crate::sql::transaction().await?;
+        resolve(&mut transaction, id, resolution).await?;
+        transaction.commit().await
+    }.await;
+
+    if let Err(e) = result {
+        error!("Failed to update outbox entry {id}: {e}");
+    }
+}
+
+async fn record_in_flight<D: Deliverable>(message: &D) -> Option<Uuid> {
+    let payload = match serde_json::to_value(message) {
+        Ok(payload) => payload,
+        Err(e) => {
+            error!("Failed to serialize outbound message for the outbox: {e}");
+            return None;
+        }
+    };
+
+    let result = async {
+        let mut transaction = crate::sql::transaction().await?;
+        let id = queries::enqueue(&mut transaction, D::TARGET, payload, IN_FLIGHT_TIMEOUT_SECS).await?;
+        transaction.commit().await?;
+        Ok::<_, sqlx::Error>(id)
+    }.await;
+
+    match result {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("Failed to record outbound message in the outbox, it won't be retried: {e}");
+            None
+        }
+    }
+}
+
+/// Records the message in the outbox and tries to deliver it immediately.
+///
+/// If delivery fails with a retryable error, the message is left in the outbox
+/// for the worker to retry. If the outbox can't be written to, the message is
+/// still sent, but it won't be retried.
+pub (super) async fn send_now<D: Deliverable>(message: D) -> Outcome<D::Sent, D::Error> {
+    let outbox_id = record_in_flight(&message).await;
+
+    match (message.deliver().await, outbox_id) {
+        (Ok(sent), Some(id)) => {
+            resolve_standalone(id, Resolution::Sent).await;
+            Outcome::Sent(sent)
+        },
+        (Ok(sent), None) => Outcome::Sent(sent),
+        (Err(e), Some(id)) if D::is_retryable(&e) => {
+            warn!("Outbound message failed to send, queued as {id} for retry");
+            debug!("Send error: {e:?}");
+
+            let delay_secs = D::retry_after(&e).unwrap_or_else(|| backoff_secs(1));
+            let resolution = Resolution::Retry { error: format!("{e:?}"), delay_secs };
+            resolve_standalone(id, resolution).await;
+            Outcome::Queued(id)
+        },
+        (Err(e), Some(id)) => {
+            resolve_standalone(id, Resolution::Failed { error: format!("{e:?}") }).await;
+            Outcome::Failed(e)
+        },
+        (Err(e), None) => Outcome::Failed(e),
+    }
+}
+
+/// Records the message in the outbox as part of the transaction. The worker
+/// sends it once the transaction has been committed.
+pub (super) async fn enqueue<D: Deliverable>(ctx: &mut Ctx, message: &D) -> Result<Uuid, FromSqlErr> {
+    let Ok(payload) = serde_json::to_value(message) else {
+        return Err(FromSqlErr::OtherServerError("Failed to serialize outbound message.".into()));
+    };
+
+    let id = queries::enqueue(ctx, D::TARGET, payload, 0.0).await?;
+    wake_on_commit();
+
+    Ok(id)
+}
+
+/// Marks that the worker has new work to pick up once the current transaction
+/// is committed.
+pub (super) fn wake_on_commit() {
+    ENQUEUED.store(true, Ordering::Relaxed);
+}
+
+/// Wakes up the worker if anything was [`enqueue`]d since the last call. This
+/// should be called after committing a transaction.
+pub (super) fn wake_if_enqueued() {
+    if ENQUEUED.swap(false, Ordering::Relaxed) {
+        WAKE.notify_one();
+    }
+}
+
+/// Why a delivery attempt made by the worker failed.
+struct AttemptError {
+    error: String,
+    retryable: bool,
+    retry_after: Option<f64>,
+}
+
+async fn attempt<D: Deliverable>(payload: serde_json::Value) -> Result<(), AttemptError> {
+    let message: D = match serde_json::from_value(payload) {
+        Ok(message) => message,
+        Err(e) => return Err(AttemptError {
+            error: format!("Malformed outbox payload: {e}"),
+            retryable: false,
+            retry_after: None,
+        }),
+    };
+
+    match message.deliver().await {
+        Ok(_) => Ok(()),
+        Err(e) => Err(AttemptError {
+            error: format!("{e:?}"),
+            retryable: D::is_retryable(&e),
+            retry_after: D::retry_after(&e),
+        }),
+    }
+}
+
+/// Claims the due messages by leasing them for [`CLAIM_LEASE_SECS`], so that
+/// no row locks are held while they're being sent.
+async fn claim_due() -> Result<Vec<DueRow>, sqlx::Error> {
+    let mut transaction = crate::sql::transaction().await?;
+
+    let due = queries::get_due(&mut transaction, BATCH_SIZE).await?;
+    let ids: Vec<Uuid> = due.iter().map(|row| row.id).collect();
+    queries::lease(&mut transaction, &ids, CLAIM_LEASE_SECS).await?;
+
+    transaction.commit().await?;
+    Ok(due)
+}
+
+/// Makes one delivery attempt for each of the due messages, returning how many
+/// there were.
+///
+/// Each message is resolved in its own transaction right after it's sent, so a
+/// failure partway through never un-marks messages that already went out.
+async fn process_due() -> Result<usize, sqlx::Error> {
+    let due = claim_due().await?;
+    let count = due.len();
+
+    for DueRow { id, target, payload, attempts } in due {
+        trace!("Retrying outbox entry {id} ({target:?}, attempt {})", attempts + 1);
+
+        let result = match target {
+            OutboxTarget::Discord => attempt::<ToDiscord>(payload).await,
+            OutboxTarget::Frontend => attempt::<ToFrontend>(payload).await,
+            OutboxTarget::Deploy => attempt::<ToDeploy>(payload).await,
+        };
+
+        let resolution = match result {
+            Ok(()) => {
+                info!("Outbox entry {id} sent");
+                Resolution::Sent
+            },
+            Err(AttemptError { error, retryable: true, retry_after }) if attempts + 1 < MAX_ATTEMPTS => {
+                debug!("Outbox entry {id} failed to send again: {error}");
+
+                let delay_secs = retry_after.unwrap_or_else(|| backoff_secs(attempts + 1));
+                Resolution::Retry { error, delay_secs }
+            },
+            Err(AttemptError { error, .. }) => {
+                error!("Giving up on outbox entry {id}: {error}");
+                Resolution::Failed { error }
+            },
+        };
+
+        resolve_standalone(id, resolution).await;
+    }
+
+    Ok(count)
+}
+
+/// Runs the outbox worker forever. This should be spawned once on startup.
+///
+/// The worker checks for due messages every few seconds, or immediately after
+/// being woken up by [`wake_if_enqueued`].
+pub async fn run_worker() {
+    info!("Starting outbox worker");
+
+    loop {
+        match process_due().await {
+            Ok(count) if count as u32 >= BATCH_SIZE => continue,
+            Ok(_) => (),
+            Err(e) => warn!("Outbox worker failed to process entries: {e}"),
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => (),
+            _ = WAKE.notified() => (),
+        }
+    }
+}
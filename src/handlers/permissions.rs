@@ -0,0 +1,343 @@
+//! Which operations each caller may use.
+//!
+//! Every query in a request is named by its target and tags, e.g.
+//! `sql.team.create` or `deploy.remove` (see [`operation_name`]), and the
+//! request is only handled if the caller's [`Token`] allows every one of them.
+//! SQL sequences are checked step by step.
+//!
+//! The frontend handles what participants and admins do on the site. Creating,
+//! changing and deleting challenges, deleting the solves on them, and getting
+//! per-team flags are left to the deploy server.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::Token;
+use crate::logging::*;
+use crate::payloads::incoming::{ Batchable, Incoming };
+use crate::payloads::outgoing::error::{ ErrorCode, RequestError };
+
+/// The operations the frontend may use. A trailing `*` matches any operation
+/// starting with what comes before it.
+const FRONTEND: &[&str] = &[
+    "deploy.*",
+    "discord.*",
+    "frontend.*",
+
+    "sql.user.*",
+    "sql.team.*",
+    // Not `sql.chall.get_team_flag`, since that would let participants get
+    // other teams' per-team flags
+    "sql.chall.get",
+    "sql.chall.get_all",
+    "sql.chall.get_available",
+    "sql.solve.get*",
+    "sql.solve.attempt",
+    "sql.outbox.*",
+    "sql.audit.*",
+];
+
+/// The operations the deploy server may use.
+const DEPLOY: &[&str] = &[
+    "discord.*",
+    "frontend.*",
+
+    "sql.chall.*",
+    "sql.solve.get*",
+    "sql.solve.clear_all_chall",
+    "sql.solve.delete",
+    "sql.solve.restore",
+];
+
+fn allowed(token: Token) -> &'static [&'static str] {
+    match token {
+        Token::Frontend => FRONTEND,
+        Token::Deploy => DEPLOY,
+        // OAuth tokens only ever come in request bodies
+        Token::Oauth => &[],
+    }
+}
+
+fn matches(pattern: &str, operation: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => operation.starts_with(prefix),
+        None => pattern == operation,
+    }
+}
+
+/// Builds the operation name from the tags of a serialized query, e.g.
+/// `sql.team.create` or `deploy.remove`.
+pub (crate) fn operation_name(target: &str, query: &Value) -> String {
+    let mut operation = target.to_string();
+
+    let mut value = query;
+    loop {
+        let tag = ["__type", "__query_name"]
+            .into_iter()
+            .find_map(|key| value.get(key).and_then(Value::as_str));
+        let Some(tag) = tag else { break };
+
+        operation.push('.');
+        operation.push_str(tag);
+
+        match ["details", "params", "data"].into_iter().find_map(|key| value.get(key)) {
+            Some(inner) => value = inner,
+            None => break,
+        }
+    }
+    operation
+}
+
+/// The steps of a serialized SQL sequence, or `None` if the query isn't one.
+pub (crate) fn sequence_steps(query: &Value) -> Option<&[Value]> {
+    if query.get("__type").and_then(Value::as_str) != Some("sequence") {
+        return None;
+    }
+    query.get("details").and_then(Value::as_array).map(Vec::as_slice)
+}
+
+/// A request using an operation its caller isn't allowed to.
+#[derive(Debug, Clone)]
+pub struct Forbidden {
+    /// The token the request was authenticated with.
+    pub token: Token,
+    /// The first disallowed operation in the request.
+    pub operation: String,
+    /// A JSON pointer to the query using the operation.
+    pub pointer: String,
+}
+
+impl From<Forbidden> for RequestError {
+    fn from(Forbidden { token, operation, pointer }: Forbidden) -> Self {
+        Self::new(ErrorCode::Forbidden, format!("The {token:?} token isn't allowed to use `{operation}`"))
+            .at(pointer)
+    }
+}
+
+/// Gets the names of the operations in a target's queries, along with JSON
+/// pointers to them. SQL sequences are named by each of their steps.
+fn operations<T: Serialize>(target: &str, queries: &Option<Batchable<T>>) -> Vec<(String, String)> {
+    let mut operations = vec![];
+
+    let Some(queries) = queries else { return operations };
+
+    for (index, query) in queries.iter().enumerate() {
+        let pointer = match queries {
+            Batchable::Single(_) => format!("/{target}"),
+            Batchable::Batch(_) => format!("/{target}/{index}"),
+        };
+
+        let value = match serde_json::to_value(query) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("Failed to serialize {target} query to check its permissions: {e}");
+                operations.push((target.to_string(), pointer));
+                continue;
+            }
+        };
+
+        match sequence_steps(&value) {
+            Some(steps) => operations.extend(
+                steps
+                    .iter()
+                    .enumerate()
+                    .map(|(step, query)| (operation_name(target, query), format!("{pointer}/details/{step}"))),
+            ),
+            None => operations.push((operation_name(target, &value), pointer)),
+        }
+    }
+    operations
+}
+
+/// Checks that the token allows every operation in the request.
+pub fn check(token: Token, incoming: &Incoming) -> Result<(), Forbidden> {
+    let allowed = allowed(token);
+
+    let operations = [
+        operations("deploy", &incoming.depl),
+        operations("discord", &incoming.disc),
+        operations("frontend", &incoming.fron),
+        operations("sql", &incoming.sqll),
+    ];
+
+    for (operation, pointer) in operations.into_iter().flatten() {
+        if !allowed.iter().any(|pattern| matches(pattern, &operation)) {
+            warn!("{token:?} token tried to use `{operation}`");
+            return Err(Forbidden { token, operation, pointer });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "00000000-0000-0000-0000-000000000000";
+    const AUTH: &str = r#"{ "__type": "pass", "params": { "password": "hunter2" } }"#;
+
+    fn incoming(body: &str) -> Incoming {
+        match Incoming::parse(body.as_bytes()) {
+            Ok(incoming) => incoming,
+            Err(e) => panic!("failed to parse {body}: {e:?}"),
+        }
+    }
+
+    fn sql(kind: &str, name: &str, params: &str) -> String {
+        format!(r#"{{ "__type": "{kind}", "details": {{ "__query_name": "{name}", "params": {params} }} }}"#)
+    }
+
+    /// One representative query of each kind, with its operation name.
+    fn queries() -> Vec<(&'static str, String)> {
+        vec![
+            ("deploy.poll", format!(r#"{{ "deploy": {{ "__type": "poll", "data": {{ "id": "{ID}" }} }} }}"#)),
+            ("discord.participant", r#"{ "discord": { "__type": "participant", "details": {
+                "__participant_message_type": "alert", "metadata": { "message": "hi" }
+            } } }"#.to_string()),
+            ("frontend.sync", r#"{ "frontend": { "__type": "sync", "data": { "__sync_type": "all" } } }"#.to_string()),
+            ("sql.user.create", format!(r#"{{ "sql": {} }}"#, sql("user", "create", &format!(
+                r#"{{ "email": "a@b.c", "name": "a", "eligible": true, "admin": false, "auth": {AUTH} }}"#
+            )))),
+            ("sql.team.get", format!(r#"{{ "sql": {} }}"#, sql("team", "get", &format!(r#"{{ "id": "{ID}" }}"#)))),
+            ("sql.chall.create", format!(r#"{{ "sql": {} }}"#, sql("chall", "create", r#"{
+                "name": "a", "description": "b", "points": 500,
+                "authors": [], "hints": [], "categories": [], "tags": [], "links": [],
+                "visible": true, "source_folder": "a", "flag": "flag{a}"
+            }"#))),
+            ("sql.chall.get", format!(r#"{{ "sql": {} }}"#, sql("chall", "get", &format!(r#"{{ "id": "{ID}", "team_id": "{ID}" }}"#)))),
+            ("sql.chall.get_team_flag", format!(r#"{{ "sql": {} }}"#, sql("chall", "get_team_flag", &format!(r#"{{ "id": "{ID}", "team_id": "{ID}" }}"#)))),
+            ("sql.solve.attempt", format!(r#"{{ "sql": {} }}"#, sql("solve", "attempt", &format!(
+                r#"{{ "user_id": "{ID}", "team_id": "{ID}", "chall_id": "{ID}", "user_auth": {AUTH}, "flag_guess": "flag{{a}}" }}"#
+            )))),
+            ("sql.solve.delete", format!(r#"{{ "sql": {} }}"#, sql("solve", "delete", &format!(
+                r#"{{ "admin_id": "{ID}", "admin_auth": {AUTH}, "id": "{ID}" }}"#
+            )))),
+            ("sql.outbox.list", format!(r#"{{ "sql": {} }}"#, sql("outbox", "list", &format!(
+                r#"{{ "admin_id": "{ID}", "admin_auth": {AUTH}, "limit": 10 }}"#
+            )))),
+        ]
+    }
+
+    /// Whether each token may use each of the [`queries`], as frontend,
+    /// deploy, then OAuth.
+    const EXPECTED: &[(&str, [bool; 3])] = &[
+        ("deploy.poll",             [true,  false, false]),
+        ("discord.participant",     [true,  true,  false]),
+        ("frontend.sync",           [true,  true,  false]),
+        ("sql.user.create",         [true,  false, false]),
+        ("sql.team.get",            [true,  false, false]),
+        ("sql.chall.create",        [false, true,  false]),
+        ("sql.chall.get",           [true,  true,  false]),
+        ("sql.chall.get_team_flag", [false, true,  false]),
+        ("sql.solve.attempt",       [true,  false, false]),
+        ("sql.solve.delete",        [false, true,  false]),
+        ("sql.outbox.list",         [true,  false, false]),
+    ];
+
+    #[test]
+    fn each_token_may_only_use_its_operations() {
+        let queries = queries();
+        assert_eq!(queries.len(), EXPECTED.len());
+
+        for ((operation, body), (expected_operation, allowed)) in queries.iter().zip(EXPECTED) {
+            assert_eq!(operation, expected_operation);
+            let incoming = incoming(body);
+
+            for (token, allowed) in [Token::Frontend, Token::Deploy, Token::Oauth].into_iter().zip(allowed.iter().copied()) {
+                match check(token, &incoming) {
+                    Ok(()) => assert!(allowed, "{token:?} shouldn't be allowed to use `{operation}`"),
+                    Err(forbidden) => {
+                        assert!(!allowed, "{token:?} should be allowed to use `{operation}`");
+                        assert_eq!(forbidden.token, token);
+                        assert_eq!(forbidden.operation, *operation);
+                    },
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn operations_are_named_by_their_tags() {
+        for (operation, body) in queries() {
+            let Ok(value) = serde_json::from_str::<Value>(&body) else { panic!("invalid json {body}") };
+            let Some((target, query)) = value.as_object().and_then(|object| object.iter().next()) else {
+                panic!("no target in {body}")
+            };
+            assert_eq!(operation_name(target, query), operation);
+        }
+    }
+
+    #[test]
+    fn patterns_only_match_prefixes_with_a_star() {
+        assert!(matches("sql.solve.get*", "sql.solve.get_all"));
+        assert!(matches("sql.chall.get", "sql.chall.get"));
+        assert!(!matches("sql.chall.get", "sql.chall.get_team_flag"));
+        assert!(!matches("sql.team.*", "sql.teams.get"));
+    }
+
+    #[test]
+    fn empty_requests_are_allowed() {
+        let incoming = incoming("{}");
+        for token in [Token::Frontend, Token::Deploy, Token::Oauth] {
+            assert!(check(token, &incoming).is_ok());
+        }
+    }
+
+    #[test]
+    fn single_queries_point_at_the_target() {
+        let body = format!(r#"{{ "sql": {} }}"#, sql("user", "get", &format!(r#"{{ "id": "{ID}" }}"#)));
+
+        let Err(forbidden) = check(Token::Deploy, &incoming(&body)) else { panic!("deploy got a user") };
+        assert_eq!(forbidden.operation, "sql.user.get");
+        assert_eq!(forbidden.pointer, "/sql");
+    }
+
+    #[test]
+    fn batches_point_at_the_forbidden_query() {
+        let body = format!(
+            r#"{{ "sql": [{}, {}] }}"#,
+            sql("team", "get", &format!(r#"{{ "id": "{ID}" }}"#)),
+            sql("chall", "get_team_flag", &format!(r#"{{ "id": "{ID}", "team_id": "{ID}" }}"#)),
+        );
+
+        let Err(forbidden) = check(Token::Frontend, &incoming(&body)) else { panic!("frontend got a team flag") };
+        assert_eq!(forbidden.operation, "sql.chall.get_team_flag");
+        assert_eq!(forbidden.pointer, "/sql/1");
+    }
+
+    #[test]
+    fn sequences_are_checked_step_by_step() {
+        let chall_create = r#"{
+            "name": "a", "description": "b", "points": 500,
+            "authors": [], "hints": [], "categories": [], "tags": [], "links": [],
+            "visible": true, "source_folder": "a", "flag": "flag{a}"
+        }"#;
+        let sequence = format!(
+            r#"{{ "__type": "sequence", "details": [{}, {}] }}"#,
+            sql("chall", "get", &format!(r#"{{ "id": "{ID}", "team_id": "{ID}" }}"#)),
+            sql("chall", "create", chall_create),
+        );
+
+        let single = incoming(&format!(r#"{{ "sql": {sequence} }}"#));
+        let Err(forbidden) = check(Token::Frontend, &single) else { panic!("frontend created a challenge") };
+        assert_eq!(forbidden.operation, "sql.chall.create");
+        assert_eq!(forbidden.pointer, "/sql/details/1");
+        assert!(check(Token::Deploy, &single).is_ok());
+
+        let batched = incoming(&format!(r#"{{ "sql": [{sequence}] }}"#));
+        let Err(forbidden) = check(Token::Frontend, &batched) else { panic!("frontend created a challenge") };
+        assert_eq!(forbidden.pointer, "/sql/0/details/1");
+    }
+
+    #[test]
+    fn the_first_forbidden_target_is_reported() {
+        let body = format!(
+            r#"{{ "deploy": {{ "__type": "poll", "data": {{ "id": "{ID}" }} }}, "sql": {} }}"#,
+            sql("user", "get", &format!(r#"{{ "id": "{ID}" }}"#)),
+        );
+
+        let Err(forbidden) = check(Token::Deploy, &incoming(&body)) else { panic!("deploy polled itself") };
+        assert_eq!(forbidden.operation, "deploy.poll");
+        assert_eq!(forbidden.pointer, "/deploy");
+    }
+}
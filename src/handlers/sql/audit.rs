@@ -0,0 +1,31 @@
+use crate::logging::*;
+use crate::payloads::*;
+
+use incoming::sql::AuditQuery;
+use outgoing::sql::{FromSql, FromSqlErr};
+
+use super::prepared::audit as queries;
+use queries::get_entries;
+
+use super::check_admin;
+
+pub async fn handle(ctx: &mut super::Ctx, query: AuditQuery) -> Result<FromSql, FromSqlErr> {
+    trace!("Handling SQL audit req");
+
+    let success_res = match query {
+        AuditQuery::List { admin_id, admin_auth, before, limit, filter } => {
+            debug!("SQL audit req classified as 'List<{before:?}, {limit}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            // Cap here to prevent server from being overloaded by a
+            // badly-written client
+            if limit > 500 {
+                return Err(FromSqlErr::RequestTooBig(limit as u64, 500))
+            }
+
+            FromSql::AuditEntryArr(get_entries(ctx, before, limit, &filter).await?)
+        },
+    };
+    Ok(success_res)
+}
@@ -1,69 +1,208 @@
 use crate::logging::*;
 use crate::payloads::*;
 
-use incoming::sql::ChallQuery;
+use incoming::sql::{ ChallQuery, FlagType, HintInput, ScoringType };
 use outgoing::sql::{FromSql, FromSqlErr};
 use uuid::Uuid;
 
 use super::prepared::challenges as queries;
 use super::prepared::challenges::get_chall_by_source_folder;
 use queries::{
-    get_all_challs, get_chall,
+    get_all_challs, get_available_challs, get_chall,
     create_chall, update_chall,
 };
 use queries::{ ChallInput, NewChallInput };
 
-pub async fn handle(mut ctx: super::Ctx, query: ChallQuery) -> Result<FromSql, FromSqlErr> {
+use super::flags;
+use super::scoring::update_chall_value;
+
+/// Rejects the challenge's flags if any of them aren't valid for its flag type,
+/// and generates a flag secret if it needs one.
+async fn validate_flags(ctx: &mut super::Ctx, id: Uuid) -> Result<(), FromSqlErr> {
+    let Some(chall_flags) = queries::get_chall_flags(&mut *ctx, id).await? else {
+        return Err(FromSqlErr::DoesNotExist(id));
+    };
+    flags::validate(chall_flags.flag_type, chall_flags.flags())?;
+
+    if chall_flags.flag_type == FlagType::PerTeam && chall_flags.flag_secret.is_none() {
+        debug!("Generating flag secret for challenge {id}");
+        queries::init_chall_flag_secret(ctx, id, &flags::new_secret()).await?;
+    }
+    Ok(())
+}
+
+/// Rejects hints that would give teams points for unlocking them.
+fn validate_hints(hints: &[HintInput]) -> Result<(), FromSqlErr> {
+    let negative = hints.iter().any(|hint| matches!(hint, HintInput::Priced { cost, .. } if *cost < 0));
+    if negative {
+        return Err(FromSqlErr::BadRequest("Hint costs can't be negative.".into()));
+    }
+    Ok(())
+}
+
+/// Replaces the challenge's prerequisites, rejecting unknown challenges and
+/// prerequisites that would leave the challenge locked forever.
+async fn set_prerequisites(ctx: &mut super::Ctx, id: Uuid, prerequisites: Vec<Uuid>) -> Result<(), FromSqlErr> {
+    if prerequisites.contains(&id) {
+        return Err(FromSqlErr::BadRequest("A challenge can't be its own prerequisite.".into()));
+    }
+
+    let missing = queries::set_chall_prerequisites(&mut *ctx, id, &prerequisites).await?;
+    if let Some(missing_id) = missing.into_iter().next() {
+        return Err(FromSqlErr::DoesNotExist(missing_id));
+    }
+
+    if queries::chall_prerequisites_cyclic(ctx, id).await? {
+        return Err(FromSqlErr::BadRequest("Challenge prerequisites can't form a cycle.".into()));
+    }
+    Ok(())
+}
+
+pub async fn handle(ctx: &mut super::Ctx, query: ChallQuery) -> Result<FromSql, FromSqlErr> {
     trace!("Handling SQL chall req");
 
     let success_res = match query {
         ChallQuery::GetAllChallenges => {
             debug!("SQL chall req classified as 'GetAllChallenges' req");
-            FromSql::ChallArr(get_all_challs(&mut ctx).await?)
+            FromSql::ChallArr(get_all_challs(ctx).await?)
         },
-        ChallQuery::GetChallenge { id } => {
-            debug!("SQL chall req classified as 'GetChallenge<{id}>' req");
+        ChallQuery::GetAvailableChallenges { team_id } => {
+            debug!("SQL chall req classified as 'GetAvailableChallenges<{team_id}>' req");
+            FromSql::ChallArr(get_available_challs(ctx, team_id).await?)
+        },
+        ChallQuery::GetChallenge { id, team_id } => {
+            debug!("SQL chall req classified as 'GetChallenge<{id}, {team_id:?}>' req");
+
+            if let Some(team_id) = team_id {
+                super::check_chall_available(ctx, id, team_id).await?;
+            }
 
-            if let Some(chall) = get_chall(&mut ctx, id).await? {
+            if let Some(chall) = get_chall(ctx, id).await? {
                 FromSql::Chall(chall)
             } else {
                 return Err(FromSqlErr::DoesNotExist(id))
             }
         },
+        ChallQuery::GetTeamFlag { id, team_id } => {
+            debug!("SQL chall req classified as 'GetTeamFlag<{id}, {team_id}>' req");
+
+            let Some(chall_flags) = queries::get_chall_flags(ctx, id).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+
+            let team_flags = flags::team_flags(&chall_flags, team_id)?;
+            let Some(flag) = team_flags.into_iter().next() else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+            FromSql::Flag(flag)
+        },
         ChallQuery::CreateChallenge {
             id,
             name, description, points,
-            authors, hints, categories, tags, links,
-            visible, source_folder, flag
+            scoring_type, min_points, decay,
+            authors, hints, prerequisites, categories, tags, links,
+            visible, source_folder,
+            flag, flag_type, accepted_flags, flag_secret,
         } => {
             debug!("SQL chall req classified as 'CreateChallenge<`{name}`>' req");
-            FromSql::Chall(create_chall(&mut ctx, NewChallInput {
+
+            validate_hints(&hints)?;
+
+            let chall = create_chall(ctx, NewChallInput {
                 id,
                 name, description, points,
+                scoring_type: scoring_type.unwrap_or(ScoringType::Static),
+                min_points: min_points.unwrap_or(points),
+                decay: decay.unwrap_or(0),
                 authors, hints, categories, tags, links,
-                visible, source_folder, flag,
-            }).await?)
+                visible, source_folder,
+                flag,
+                flag_type: flag_type.unwrap_or(FlagType::Exact),
+                accepted_flags: accepted_flags.unwrap_or_default(),
+                flag_secret,
+            }).await?;
+
+            validate_flags(ctx, chall.id).await?;
+            set_prerequisites(ctx, chall.id, prerequisites).await?;
+
+            // Recreating an existing challenge can change its value, so the
+            // scores of its solvers may need to be updated.
+            update_chall_value(ctx, chall.id).await?;
+
+            if let Some(chall) = get_chall(ctx, chall.id).await? {
+                FromSql::Chall(chall)
+            } else {
+                return Err(FromSqlErr::DoesNotExist(chall.id))
+            }
         },
         ChallQuery::UpdateChallenge {
             id,
             name, description, points,
-            authors, hints, categories, tags, links,
-            visible, source_folder
+            scoring_type, min_points, decay,
+            authors, hints, prerequisites, categories, tags, links,
+            visible, source_folder,
+            flag_type, accepted_flags, flag_secret,
         } => {
             debug!("SQL chall req classified as 'UpdateChallenge<`{id}`>' req");
 
-            let opt_chall = update_chall(&mut ctx, id, ChallInput {
+            if let Some(hints) = &hints {
+                validate_hints(hints)?;
+            }
+
+            let opt_chall = update_chall(ctx, id, ChallInput {
                 name, description, points,
+                scoring_type, min_points, decay,
                 authors, hints, categories, tags, links,
                 visible, source_folder,
+                flag_type, accepted_flags, flag_secret,
             }).await?;
 
-            if let Some(chall) = opt_chall {
+            if opt_chall.is_none() {
+                return Err(FromSqlErr::DoesNotExist(id));
+            }
+
+            validate_flags(ctx, id).await?;
+            if let Some(prerequisites) = prerequisites {
+                set_prerequisites(ctx, id, prerequisites).await?;
+            }
+
+            update_chall_value(ctx, id).await?;
+
+            if let Some(chall) = get_chall(ctx, id).await? {
                 FromSql::Chall(chall)
             } else {
                 return Err(FromSqlErr::DoesNotExist(id));
             }
-        }
+        },
+        ChallQuery::DeleteChallenge { admin_id, admin_auth, id, remove_deployment, sync_frontend } => {
+            debug!("SQL chall req classified as 'DeleteChallenge<`{id}`>' req");
+
+            use super::prepared::solves::{ clear_all_solves_for_challenge, update_all_scores };
+            use crate::payloads::incoming::{ ToDeploy, ToFrontend, frontend::SyncType };
+
+            super::check_admin(ctx, admin_id, admin_auth).await?;
+
+            let Some(chall) = get_chall(ctx, id).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+
+            clear_all_solves_for_challenge(ctx, id).await?;
+            queries::archive_chall(ctx, id).await?;
+
+            // Its solves no longer count, but teams still pay for the hints
+            // they unlocked
+            update_all_scores(ctx).await?;
+
+            if remove_deployment {
+                crate::handlers::outbox::enqueue(ctx, &ToDeploy::Remove { chall: id }).await?;
+            }
+            if sync_frontend {
+                crate::handlers::outbox::enqueue(ctx, &ToFrontend::Sync(SyncType::Chall(id))).await?;
+            }
+            warn!("Challenge {id} ({:?}) deleted by {admin_id}", chall.name.str());
+
+            FromSql::Chall(chall)
+        },
     };
     Ok(success_res)
 }
@@ -100,3 +239,88 @@ pub async fn get_chall_source_folder_by_id(id: Uuid) -> Result<Option<String>, s
 
     Ok(Some(source_folder))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::prepared::hints::unlock_hint;
+    use super::super::testing;
+
+    fn priced(content: &str, cost: i32) -> HintInput {
+        HintInput::Priced { content: content.to_string(), cost }
+    }
+
+    async fn hint_ids(ctx: &mut super::super::Ctx, chall_id: Uuid) -> Vec<Uuid> {
+        let Ok(Some(chall)) = get_chall(ctx, chall_id).await else {
+            panic!("Couldn't get challenge {chall_id}");
+        };
+        chall.hint_ids
+    }
+
+    #[test]
+    fn negative_hint_costs_are_rejected() {
+        assert!(validate_hints(&[priced("a", 0), HintInput::Free("b".to_string())]).is_ok());
+        assert!(matches!(
+            validate_hints(&[priced("a", 10), priced("b", -1)]),
+            Err(FromSqlErr::BadRequest(_)),
+        ));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn changing_hints_rescores_teams_that_unlocked_them(pool: sqlx::PgPool) {
+        let mut ctx = testing::setup(&pool).await;
+        let chall_id = testing::chall(&mut ctx, "chall", 100).await;
+
+        let hints = vec![priced("a", 10), priced("b", 20), priced("c", 5)];
+        assert!(queries::set_chall_hints(&mut ctx, chall_id, hints).await.is_ok());
+        let old_ids = hint_ids(&mut ctx, chall_id).await;
+
+        // One team per hint: the first gets repriced, the second replaced and
+        // the third removed.
+        let mut teams = Vec::new();
+        for (i, &hint_id) in old_ids.iter().enumerate() {
+            let team_id = testing::team(&mut ctx, &format!("team {i}")).await;
+            let user_id = testing::user(&mut ctx, &format!("user {i}"), team_id).await;
+            assert!(matches!(unlock_hint(&mut ctx, hint_id, team_id, user_id).await, Ok(true)));
+            teams.push(team_id);
+        }
+
+        let hints = vec![priced("a", 15), priced("b, but better", 20)];
+        assert!(queries::set_chall_hints(&mut ctx, chall_id, hints).await.is_ok());
+        let new_ids = hint_ids(&mut ctx, chall_id).await;
+
+        assert_eq!(new_ids.len(), 2);
+        assert_eq!(new_ids[0], old_ids[0]);
+        assert_ne!(new_ids[1], old_ids[1]);
+
+        assert_eq!(testing::team_score(&mut ctx, teams[0]).await, -15);
+        assert_eq!(testing::team_score(&mut ctx, teams[1]).await, 0);
+        assert_eq!(testing::team_score(&mut ctx, teams[2]).await, 0);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn deleting_a_challenge_keeps_its_hint_costs(pool: sqlx::PgPool) {
+        use super::super::prepared::solves::update_all_scores;
+
+        let mut ctx = testing::setup(&pool).await;
+        let (admin_id, admin_auth) = testing::admin(&mut ctx).await;
+
+        let team_id = testing::team(&mut ctx, "team").await;
+        let user_id = testing::user(&mut ctx, "user", team_id).await;
+        let chall_id = testing::chall(&mut ctx, "chall", 100).await;
+        let hint_id = testing::hint(&mut ctx, chall_id, 1, 30).await;
+
+        testing::solve(&mut ctx, user_id, team_id, chall_id, "2024-01-01 12:00:00").await;
+        assert!(matches!(unlock_hint(&mut ctx, hint_id, team_id, user_id).await, Ok(true)));
+        assert!(update_all_scores(&mut ctx).await.is_ok());
+        assert_eq!(testing::team_score(&mut ctx, team_id).await, 70);
+
+        let deleted = handle(&mut ctx, ChallQuery::DeleteChallenge {
+            admin_id, admin_auth, id: chall_id,
+            remove_deployment: false, sync_frontend: false,
+        }).await;
+        assert!(deleted.is_ok());
+
+        assert_eq!(testing::team_score(&mut ctx, team_id).await, -30);
+    }
+}
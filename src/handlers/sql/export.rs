@@ -0,0 +1,142 @@
+//! Exporting the scoreboard for other sites, mainly CTFtime.
+//!
+//! Both formats list the teams in the order given, so the standings have to
+//! already be ranked (see [`get_standings`][super::prepared::teams::get_standings]).
+
+use std::fmt::Write;
+
+use crate::payloads::outgoing::sql::{ CtftimeScoreboard, CtftimeStanding };
+
+use super::prepared::teams::StandingRow;
+
+/// Renders the standings as a CTFtime scoreboard feed.
+pub fn ctftime(standings: Vec<StandingRow>) -> CtftimeScoreboard {
+    let standings = standings
+        .into_iter()
+        .enumerate()
+        .map(|(idx, row)| CtftimeStanding {
+            pos: idx as u32 + 1,
+            team: row.name.string(),
+            score: row.score,
+        })
+        .collect();
+
+    CtftimeScoreboard { standings }
+}
+
+/// Quotes a CSV field if it needs to be. Fields that a spreadsheet would treat
+/// as a formula are prefixed with `'`, since team names are user-controlled.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+/// Renders the standings as CSV, with a header row.
+pub fn csv(standings: Vec<StandingRow>) -> String {
+    let mut out = String::from("pos,team,score,last_solve\n");
+
+    for (idx, row) in standings.into_iter().enumerate() {
+        let last_solve = row.last_solve
+            .map(|time| time.and_utc().to_rfc3339())
+            .unwrap_or_default();
+
+        // Writing to a string can't fail
+        let _ = writeln!(
+            out,
+            "{},{},{},{}",
+            idx + 1,
+            csv_field(row.name.str()),
+            row.score,
+            last_solve,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::CiText;
+
+    fn row(name: &str, score: i64, last_solve: Option<&str>) -> StandingRow {
+        let last_solve = last_solve.map(|time| match time.parse() {
+            Ok(time) => time,
+            Err(e) => panic!("bad time {time}: {e}"),
+        });
+
+        StandingRow {
+            team_id: uuid::Uuid::nil(),
+            name: CiText::wrap(name.to_string()),
+            score,
+            last_solve,
+        }
+    }
+
+    #[test]
+    fn plain_fields_are_left_alone() {
+        assert_eq!(csv_field("team rocket"), "team rocket");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn fields_with_separators_or_quotes_are_quoted() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("two\r\nlines"), "\"two\r\nlines\"");
+    }
+
+    #[test]
+    fn formulas_are_escaped() {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\t=1"), "'\t=1");
+        assert_eq!(csv_field("a=1"), "a=1");
+    }
+
+    #[test]
+    fn formulas_that_need_quoting_are_escaped_inside_the_quotes() {
+        assert_eq!(csv_field("=HYPERLINK(\"x\",\"y\")"), "\"'=HYPERLINK(\"\"x\"\",\"\"y\"\")\"");
+    }
+
+    #[test]
+    fn csv_ranks_rows_in_order() {
+        let standings = vec![
+            row("first", 500, Some("2024-01-01T12:00:00")),
+            row("=second", 300, None),
+            row("third, inc", 0, None),
+        ];
+
+        assert_eq!(csv(standings), concat!(
+            "pos,team,score,last_solve\n",
+            "1,first,500,2024-01-01T12:00:00+00:00\n",
+            "2,'=second,300,\n",
+            "3,\"third, inc\",0,\n",
+        ));
+    }
+
+    #[test]
+    fn ctftime_ranks_rows_in_order() {
+        let scoreboard = ctftime(vec![row("first", 500, None), row("=second", 300, None)]);
+
+        let standings: Vec<_> = scoreboard.standings
+            .into_iter()
+            .map(|standing| (standing.pos, standing.team, standing.score))
+            .collect();
+        assert_eq!(standings, vec![
+            (1, "first".to_string(), 500),
+            (2, "=second".to_string(), 300),
+        ]);
+    }
+}
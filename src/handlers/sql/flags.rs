@@ -0,0 +1,324 @@
+//! Checking flag guesses against a challenge's flags.
+//!
+//! A challenge has a primary flag and any number of other accepted flags, and
+//! a guess is correct if it matches any of them. How a guess matches a flag is
+//! set by the challenge's [`FlagType`].
+//!
+//! Per-team flags are templates, where [`PLACEHOLDER`] is replaced with the
+//! start of an HMAC-SHA256 of the team's id, keyed with the challenge's flag
+//! secret. A team submitting a flag generated for another team can then be
+//! caught with [`find_owner`].
+
+use constant_time_eq::constant_time_eq;
+use hmac::{ Hmac, Mac };
+use rand::RngCore;
+use regex::{ Regex, RegexBuilder };
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::payloads::incoming::sql::FlagType;
+use crate::payloads::outgoing::sql::FromSqlErr;
+
+use super::prepared::challenges::ChallFlagsRow;
+
+/// What's replaced with the team's value in per-team flag templates.
+pub const PLACEHOLDER: &str = "{}";
+
+/// The number of hex characters of the HMAC put into per-team flags.
+const TEAM_VALUE_LEN: usize = 16;
+
+/// The most memory a compiled flag pattern is allowed to take up, so that a
+/// bad pattern can't take the server down.
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+/// A flag pattern that failed to compile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidFlag {
+    pub pattern: String,
+    pub reason: String,
+}
+
+impl From<InvalidFlag> for FromSqlErr {
+    fn from(InvalidFlag { pattern, reason }: InvalidFlag) -> Self {
+        Self::BadRequest(format!("Invalid flag pattern `{pattern}`: {reason}").into())
+    }
+}
+
+/// Compiles a pattern that has to match the whole guess.
+fn compile(pattern: &str) -> Result<Regex, InvalidFlag> {
+    RegexBuilder::new(&format!("^(?:{pattern})$"))
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .map_err(|e| InvalidFlag { pattern: pattern.to_string(), reason: e.to_string() })
+}
+
+/// Checks that every flag is valid for the flag type. Regex flags have to
+/// compile, and per-team flags need a placeholder.
+pub fn validate<'a>(flag_type: FlagType, flags: impl IntoIterator<Item = &'a str>) -> Result<(), InvalidFlag> {
+    match flag_type {
+        FlagType::Regex => for flag in flags {
+            compile(flag)?;
+        },
+        FlagType::PerTeam => for flag in flags {
+            if !flag.contains(PLACEHOLDER) {
+                return Err(InvalidFlag {
+                    pattern: flag.to_string(),
+                    reason: format!("per-team flags need a `{PLACEHOLDER}` placeholder"),
+                });
+            }
+        },
+        FlagType::Exact | FlagType::CaseInsensitive => (),
+    }
+    Ok(())
+}
+
+/// Generates a new random flag secret.
+pub fn new_secret() -> String {
+    let mut secret = [0; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    hex::encode(secret)
+}
+
+/// Fills in a per-team flag template for the team.
+pub fn team_flag(template: &str, secret: &str, team_id: Uuid) -> Result<String, InvalidFlag> {
+    let Ok(mut mac) = <Hmac<Sha256>>::new_from_slice(secret.as_bytes()) else {
+        return Err(InvalidFlag {
+            pattern: template.to_string(),
+            reason: "the flag secret can't be used as an HMAC key".into(),
+        });
+    };
+    mac.update(team_id.as_bytes());
+
+    let value = hex::encode(mac.finalize().into_bytes());
+    Ok(template.replace(PLACEHOLDER, &value[..TEAM_VALUE_LEN]))
+}
+
+/// Gets the challenge's flags as they are for the team. These are only
+/// different between teams for per-team flags.
+pub fn team_flags(chall: &ChallFlagsRow, team_id: Uuid) -> Result<Vec<String>, InvalidFlag> {
+    if chall.flag_type != FlagType::PerTeam {
+        return Ok(chall.flags().map(str::to_string).collect());
+    }
+
+    let Some(secret) = chall.flag_secret.as_deref() else {
+        return Err(InvalidFlag {
+            pattern: chall.flag.clone(),
+            reason: "per-team flags need a flag secret".into(),
+        });
+    };
+
+    chall.flags().map(|template| team_flag(template, secret, team_id)).collect()
+}
+
+/// Checks whether `guess` is a correct flag for the team.
+pub fn check_for_team(chall: &ChallFlagsRow, team_id: Uuid, guess: &str) -> Result<bool, InvalidFlag> {
+    match chall.flag_type {
+        FlagType::PerTeam => {
+            let flags = team_flags(chall, team_id)?;
+            check(FlagType::PerTeam, flags.iter().map(String::as_str), guess)
+        },
+        flag_type => check(flag_type, chall.flags(), guess),
+    }
+}
+
+/// Whether `guess` could be the per-team flag `template` filled in for some
+/// team, i.e. the template with every placeholder replaced by a team value's
+/// worth of hex.
+fn fits_template(template: &str, guess: &str) -> bool {
+    let mut parts = template.split(PLACEHOLDER);
+    let Some(rest) = parts.next().and_then(|prefix| guess.strip_prefix(prefix)) else {
+        return false;
+    };
+
+    let rest = parts.try_fold(rest, |rest, part| {
+        let value = rest.get(..TEAM_VALUE_LEN)?;
+        if !value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return None;
+        }
+        rest.get(TEAM_VALUE_LEN..)?.strip_prefix(part)
+    });
+    rest == Some("")
+}
+
+/// Finds which of the teams the per-team flag `guess` was generated for, if
+/// any. Only guesses shaped like one of the challenge's flags are checked
+/// against every team, since that takes an HMAC per team.
+pub fn find_owner(chall: &ChallFlagsRow, team_ids: impl IntoIterator<Item = Uuid>, guess: &str) -> Result<Option<Uuid>, InvalidFlag> {
+    if chall.flag_type != FlagType::PerTeam {
+        return Ok(None);
+    }
+    if !chall.flags().any(|template| fits_template(template, guess)) {
+        return Ok(None);
+    }
+
+    for team_id in team_ids {
+        if check_for_team(chall, team_id, guess)? {
+            return Ok(Some(team_id));
+        }
+    }
+    Ok(None)
+}
+
+/// Checks whether `guess` matches any of the flags. Per-team flags have to be
+/// filled in with [`team_flags`] first, and are then matched exactly.
+pub fn check<'a>(flag_type: FlagType, flags: impl IntoIterator<Item = &'a str>, guess: &str) -> Result<bool, InvalidFlag> {
+    for flag in flags {
+        let matches = match flag_type {
+            FlagType::Exact | FlagType::PerTeam => constant_time_eq(flag.as_bytes(), guess.as_bytes()),
+            FlagType::CaseInsensitive => {
+                let flag = flag.to_lowercase();
+                let guess = guess.to_lowercase();
+                constant_time_eq(flag.as_bytes(), guess.as_bytes())
+            },
+            FlagType::Regex => compile(flag)?.is_match(guess),
+        };
+
+        if matches {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_matches_only_identical_flags() {
+        let flags = ["bcactf{flag}"];
+
+        assert_eq!(check(FlagType::Exact, flags, "bcactf{flag}"), Ok(true));
+        assert_eq!(check(FlagType::Exact, flags, "BCACTF{FLAG}"), Ok(false));
+        assert_eq!(check(FlagType::Exact, flags, "bcactf{flag} "), Ok(false));
+        assert_eq!(check(FlagType::Exact, flags, ""), Ok(false));
+    }
+
+    #[test]
+    fn case_insensitive_ignores_case() {
+        let flags = ["bcactf{Flag}"];
+
+        assert_eq!(check(FlagType::CaseInsensitive, flags, "BCACTF{FLAG}"), Ok(true));
+        assert_eq!(check(FlagType::CaseInsensitive, flags, "bcactf{flag}"), Ok(true));
+        assert_eq!(check(FlagType::CaseInsensitive, flags, "bcactf{flags}"), Ok(false));
+    }
+
+    #[test]
+    fn regex_matches_whole_guess() {
+        let flags = [r"bcactf\{[a-f0-9]{8}\}"];
+
+        assert_eq!(check(FlagType::Regex, flags, "bcactf{0123abcd}"), Ok(true));
+        assert_eq!(check(FlagType::Regex, flags, "bcactf{0123abcg}"), Ok(false));
+        assert_eq!(check(FlagType::Regex, flags, "xbcactf{0123abcd}"), Ok(false));
+        assert_eq!(check(FlagType::Regex, flags, "bcactf{0123abcd}x"), Ok(false));
+    }
+
+    #[test]
+    fn regex_alternation_is_anchored() {
+        let flags = [r"bcactf\{a\}|bcactf\{b\}"];
+
+        assert_eq!(check(FlagType::Regex, flags, "bcactf{b}"), Ok(true));
+        assert_eq!(check(FlagType::Regex, flags, "bcactf{a}junk"), Ok(false));
+        assert_eq!(check(FlagType::Regex, flags, "junkbcactf{b}"), Ok(false));
+    }
+
+    #[test]
+    fn any_accepted_flag_matches() {
+        let flags = ["bcactf{one}", "bcactf{two}"];
+
+        assert_eq!(check(FlagType::Exact, flags, "bcactf{one}"), Ok(true));
+        assert_eq!(check(FlagType::Exact, flags, "bcactf{two}"), Ok(true));
+        assert_eq!(check(FlagType::Exact, flags, "bcactf{three}"), Ok(false));
+        assert_eq!(check(FlagType::Exact, [], "bcactf{one}"), Ok(false));
+    }
+
+    #[test]
+    fn only_regex_flags_can_be_invalid() {
+        assert!(validate(FlagType::Regex, [r"bcactf\{[a-f0-9]{8}\}"]).is_ok());
+        assert!(validate(FlagType::Regex, ["bcactf{(unclosed}"]).is_err());
+        assert!(validate(FlagType::Exact, ["bcactf{(unclosed}"]).is_ok());
+        assert!(validate(FlagType::CaseInsensitive, ["bcactf{(unclosed}"]).is_ok());
+
+        assert!(check(FlagType::Regex, ["bcactf{(unclosed}"], "bcactf{(unclosed}").is_err());
+    }
+
+    fn per_team_chall() -> ChallFlagsRow {
+        ChallFlagsRow {
+            flag: "bcactf{shared_{}}".into(),
+            flag_type: FlagType::PerTeam,
+            accepted_flags: vec![],
+            flag_secret: Some("secret".into()),
+        }
+    }
+
+    fn flag_for(template: &str, secret: &str, team_id: Uuid) -> String {
+        let Ok(flag) = team_flag(template, secret, team_id) else {
+            panic!("HMAC accepts keys of any length");
+        };
+        flag
+    }
+
+    #[test]
+    fn per_team_flags_differ_between_teams() {
+        let (team_a, team_b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let flag_a = flag_for("bcactf{shared_{}}", "secret", team_a);
+        let flag_b = flag_for("bcactf{shared_{}}", "secret", team_b);
+
+        assert_ne!(flag_a, flag_b);
+        assert_eq!(flag_a, flag_for("bcactf{shared_{}}", "secret", team_a));
+        assert_ne!(flag_a, flag_for("bcactf{shared_{}}", "other secret", team_a));
+
+        assert!(flag_a.starts_with("bcactf{shared_"));
+        assert_eq!(flag_a.len(), "bcactf{shared_}".len() + TEAM_VALUE_LEN);
+    }
+
+    #[test]
+    fn per_team_flags_only_accepted_from_their_team() {
+        let chall = per_team_chall();
+        let (team_a, team_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let flag_a = flag_for(&chall.flag, "secret", team_a);
+
+        assert_eq!(check_for_team(&chall, team_a, &flag_a), Ok(true));
+        assert_eq!(check_for_team(&chall, team_b, &flag_a), Ok(false));
+        assert_eq!(check_for_team(&chall, team_a, "bcactf{shared_{}}"), Ok(false));
+    }
+
+    #[test]
+    fn shared_flags_are_traced_to_their_owner() {
+        let chall = per_team_chall();
+        let teams = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        let flag = flag_for(&chall.flag, "secret", teams[1]);
+
+        assert_eq!(find_owner(&chall, teams, &flag), Ok(Some(teams[1])));
+        assert_eq!(find_owner(&chall, teams, "bcactf{shared_0000000000000000}"), Ok(None));
+    }
+
+    #[test]
+    fn only_guesses_shaped_like_the_template_fit_it() {
+        let template = "bcactf{shared_{}}";
+        let flag = flag_for(template, "secret", Uuid::new_v4());
+
+        assert!(fits_template(template, &flag));
+        assert!(fits_template(template, "bcactf{shared_0123456789abcdef}"));
+        assert!(!fits_template(template, "bcactf{shared_0123456789abcde}"));
+        assert!(!fits_template(template, "bcactf{shared_0123456789abcdef0}"));
+        assert!(!fits_template(template, "bcactf{shared_0123456789ABCDEF}"));
+        assert!(!fits_template(template, "bcactf{shared_0123456789abcdeg}"));
+        assert!(!fits_template(template, "bcactf{other_0123456789abcdef}"));
+        assert!(!fits_template(template, ""));
+
+        let template = "{}-{}";
+        assert!(fits_template(template, "0123456789abcdef-fedcba9876543210"));
+        assert!(!fits_template(template, "0123456789abcdef-"));
+    }
+
+    #[test]
+    fn per_team_flags_need_a_placeholder_and_secret() {
+        assert!(validate(FlagType::PerTeam, ["bcactf{shared_{}}"]).is_ok());
+        assert!(validate(FlagType::PerTeam, ["bcactf{shared}"]).is_err());
+
+        let chall = ChallFlagsRow { flag_secret: None, ..per_team_chall() };
+        assert!(check_for_team(&chall, Uuid::new_v4(), "bcactf{shared_}").is_err());
+    }
+}
@@ -1,76 +1,253 @@
-mod prepared;
+pub (super) mod prepared;
 
+mod audit;
 mod challs;
+mod outbox;
 mod solves;
 mod teams;
 mod users;
 
+mod export;
+mod flags;
+mod scoring;
+
+#[cfg(test)]
+mod testing;
+
 use async_trait::async_trait;
+use serde::{ Serialize, Serializer };
 
+use crate::Token;
 use crate::payloads::incoming::ToSql;
+use crate::payloads::incoming::sql::Auth;
 use crate::payloads::outgoing::sql::{FromSql, FromSqlErr};
 use crate::logging::*;
 
 use super::{Handle, ResponseFrom};
 
-use sqlx::{ pool::PoolConnection, Postgres };
-type Ctx = PoolConnection<Postgres>;
+use sqlx::{ Postgres, Transaction };
+pub (super) type Ctx = Transaction<'static, Postgres>;
 
 pub use challs::{ get_chall_id_by_source_folder, get_chall_source_folder_by_id };
 
+/// Checks that the user exists, authenticates with `admin_auth`, and is an
+/// admin.
+async fn check_admin(ctx: &mut Ctx, admin_id: uuid::Uuid, admin_auth: Auth) -> Result<(), FromSqlErr> {
+    if !prepared::users::check_user_auth(ctx, admin_id, admin_auth).await? {
+        return Err(FromSqlErr::Auth);
+    }
+
+    let Some(admin) = prepared::users::get_user(ctx, admin_id).await? else {
+        return Err(FromSqlErr::DoesNotExist(admin_id));
+    };
+    if !admin.admin {
+        warn!("Non-admin user {admin_id} tried to make an admin request");
+        return Err(FromSqlErr::Auth);
+    }
+
+    Ok(())
+}
+
+/// Rejects the request if the user or their team is banned.
+async fn check_not_banned(ctx: &mut Ctx, user_id: uuid::Uuid, team_id: uuid::Uuid) -> Result<(), FromSqlErr> {
+    if let Some(banned_id) = prepared::users::get_banned(ctx, user_id, team_id).await? {
+        info!("Rejected request from banned user or team {banned_id}");
+        return Err(FromSqlErr::Banned(banned_id));
+    }
+    Ok(())
+}
+
+/// Rejects the request if the team can't see the challenge, because it's
+/// hidden or because the team hasn't solved all of its prerequisites. Hidden
+/// challenges are treated as not existing.
+async fn check_chall_available(ctx: &mut Ctx, chall_id: uuid::Uuid, team_id: uuid::Uuid) -> Result<(), FromSqlErr> {
+    if prepared::challenges::get_chall_visible(ctx, chall_id).await? != Some(true) {
+        return Err(FromSqlErr::DoesNotExist(chall_id));
+    }
+    if !prepared::challenges::chall_unlocked_for_team(ctx, chall_id, team_id).await? {
+        return Err(FromSqlErr::ChallengeLocked(chall_id));
+    }
+    Ok(())
+}
+
+/// Runs a single (non-sequence) query inside of the given transaction.
+async fn handle_query(ctx: &mut Ctx, query: ToSql) -> Result<FromSql, FromSqlErr> {
+    let return_payload = match query {
+        ToSql::Chall(chall_query) => {
+            debug!("SQL req classified as chall req");
+            match challs::handle(ctx, chall_query).await {
+                Ok(return_payload) => return_payload,
+                Err(e) => {
+                    debug!("Challs SQL error: {e:?}");
+                    return Err(e);
+                }
+            }
+        },
+        ToSql::Team(team_query) => {
+            debug!("SQL req classified as team req");
+            match teams::handle(ctx, team_query).await {
+                Ok(return_payload) => return_payload,
+                Err(e) => {
+                    debug!("Teams SQL error: {e:?}");
+                    return Err(e);
+                }
+            }
+        },
+        ToSql::User(user_query) => {
+            debug!("SQL req classified as user req");
+            match users::handle(ctx, user_query).await {
+                Ok(return_payload) => return_payload,
+                Err(e) => {
+                    debug!("Users SQL error: {e:?}");
+                    return Err(e);
+                }
+            }
+        },
+        ToSql::Solve(solve_query) => {
+            debug!("SQL req classified as solve req");
+            match solves::handle(ctx, solve_query).await {
+                Ok(return_payload) => return_payload,
+                Err(e) => {
+                    debug!("Solve SQL error: {e:?}");
+                    return Err(e);
+                }
+            }
+        },
+        ToSql::Outbox(outbox_query) => {
+            debug!("SQL req classified as outbox req");
+            match outbox::handle(ctx, outbox_query).await {
+                Ok(return_payload) => return_payload,
+                Err(e) => {
+                    debug!("Outbox SQL error: {e:?}");
+                    return Err(e);
+                }
+            }
+        },
+        ToSql::Audit(audit_query) => {
+            debug!("SQL req classified as audit req");
+            match audit::handle(ctx, audit_query).await {
+                Ok(return_payload) => return_payload,
+                Err(e) => {
+                    debug!("Audit SQL error: {e:?}");
+                    return Err(e);
+                }
+            }
+        },
+        ToSql::Sequence(_) => {
+            warn!("Nested SQL sequence rejected");
+            return Err(FromSqlErr::BadRequest("SQL sequences can't be nested".into()));
+        },
+    };
+    Ok(return_payload)
+}
+
+/// Runs each of the queries in order inside of the same transaction, stopping
+/// at the first one that fails.
+async fn handle_sequence(ctx: &mut Ctx, queries: Vec<ToSql>) -> Result<FromSql, FromSqlErr> {
+    debug!("SQL req classified as sequence of {} reqs", queries.len());
+
+    let mut payloads = Vec::with_capacity(queries.len());
+    for (index, query) in queries.into_iter().enumerate() {
+        match handle_query(ctx, query).await {
+            Ok(payload) => payloads.push(payload),
+            Err(e) => {
+                debug!("SQL sequence failed at step {index}");
+                return Err(FromSqlErr::SequenceStep { index, error: Box::new(e) });
+            }
+        }
+    }
+    Ok(FromSql::Sequence(payloads))
+}
+
+/// A SQL query, along with the token of the request it's from if it's audited
+/// (see [`super::audit::PendingSql`]). It's serialized as just the query.
+pub (super) struct Audited {
+    token: Option<Token>,
+    query: ToSql,
+}
+
+impl Audited {
+    pub (super) fn new(token: Option<Token>, query: ToSql) -> Self {
+        Self { token, query }
+    }
+}
+
+impl Serialize for Audited {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.query.serialize(serializer)
+    }
+}
+
+/// Writes the audit entries of a query that failed, in a transaction of their
+/// own since the query's was rolled back.
+async fn record_failed(audited: super::audit::PendingSql, token: Token, error: &FromSqlErr) {
+    let write = async {
+        let mut transaction = crate::sql::transaction().await?;
+        audited.record(&mut transaction, token, Err(error)).await?;
+        transaction.commit().await
+    };
+    if let Err(e) = write.await {
+        error!("Failed to write to the audit log: {e}");
+    }
+}
+
 #[async_trait]
-impl Handle for ToSql {
+impl Handle for Audited {
     type SuccessPayload = FromSql;
     type ErrorPayload = FromSqlErr;
     async fn handle(self) -> ResponseFrom<Self> {
         trace!("Handling SQL req");
-        
-        let sql_connection = crate::sql::connection().await?;
-        debug!("Database connection acquired.");
-
-        let return_payload = match self {
-            ToSql::Chall(chall_query) => {
-                debug!("SQL req classified as chall req");
-                match challs::handle(sql_connection, chall_query).await {
-                    Ok(return_payload) => return_payload,
-                    Err(e) => {
-                        debug!("Challs SQL error: {e:?}");
-                        return Err(e);
-                    }
-                }
-            },
-            ToSql::Team(team_query) => {
-                debug!("SQL req classified as team req");
-                match teams::handle(sql_connection, team_query).await {
-                    Ok(return_payload) => return_payload,
-                    Err(e) => {
-                        debug!("Teams SQL error: {e:?}");
-                        return Err(e);
+
+        let audited = self.token.map(|token| (token, super::audit::PendingSql::new(&self.query)));
+
+        let mut transaction = crate::sql::transaction().await?;
+        debug!("Database transaction started.");
+
+        let handling = async {
+            match self.query {
+                ToSql::Sequence(queries) => handle_sequence(&mut transaction, queries).await,
+                query => handle_query(&mut transaction, query).await,
+            }
+        };
+        let (result, counts) = crate::metrics::Uncommitted::collect(handling).await;
+
+        match result {
+            Ok(return_payload) => {
+                if let Some((token, audited)) = audited {
+                    if let Err(e) = audited.record(&mut transaction, token, Ok(&return_payload)).await {
+                        error!("Failed to write to the audit log: {e}");
                     }
                 }
+
+                transaction.commit().await?;
+                debug!("Database transaction committed.");
+
+                super::outbox::wake_if_enqueued();
+                counts.commit();
+                Ok(return_payload)
             },
-            ToSql::User(user_query) => {
-                debug!("SQL req classified as user req");
-                match users::handle(sql_connection, user_query).await {
-                    Ok(return_payload) => return_payload,
-                    Err(e) => {
-                        debug!("Users SQL error: {e:?}");
-                        return Err(e);
-                    }
+            Err(e) => {
+                if let Err(rollback_err) = transaction.rollback().await {
+                    warn!("Failed to roll back database transaction: {rollback_err}");
+                } else {
+                    debug!("Database transaction rolled back.");
                 }
-            },
-            ToSql::Solve(solve_query) => {
-                debug!("SQL req classified as solve req");
-                match solves::handle(sql_connection, solve_query).await {
-                    Ok(return_payload) => return_payload,
-                    Err(e) => {
-                        debug!("Solve SQL error: {e:?}");
-                        return Err(e);
-                    }
+
+                if let Some((token, audited)) = audited {
+                    record_failed(audited, token, &e).await;
                 }
+                Err(e)
             },
-        };
-        Ok(return_payload)
+        }
     }
 }
 
+/// Handles the query without auditing it.
+#[async_trait]
+impl Handle for ToSql {
+    type SuccessPayload = FromSql;
+    type ErrorPayload = FromSqlErr;
+    async fn handle(self) -> ResponseFrom<Self> {
+        Audited::new(None, self).handle().await
+    }
+}
@@ -0,0 +1,47 @@
+use crate::logging::*;
+use crate::payloads::*;
+
+use incoming::sql::OutboxQuery;
+use outgoing::sql::{FromSql, FromSqlErr};
+
+use super::prepared::outbox as queries;
+use queries::{ get_entries, get_entry, replay };
+
+use super::check_admin;
+
+pub async fn handle(ctx: &mut super::Ctx, query: OutboxQuery) -> Result<FromSql, FromSqlErr> {
+    trace!("Handling SQL outbox req");
+
+    let success_res = match query {
+        OutboxQuery::List { admin_id, admin_auth, status, limit } => {
+            debug!("SQL outbox req classified as 'List<{status:?}, {limit}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            // Cap here to prevent server from being overloaded by a
+            // badly-written client
+            if limit > 500 {
+                return Err(FromSqlErr::RequestTooBig(limit as u64, 500))
+            }
+
+            FromSql::OutboxEntryArr(get_entries(ctx, status, limit).await?)
+        },
+        OutboxQuery::Replay { admin_id, admin_auth, id } => {
+            debug!("SQL outbox req classified as 'Replay<{id}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            if replay(ctx, id).await? != 1 {
+                return Err(FromSqlErr::DoesNotExist(id));
+            }
+            crate::handlers::outbox::wake_on_commit();
+
+            if let Some(entry) = get_entry(ctx, id).await? {
+                FromSql::OutboxEntry(entry)
+            } else {
+                return Err(FromSqlErr::DoesNotExist(id))
+            }
+        },
+    };
+    Ok(success_res)
+}
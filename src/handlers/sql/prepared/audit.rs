@@ -0,0 +1,76 @@
+use sqlx::{ query, query_as, PgConnection };
+use uuid::Uuid;
+
+use super::Ctx;
+use crate::Token;
+use crate::payloads::incoming::sql::{ AuditFilter, AuditOutcome };
+use crate::payloads::outgoing::sql::AuditEntry;
+
+
+#[derive(Debug, Clone)]
+pub struct AuditInput {
+    pub token: Token,
+    pub operation: String,
+    pub params: serde_json::Value,
+    pub actor_id: Option<Uuid>,
+    pub affected_ids: Vec<Uuid>,
+    pub outcome: AuditOutcome,
+    pub status_code: u16,
+}
+
+pub async fn insert_entry(ctx: &mut PgConnection, input: AuditInput) -> Result<Uuid, sqlx::Error> {
+    let query = query!(
+        r#"
+            INSERT INTO audit_log (token, operation, params, actor_id, affected_ids, outcome, status_code)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id;
+        "#,
+        input.token as Token,
+        input.operation,
+        input.params,
+        input.actor_id,
+        &input.affected_ids,
+        input.outcome as AuditOutcome,
+        input.status_code as i16,
+    );
+    query
+        .fetch_one(ctx)
+        .await
+        .map(|row| row.id)
+}
+
+/// Gets the entries matching the filter, newest first, starting after the
+/// entry `before` if it's given.
+pub async fn get_entries(ctx: &mut Ctx, before: Option<Uuid>, limit: u32, filter: &AuditFilter) -> Result<Vec<AuditEntry>, sqlx::Error> {
+    let query = query_as!(
+        AuditEntry,
+        r#"
+            SELECT
+                id, token AS "token: _", operation, params,
+                actor_id, affected_ids,
+                outcome AS "outcome: _", status_code,
+                inserted_at
+            FROM audit_log
+            WHERE
+                (
+                    $1::uuid IS NULL OR
+                    (inserted_at, id) < (SELECT inserted_at, id FROM audit_log WHERE id = $1)
+                ) AND
+                ($3::text IS NULL OR operation = $3) AND
+                ($4::uuid IS NULL OR affected_ids @> ARRAY[$4::uuid]) AND
+                ($5::uuid IS NULL OR actor_id = $5) AND
+                ($6::auth_token IS NULL OR token = $6) AND
+                ($7::audit_outcome IS NULL OR outcome = $7)
+            ORDER BY inserted_at DESC, id DESC
+            LIMIT $2;
+        "#,
+        before,
+        limit as i64,
+        filter.operation,
+        filter.affected_id,
+        filter.actor_id,
+        filter.token as Option<Token>,
+        filter.outcome as Option<AuditOutcome>,
+    );
+    query.fetch_all(ctx).await
+}
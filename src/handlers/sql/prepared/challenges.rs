@@ -1,9 +1,9 @@
-use sqlx::{ query, query_as };
+use sqlx::{ query, query_as, PgConnection };
 use uuid::Uuid;
 
 use super::Ctx;
 use crate::payloads::{
-    incoming::sql::Link,
+    incoming::sql::{ FlagType, HintInput, Link, ScoringType },
     outgoing::sql::Chall,
 };
 
@@ -22,15 +22,27 @@ pub async fn set_chall_updated(ctx: &mut Ctx, id: Uuid) -> Result<u64, sqlx::Err
         .map(|res| res.rows_affected())
 }
 
-pub async fn get_chall(ctx: &mut Ctx, id: Uuid) -> Result<Option<Chall>, sqlx::Error> {
+pub async fn get_chall(ctx: &mut PgConnection, id: Uuid) -> Result<Option<Chall>, sqlx::Error> {
     let query = query_as!(
         Chall,
         r#"
             SELECT
                 challenges.id,
-                name as "name: _", description, points,
-                authors, hints, categories, tags,
+                name as "name: _", description, points, current_points,
+                authors, categories, tags,
                 solve_count, visible, source_folder,
+                ARRAY(
+                    SELECT hint.id FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_ids!",
+                ARRAY(
+                    SELECT hint.cost FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_costs!",
+                ARRAY(
+                    SELECT prereq.prerequisite_id FROM challenge_prerequisites AS prereq
+                    WHERE prereq.challenge_id = challenges.id
+                ) as "prerequisites!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'nc'    ), ARRAY[]::text[]) as "links_nc!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'web'   ), ARRAY[]::text[]) as "links_web!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'admin' ), ARRAY[]::text[]) as "links_admin!",
@@ -45,15 +57,27 @@ pub async fn get_chall(ctx: &mut Ctx, id: Uuid) -> Result<Option<Chall>, sqlx::E
     query.fetch_optional(ctx).await
 }
 
-pub async fn get_chall_by_source_folder(ctx: &mut Ctx, folder: &str) -> Result<Option<Chall>, sqlx::Error> {
+pub async fn get_chall_by_source_folder(ctx: &mut PgConnection, folder: &str) -> Result<Option<Chall>, sqlx::Error> {
     let query = query_as!(
         Chall,
         r#"
             SELECT
                 challenges.id,
-                name as "name: _", description, points,
-                authors, hints, categories, tags,
+                name as "name: _", description, points, current_points,
+                authors, categories, tags,
                 solve_count, visible, source_folder,
+                ARRAY(
+                    SELECT hint.id FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_ids!",
+                ARRAY(
+                    SELECT hint.cost FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_costs!",
+                ARRAY(
+                    SELECT prereq.prerequisite_id FROM challenge_prerequisites AS prereq
+                    WHERE prereq.challenge_id = challenges.id
+                ) as "prerequisites!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'nc'    ), ARRAY[]::text[]) as "links_nc!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'web'   ), ARRAY[]::text[]) as "links_web!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'admin' ), ARRAY[]::text[]) as "links_admin!",
@@ -74,9 +98,21 @@ pub async fn get_all_challs(ctx: &mut Ctx) -> Result<Vec<Chall>, sqlx::Error> {
         r#"
             SELECT
                 challenges.id,
-                name as "name: _", description, points,
-                authors, hints, categories, tags,
+                name as "name: _", description, points, current_points,
+                authors, categories, tags,
                 solve_count, visible, source_folder,
+                ARRAY(
+                    SELECT hint.id FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_ids!",
+                ARRAY(
+                    SELECT hint.cost FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_costs!",
+                ARRAY(
+                    SELECT prereq.prerequisite_id FROM challenge_prerequisites AS prereq
+                    WHERE prereq.challenge_id = challenges.id
+                ) as "prerequisites!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'nc'    ), ARRAY[]::text[]) as "links_nc!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'web'   ), ARRAY[]::text[]) as "links_web!",
                 COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'admin' ), ARRAY[]::text[]) as "links_admin!",
@@ -89,19 +125,61 @@ pub async fn get_all_challs(ctx: &mut Ctx) -> Result<Vec<Chall>, sqlx::Error> {
     query.fetch_all(ctx).await
 }
 
+/// Gets the visible challenges the team has unlocked.
+pub async fn get_available_challs(ctx: &mut Ctx, team_id: Uuid) -> Result<Vec<Chall>, sqlx::Error> {
+    let query = query_as!(
+        Chall,
+        r#"
+            SELECT
+                challenges.id,
+                name as "name: _", description, points, current_points,
+                authors, categories, tags,
+                solve_count, visible, source_folder,
+                ARRAY(
+                    SELECT hint.id FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_ids!",
+                ARRAY(
+                    SELECT hint.cost FROM challenge_hints AS hint
+                    WHERE hint.challenge_id = challenges.id ORDER BY hint.position
+                ) as "hint_costs!",
+                ARRAY(
+                    SELECT prereq.prerequisite_id FROM challenge_prerequisites AS prereq
+                    WHERE prereq.challenge_id = challenges.id
+                ) as "prerequisites!",
+                COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'nc'    ), ARRAY[]::text[]) as "links_nc!",
+                COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'web'   ), ARRAY[]::text[]) as "links_web!",
+                COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'admin' ), ARRAY[]::text[]) as "links_admin!",
+                COALESCE(array_agg(links.url) FILTER (WHERE links.type = 'static'), ARRAY[]::text[]) as "links_static!"
+            FROM challenges
+                LEFT JOIN challenge_links as links ON links.challenge_id = challenges.id
+            WHERE visible AND challenge_unlocked_for_team(challenges.id, $1)
+            GROUP BY challenges.id;
+        "#,
+        team_id,
+    );
+    query.fetch_all(ctx).await
+}
+
 
 #[derive(Debug, Clone)]
 pub struct ChallInput {
     pub name: Option<String>,
     pub description: Option<String>,
     pub points: Option<i32>,
+    pub scoring_type: Option<ScoringType>,
+    pub min_points: Option<i32>,
+    pub decay: Option<i32>,
     pub authors: Option<Vec<String>>,
-    pub hints: Option<Vec<String>>,
+    pub hints: Option<Vec<HintInput>>,
     pub categories: Option<Vec<String>>,
     pub tags: Option<Vec<String>>,
     pub links: Option<Vec<Link>>,
     pub visible: Option<bool>,
     pub source_folder: Option<String>,
+    pub flag_type: Option<FlagType>,
+    pub accepted_flags: Option<Vec<String>>,
+    pub flag_secret: Option<String>,
 }
 
 pub async fn update_chall(ctx: &mut Ctx, id: Uuid, input: ChallInput) -> Result<Option<Chall>, sqlx::Error> {
@@ -113,11 +191,16 @@ pub async fn update_chall(ctx: &mut Ctx, id: Uuid, input: ChallInput) -> Result<
                 description = COALESCE($3, description),
                 points = COALESCE($4, points),
                 authors = COALESCE($5, authors),
-                hints = COALESCE($6, hints),
-                categories = COALESCE($7, categories),
-                tags = COALESCE($8, tags),
-                visible = COALESCE($9, visible),
-                source_folder = COALESCE($10, source_folder)
+                categories = COALESCE($6, categories),
+                tags = COALESCE($7, tags),
+                visible = COALESCE($8, visible),
+                source_folder = COALESCE($9, source_folder),
+                scoring_type = COALESCE($10, scoring_type),
+                min_points = COALESCE($11, min_points),
+                decay = COALESCE($12, decay),
+                flag_type = COALESCE($13, flag_type),
+                accepted_flags = COALESCE($14, accepted_flags),
+                flag_secret = COALESCE($15, flag_secret)
             WHERE id = $1;
         "#,
         id,
@@ -125,11 +208,16 @@ pub async fn update_chall(ctx: &mut Ctx, id: Uuid, input: ChallInput) -> Result<
         input.description,
         input.points,
         input.authors.as_deref(),
-        input.hints.as_deref(),
         input.categories.as_deref(),
         input.tags.as_deref(),
         input.visible,
         input.source_folder,
+        input.scoring_type as Option<ScoringType>,
+        input.min_points,
+        input.decay,
+        input.flag_type as Option<FlagType>,
+        input.accepted_flags.as_deref(),
+        input.flag_secret,
     );
     let affected = query
         .execute(&mut *ctx)
@@ -141,6 +229,9 @@ pub async fn update_chall(ctx: &mut Ctx, id: Uuid, input: ChallInput) -> Result<
     if let Some(links) = input.links {
         set_chall_links(&mut *ctx, id, links).await?;
     }
+    if let Some(hints) = input.hints {
+        set_chall_hints(&mut *ctx, id, hints).await?;
+    }
     set_chall_updated(&mut *ctx, id).await?;
 
     let Some(output) = get_chall(ctx, id).await? else {
@@ -186,14 +277,40 @@ pub async fn set_chall_links(ctx: &mut Ctx, id: Uuid, links: Vec<Link>) -> Resul
     Ok(())
 }
 
+/// Sets the challenge's hints. Hints that keep their position and content keep
+/// their ids, so teams that unlocked them keep them unlocked (at the new cost).
+/// See `set_challenge_hints` in `schema/functions.sql`.
+pub async fn set_chall_hints(ctx: &mut Ctx, id: Uuid, hints: Vec<HintInput>) -> Result<(), sqlx::Error> {
+    let (contents, costs): (Vec<String>, Vec<i32>) = hints
+        .into_iter()
+        .map(HintInput::into_parts)
+        .unzip();
+
+    let query = query!(
+        r#"
+        SELECT set_challenge_hints($1, $2, $3);
+        "#,
+        id,
+        &contents,
+        &costs,
+    );
+    query.execute(&mut *ctx).await?;
+
+    set_chall_updated(ctx, id).await?;
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub struct NewChallInput {
     pub id: Option<Uuid>,
     pub name: String,
     pub description: String,
     pub points: i32,
+    pub scoring_type: ScoringType,
+    pub min_points: i32,
+    pub decay: i32,
     pub authors: Vec<String>,
-    pub hints: Vec<String>,
+    pub hints: Vec<HintInput>,
     pub categories: Vec<String>,
     pub tags: Vec<String>,
     pub links: Vec<Link>,
@@ -201,6 +318,9 @@ pub struct NewChallInput {
     pub source_folder: String,
 
     pub flag: String,
+    pub flag_type: FlagType,
+    pub accepted_flags: Vec<String>,
+    pub flag_secret: Option<String>,
 }
 
 pub async fn create_chall(ctx: &mut Ctx, input: NewChallInput) -> Result<Chall, sqlx::Error> {
@@ -209,10 +329,12 @@ pub async fn create_chall(ctx: &mut Ctx, input: NewChallInput) -> Result<Chall,
             INSERT INTO challenges (
                 id,
                 name, description, points,
-                authors, hints, categories, tags,
-                visible, source_folder, flag
+                authors, categories, tags,
+                visible, source_folder, flag,
+                scoring_type, min_points, decay, current_points,
+                flag_type, accepted_flags, flag_secret
             )
-            VALUES (COALESCE($1, uuid_generate_v4()), $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            VALUES (COALESCE($1, uuid_generate_v4()), $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $4, $14, $15, $16)
             ON CONFLICT (source_folder)
             DO UPDATE SET
                 id = COALESCE($1, uuid_generate_v4()),
@@ -220,24 +342,34 @@ pub async fn create_chall(ctx: &mut Ctx, input: NewChallInput) -> Result<Chall,
                 description = $3,
                 points = $4,
                 authors = $5,
-                hints = $6,
-                categories = $7,
-                tags = $8,
-                visible = $9,
-                source_folder = $10,
-                flag = $11;
+                categories = $6,
+                tags = $7,
+                visible = $8,
+                source_folder = $9,
+                flag = $10,
+                scoring_type = $11,
+                min_points = $12,
+                decay = $13,
+                flag_type = $14,
+                accepted_flags = $15,
+                flag_secret = COALESCE($16, challenges.flag_secret);
         "#,
         input.id,
         input.name: String,
         input.description,
         input.points,
         &input.authors,
-        &input.hints,
         &input.categories,
         &input.tags,
         input.visible,
         &input.source_folder,
-        input.flag
+        input.flag,
+        input.scoring_type as ScoringType,
+        input.min_points,
+        input.decay,
+        input.flag_type as FlagType,
+        &input.accepted_flags,
+        input.flag_secret,
     );
     query.execute(&mut *ctx).await?;
 
@@ -246,7 +378,175 @@ pub async fn create_chall(ctx: &mut Ctx, input: NewChallInput) -> Result<Chall,
     };
 
     set_chall_links(&mut *ctx, output.id, input.links).await?;
+    set_chall_hints(&mut *ctx, output.id, input.hints).await?;
 
     set_chall_updated(ctx, output.id).await?;
     Ok(output)
 }
+
+
+#[derive(Debug, Clone, Copy)]
+pub struct ChallScoringRow {
+    pub scoring_type: ScoringType,
+    pub points: i32,
+    pub min_points: i32,
+    pub decay: i32,
+    pub solve_count: i32,
+    pub current_points: i32,
+}
+
+pub async fn get_chall_scoring(ctx: &mut Ctx, id: Uuid) -> Result<Option<ChallScoringRow>, sqlx::Error> {
+    let query = query_as!(
+        ChallScoringRow,
+        r#"
+            SELECT
+                scoring_type AS "scoring_type: _",
+                points, min_points, decay,
+                solve_count, current_points
+            FROM challenges
+            WHERE id = $1;
+        "#,
+        id,
+    );
+    query.fetch_optional(ctx).await
+}
+
+pub async fn set_chall_current_points(ctx: &mut Ctx, id: Uuid, current_points: i32) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE challenges
+            SET current_points = $2
+            WHERE id = $1;
+        "#,
+        id,
+        current_points,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+pub async fn update_scores_for_chall(ctx: &mut Ctx, id: Uuid) -> Result<(), sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT update_scores_for_challenge($1);
+        "#,
+        id,
+    );
+    query.execute(ctx).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct ChallFlagsRow {
+    pub flag: String,
+    pub flag_type: FlagType,
+    pub accepted_flags: Vec<String>,
+    pub flag_secret: Option<String>,
+}
+
+impl ChallFlagsRow {
+    /// The primary flag, followed by the other accepted flags.
+    pub fn flags(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.flag.as_str()).chain(self.accepted_flags.iter().map(String::as_str))
+    }
+}
+
+pub async fn get_chall_flags(ctx: &mut Ctx, id: Uuid) -> Result<Option<ChallFlagsRow>, sqlx::Error> {
+    let query = query_as!(
+        ChallFlagsRow,
+        r#"
+            SELECT flag, flag_type AS "flag_type: _", accepted_flags, flag_secret
+            FROM challenges
+            WHERE id = $1;
+        "#,
+        id,
+    );
+    query.fetch_optional(ctx).await
+}
+
+/// Sets the challenge's flag secret, unless it already has one.
+pub async fn init_chall_flag_secret(ctx: &mut Ctx, id: Uuid, secret: &str) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE challenges
+            SET flag_secret = COALESCE(flag_secret, $2)
+            WHERE id = $1;
+        "#,
+        id,
+        secret,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+/// Replaces the challenge's prerequisites, returning the ids that aren't
+/// challenges. Those are left out.
+pub async fn set_chall_prerequisites(ctx: &mut Ctx, id: Uuid, prerequisites: &[Uuid]) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT set_challenge_prerequisites($1, $2) AS "missing!";
+        "#,
+        id,
+        prerequisites,
+    );
+    let missing = query
+        .fetch_all(&mut *ctx)
+        .await?
+        .into_iter()
+        .map(|row| row.missing)
+        .collect();
+
+    set_chall_updated(ctx, id).await?;
+    Ok(missing)
+}
+
+/// Whether the challenge ends up requiring itself through its prerequisites.
+pub async fn chall_prerequisites_cyclic(ctx: &mut Ctx, id: Uuid) -> Result<bool, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT challenge_prerequisites_cyclic($1) AS "cyclic!";
+        "#,
+        id,
+    );
+    Ok(query.fetch_one(ctx).await?.cyclic)
+}
+
+/// Whether the challenge is visible, if it exists.
+pub async fn get_chall_visible(ctx: &mut Ctx, id: Uuid) -> Result<Option<bool>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT visible FROM challenges WHERE id = $1;
+        "#,
+        id,
+    );
+    Ok(query.fetch_optional(ctx).await?.map(|row| row.visible))
+}
+
+/// Whether the team has solved all of the challenge's prerequisites.
+pub async fn chall_unlocked_for_team(ctx: &mut Ctx, id: Uuid, team_id: Uuid) -> Result<bool, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT challenge_unlocked_for_team($1, $2) AS "unlocked!";
+        "#,
+        id,
+        team_id,
+    );
+    Ok(query.fetch_one(ctx).await?.unlocked)
+}
+
+/// Moves the challenge and its links to the archive tables. Its solves have to
+/// be archived first, see [`super::solves::clear_all_solves_for_challenge`].
+pub async fn archive_chall(ctx: &mut Ctx, id: Uuid) -> Result<(), sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT archive_challenge($1);
+        "#,
+        id,
+    );
+    query.execute(ctx).await?;
+    Ok(())
+}
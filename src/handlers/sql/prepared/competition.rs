@@ -0,0 +1,53 @@
+use chrono::NaiveDateTime;
+use sqlx::query;
+
+use super::Ctx;
+
+/// Gets when the scoreboard freeze was lifted, if it has been.
+pub async fn get_scoreboard_unfrozen_at(ctx: &mut Ctx) -> Result<Option<NaiveDateTime>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT scoreboard_unfrozen_at FROM competition_state;
+        "#,
+    );
+    let row = query.fetch_optional(ctx).await?;
+    Ok(row.and_then(|row| row.scoreboard_unfrozen_at))
+}
+
+/// Lifts the scoreboard freeze that started at `frozen_at`, returning when it
+/// was lifted. Lifting it again keeps the original time, but a lift from
+/// before `frozen_at` (e.g. from testing, or an earlier competition) is
+/// replaced.
+pub async fn unfreeze_scoreboard(ctx: &mut Ctx, frozen_at: Option<NaiveDateTime>) -> Result<NaiveDateTime, sqlx::Error> {
+    let query = query!(
+        r#"
+            INSERT INTO competition_state (id, scoreboard_unfrozen_at)
+            VALUES (true, LOCALTIMESTAMP(0))
+            ON CONFLICT (id) DO UPDATE SET
+                scoreboard_unfrozen_at = CASE
+                    WHEN competition_state.scoreboard_unfrozen_at >= $1 THEN competition_state.scoreboard_unfrozen_at
+                    ELSE EXCLUDED.scoreboard_unfrozen_at
+                END,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING scoreboard_unfrozen_at AS "unfrozen_at!";
+        "#,
+        frozen_at,
+    );
+    Ok(query.fetch_one(ctx).await?.unfrozen_at)
+}
+
+/// Puts the scoreboard freeze back in place, if it was lifted.
+pub async fn refreeze_scoreboard(ctx: &mut Ctx) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE competition_state
+            SET
+                scoreboard_unfrozen_at = NULL,
+                updated_at = CURRENT_TIMESTAMP;
+        "#,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
@@ -0,0 +1,70 @@
+use sqlx::{ query, query_as };
+use uuid::Uuid;
+
+use super::Ctx;
+use crate::payloads::outgoing::sql::Hint;
+
+pub async fn get_hint(ctx: &mut Ctx, id: Uuid) -> Result<Option<Hint>, sqlx::Error> {
+    let query = query_as!(
+        Hint,
+        r#"
+            SELECT id, challenge_id, content, cost
+            FROM challenge_hints
+            WHERE id = $1;
+        "#,
+        id,
+    );
+    query.fetch_optional(ctx).await
+}
+
+pub async fn get_unlocked_hints(ctx: &mut Ctx, team_id: Uuid) -> Result<Vec<Hint>, sqlx::Error> {
+    let query = query_as!(
+        Hint,
+        r#"
+            SELECT hint.id, hint.challenge_id, hint.content, unlock.cost
+            FROM hint_unlocks AS unlock
+                INNER JOIN challenge_hints AS hint ON hint.id = unlock.hint_id
+            WHERE unlock.team_id = $1
+            ORDER BY hint.challenge_id, hint.position;
+        "#,
+        team_id,
+    );
+    query.fetch_all(ctx).await
+}
+
+/// Unlocks the hint for the team at its current cost, and updates the team's
+/// score. Returns whether the hint was newly unlocked.
+pub async fn unlock_hint(ctx: &mut Ctx, hint_id: Uuid, team_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let query = query!(
+        r#"
+            INSERT INTO hint_unlocks (hint_id, team_id, user_id, cost)
+            SELECT id, $2, $3, cost
+            FROM challenge_hints
+            WHERE id = $1
+            ON CONFLICT (hint_id, team_id) DO NOTHING;
+        "#,
+        hint_id,
+        team_id,
+        user_id,
+    );
+    let unlocked = query
+        .execute(&mut *ctx)
+        .await?
+        .rows_affected() == 1;
+
+    if unlocked {
+        let query = query!(
+            r#"
+                UPDATE teams
+                SET
+                    score = get_score_team(id),
+                    updated_at = DEFAULT
+                WHERE id = $1;
+            "#,
+            team_id,
+        );
+        query.execute(ctx).await?;
+    }
+
+    Ok(unlocked)
+}
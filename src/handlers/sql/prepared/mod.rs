@@ -1,4 +1,8 @@
+pub mod audit;
 pub mod challenges;
+pub mod competition;
+pub mod hints;
+pub mod outbox;
 pub mod solves;
 pub mod teams;
 pub mod users;
@@ -0,0 +1,186 @@
+use sqlx::{ query, query_as };
+use uuid::Uuid;
+
+use super::Ctx;
+use crate::payloads::incoming::sql::OutboxStatus;
+use crate::payloads::outgoing::sql::{ OutboxEntry, OutboxTarget };
+
+
+pub async fn enqueue(ctx: &mut Ctx, target: OutboxTarget, payload: serde_json::Value, delay_secs: f64) -> Result<Uuid, sqlx::Error> {
+    let query = query!(
+        r#"
+            INSERT INTO outbox (target, payload, next_attempt_at)
+            VALUES ($1, $2, CURRENT_TIMESTAMP + make_interval(secs => $3))
+            RETURNING id;
+        "#,
+        target as OutboxTarget,
+        payload,
+        delay_secs,
+    );
+    query
+        .fetch_one(ctx)
+        .await
+        .map(|row| row.id)
+}
+
+#[derive(Debug, Clone)]
+pub struct DueRow {
+    pub id: Uuid,
+    pub target: OutboxTarget,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+}
+
+/// Gets (and locks for the rest of the transaction) the pending entries that
+/// are due to be sent. Entries locked by other transactions are skipped.
+pub async fn get_due(ctx: &mut Ctx, limit: u32) -> Result<Vec<DueRow>, sqlx::Error> {
+    let query = query_as!(
+        DueRow,
+        r#"
+            SELECT id, target AS "target: _", payload, attempts
+            FROM outbox
+            WHERE status = 'pending' AND next_attempt_at <= CURRENT_TIMESTAMP
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED;
+        "#,
+        limit as i64,
+    );
+    query.fetch_all(ctx).await
+}
+
+/// Pushes back when the entries are next due, so that no other worker picks
+/// them up while they're being sent.
+pub async fn lease(ctx: &mut Ctx, ids: &[Uuid], lease_secs: f64) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE outbox
+            SET
+                next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $2),
+                updated_at = DEFAULT
+            WHERE id = ANY($1);
+        "#,
+        ids,
+        lease_secs,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+pub async fn mark_sent(ctx: &mut Ctx, id: Uuid) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE outbox
+            SET
+                status = 'sent',
+                attempts = attempts + 1,
+                sent_at = CURRENT_TIMESTAMP,
+                updated_at = DEFAULT
+            WHERE id = $1;
+        "#,
+        id,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+pub async fn schedule_retry(ctx: &mut Ctx, id: Uuid, error: &str, delay_secs: f64) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE outbox
+            SET
+                status = 'pending',
+                attempts = attempts + 1,
+                last_error = $2,
+                next_attempt_at = CURRENT_TIMESTAMP + make_interval(secs => $3),
+                updated_at = DEFAULT
+            WHERE id = $1;
+        "#,
+        id,
+        error,
+        delay_secs,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+pub async fn mark_failed(ctx: &mut Ctx, id: Uuid, error: &str) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE outbox
+            SET
+                status = 'failed',
+                attempts = attempts + 1,
+                last_error = $2,
+                updated_at = DEFAULT
+            WHERE id = $1;
+        "#,
+        id,
+        error,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+pub async fn get_entry(ctx: &mut Ctx, id: Uuid) -> Result<Option<OutboxEntry>, sqlx::Error> {
+    let query = query_as!(
+        OutboxEntry,
+        r#"
+            SELECT
+                id, target AS "target: _", payload,
+                status AS "status: _", attempts, last_error,
+                next_attempt_at, sent_at, inserted_at
+            FROM outbox
+            WHERE id = $1;
+        "#,
+        id,
+    );
+    query.fetch_optional(ctx).await
+}
+
+pub async fn get_entries(ctx: &mut Ctx, status: Option<OutboxStatus>, limit: u32) -> Result<Vec<OutboxEntry>, sqlx::Error> {
+    let query = query_as!(
+        OutboxEntry,
+        r#"
+            SELECT
+                id, target AS "target: _", payload,
+                status AS "status: _", attempts, last_error,
+                next_attempt_at, sent_at, inserted_at
+            FROM outbox
+            WHERE ($1::outbox_status IS NULL OR status = $1)
+            ORDER BY inserted_at DESC
+            LIMIT $2;
+        "#,
+        status as Option<OutboxStatus>,
+        limit as i64,
+    );
+    query.fetch_all(ctx).await
+}
+
+/// Resets an entry so that it is sent again as soon as possible, regardless of
+/// whether it was already sent or gave up.
+pub async fn replay(ctx: &mut Ctx, id: Uuid) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE outbox
+            SET
+                status = 'pending',
+                next_attempt_at = CURRENT_TIMESTAMP,
+                updated_at = DEFAULT
+            WHERE id = $1;
+        "#,
+        id,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
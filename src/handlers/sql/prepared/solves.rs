@@ -2,7 +2,8 @@ use sqlx::{ query_as, query };
 use uuid::Uuid;
 
 use super::Ctx;
-use crate::payloads::outgoing::sql::Solve;
+use crate::payloads::incoming::sql::DeletedSolveFilter;
+use crate::payloads::outgoing::sql::{ Solve, DeletedSolve };
 
 
 pub async fn get_solve(ctx: &mut Ctx, id: Uuid) -> Result<Option<Solve>, sqlx::Error> {
@@ -97,6 +98,8 @@ pub struct SolveAttemptInput {
     pub team_id: Uuid,
     pub chall_id: Uuid,
     pub flag_guess: String,
+    /// Whether the guess matched the challenge's flags. See the `flags` module.
+    pub correct: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -105,12 +108,13 @@ pub async fn attempt_solve(ctx: &mut Ctx, input: SolveAttemptInput) -> Result<So
     let query = query_as!(
         IdRow,
         r#"
-            SELECT id FROM do_solve_attempt($1, $2, $3, $4) as (id uuid, guess_correct bool, already_solved bool);
+            SELECT id FROM do_solve_attempt($1, $2, $3, $4, $5) as (id uuid, guess_correct bool, already_solved bool);
         "#,
         input.user_id,
         input.team_id,
         input.chall_id,
         input.flag_guess,
+        input.correct,
     );
 
     let attempt_id = query
@@ -127,7 +131,10 @@ pub async fn attempt_solve(ctx: &mut Ctx, input: SolveAttemptInput) -> Result<So
 use crate::sql::CiText;
 
 #[derive(Debug, Clone)]
-pub struct FirstBloodInfo { pub chall: CiText, pub user: CiText, pub team: CiText }
+pub struct FirstBloodInfo {
+    pub chall: CiText, pub user: CiText, pub team: CiText,
+    pub chall_id: Uuid, pub points: i32, pub categories: Vec<String>,
+}
 pub async fn first_blood_details(ctx: &mut Ctx, solve_id: Uuid) -> Result<Option<FirstBloodInfo>, sqlx::Error> {
     let query = query_as!(
         FirstBloodInfo,
@@ -135,7 +142,10 @@ pub async fn first_blood_details(ctx: &mut Ctx, solve_id: Uuid) -> Result<Option
             SELECT
                 chall.name AS "chall: _",
                 users.name AS "user: _",
-                team.name AS "team: _"
+                team.name AS "team: _",
+                chall.id AS "chall_id!",
+                chall.current_points AS "points!",
+                chall.categories AS "categories!"
             FROM solve_attempts AS attempt
                 LEFT JOIN challenges AS chall ON chall.id = attempt.challenge_id
                 LEFT JOIN teams AS team ON team.id = attempt.team_id
@@ -173,3 +183,274 @@ pub async fn clear_all_solves_for_challenge(ctx: &mut Ctx, chall_id: Uuid) -> Re
 
     Ok(deleted)
 }
+
+/// Serializes solve attempts from the same team for the rest of the
+/// transaction, so that concurrent attempts can't slip past the rate limits.
+pub async fn lock_team_attempts(ctx: &mut Ctx, team_id: Uuid) -> Result<(), sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT 1 AS "locked!" FROM pg_advisory_xact_lock(hashtextextended($1::uuid::text, 0));
+        "#,
+        team_id,
+    );
+    query.fetch_one(ctx).await.map(|_| ())
+}
+
+/// Finds how long until fewer than `max_attempts` attempts matching the given
+/// user, team, and challenge (where given) fall within the last `window_secs`
+/// seconds. This is `None` if there already are.
+pub async fn attempt_limit_retry_after(
+    ctx: &mut Ctx,
+    user_id: Option<Uuid>, team_id: Option<Uuid>, chall_id: Option<Uuid>,
+    max_attempts: u32, window_secs: u32,
+) -> Result<Option<f64>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT
+                CEIL(EXTRACT(EPOCH FROM (inserted_at + make_interval(secs => $4) - LOCALTIMESTAMP)))::float8 AS "retry_after!"
+            FROM solve_attempts
+            WHERE
+                ($1::uuid IS NULL OR user_id = $1) AND
+                ($2::uuid IS NULL OR team_id = $2) AND
+                ($3::uuid IS NULL OR challenge_id = $3) AND
+                inserted_at > LOCALTIMESTAMP - make_interval(secs => $4)
+            ORDER BY inserted_at DESC
+            OFFSET $5 LIMIT 1;
+        "#,
+        user_id,
+        team_id,
+        chall_id,
+        f64::from(window_secs),
+        i64::from(max_attempts) - 1,
+    );
+    query
+        .fetch_optional(ctx)
+        .await
+        .map(|row| row.map(|row| row.retry_after.max(1.0)))
+}
+
+#[derive(Debug, Clone)]
+pub struct FlagSharingInput {
+    pub chall_id: Uuid,
+    pub attempt_id: Uuid,
+    pub team_id: Uuid,
+    pub user_id: Uuid,
+    pub owner_team_id: Uuid,
+    pub flag_guess: String,
+}
+
+pub async fn record_flag_sharing(ctx: &mut Ctx, input: FlagSharingInput) -> Result<Uuid, sqlx::Error> {
+    let query = query!(
+        r#"
+            INSERT INTO flag_sharing_incidents (challenge_id, attempt_id, team_id, user_id, owner_team_id, flag_guess)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id;
+        "#,
+        input.chall_id,
+        input.attempt_id,
+        input.team_id,
+        input.user_id,
+        input.owner_team_id,
+        input.flag_guess,
+    );
+    query
+        .fetch_one(ctx)
+        .await
+        .map(|row| row.id)
+}
+
+/// Gets the team and challenge of each of the user's counted solves.
+pub async fn get_counted_solves_by_user(ctx: &mut Ctx, user_id: Uuid) -> Result<Vec<(Uuid, Uuid)>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT DISTINCT team_id, challenge_id FROM solve_successes WHERE user_id = $1;
+        "#,
+        user_id,
+    );
+    let list = query
+        .fetch_all(ctx).await?
+        .into_iter().map(|row| (row.team_id, row.challenge_id))
+        .collect();
+    Ok(list)
+}
+
+/// Gets the challenges the team has solved.
+pub async fn get_solved_chall_ids_by_team(ctx: &mut Ctx, team_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT DISTINCT challenge_id FROM solve_successes WHERE team_id = $1;
+        "#,
+        team_id,
+    );
+    let list = query
+        .fetch_all(ctx).await?
+        .into_iter().map(|row| row.challenge_id)
+        .collect();
+    Ok(list)
+}
+
+/// Moves all of the user's solve attempts to `deleted_solves`. Scores aren't
+/// updated, see [`update_all_scores`].
+pub async fn delete_solves_for_user(ctx: &mut Ctx, user_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT delete_solves_for_user($1) as "id!";
+        "#,
+        user_id,
+    );
+
+    let deleted = query
+        .fetch_all(ctx)
+        .await?
+        .into_iter()
+        .map(|record| record.id)
+        .collect();
+
+    Ok(deleted)
+}
+
+/// Moves all of the team's solve attempts to `deleted_solves`. Scores aren't
+/// updated, see [`update_all_scores`].
+pub async fn delete_solves_for_team(ctx: &mut Ctx, team_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT delete_solves_for_team($1) as "id!";
+        "#,
+        team_id,
+    );
+
+    let deleted = query
+        .fetch_all(ctx)
+        .await?
+        .into_iter()
+        .map(|record| record.id)
+        .collect();
+
+    Ok(deleted)
+}
+
+/// Recomputes every user's and team's score and last solve, and every
+/// challenge's solve count.
+pub async fn update_all_scores(ctx: &mut Ctx) -> Result<(), sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT update_db_scores_solves();
+        "#,
+    );
+    query.execute(ctx).await?;
+    Ok(())
+}
+
+/// Moves a solve attempt to `deleted_solves`. Scores aren't updated, see
+/// [`update_all_scores`].
+pub async fn delete_solve(ctx: &mut Ctx, id: Uuid) -> Result<(), sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT delete_solve($1);
+        "#,
+        id,
+    );
+    query.execute(ctx).await?;
+    Ok(())
+}
+
+/// Makes the team's earliest correct attempt at the challenge count, if none
+/// of its attempts do. Returns whether one was found.
+pub async fn count_earliest_correct_attempt(ctx: &mut Ctx, team_id: Uuid, chall_id: Uuid) -> Result<bool, sqlx::Error> {
+    let query = query!(
+        r#"
+            INSERT INTO solve_successes (attempt_id, user_id, challenge_id, team_id, solved_at)
+            SELECT id, user_id, challenge_id, team_id, inserted_at
+            FROM solve_attempts AS attempt
+            WHERE
+                attempt.team_id = $1 AND attempt.challenge_id = $2 AND attempt.correct AND
+                NOT EXISTS (
+                    SELECT 1 FROM solve_successes AS success
+                    WHERE success.team_id = $1 AND success.challenge_id = $2
+                )
+            ORDER BY attempt.inserted_at
+            LIMIT 1;
+        "#,
+        team_id,
+        chall_id,
+    );
+    let result = query.execute(ctx).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn get_deleted_solve(ctx: &mut Ctx, id: Uuid) -> Result<Option<DeletedSolve>, sqlx::Error> {
+    let query = query_as!(
+        DeletedSolve,
+        r#"
+            SELECT
+                id, user_id, team_id, challenge_id AS chall_id,
+                flag_guess, correct, inserted_at AS time, deleted_at
+            FROM deleted_solves
+            WHERE id = $1;
+        "#,
+        id,
+    );
+    query.fetch_optional(ctx).await
+}
+
+/// Gets the deleted solves matching the filter, most recently deleted first.
+pub async fn get_deleted_solves(ctx: &mut Ctx, filter: &DeletedSolveFilter) -> Result<Vec<DeletedSolve>, sqlx::Error> {
+    let query = query_as!(
+        DeletedSolve,
+        r#"
+            SELECT
+                id, user_id, team_id, challenge_id AS chall_id,
+                flag_guess, correct, inserted_at AS time, deleted_at
+            FROM deleted_solves
+            WHERE
+                ($1::uuid IS NULL OR user_id = $1) AND
+                ($2::uuid IS NULL OR team_id = $2) AND
+                ($3::uuid IS NULL OR challenge_id = $3) AND
+                ($4::bool IS NULL OR correct = $4)
+            ORDER BY deleted_at DESC, inserted_at DESC;
+        "#,
+        filter.user_id,
+        filter.team_id,
+        filter.chall_id,
+        filter.correct,
+    );
+    query.fetch_all(ctx).await
+}
+
+/// Finds the user, team, or challenge of a deleted solve that no longer
+/// exists, if there is one. The solve can't be restored without them.
+pub async fn get_deleted_solve_missing_ref(ctx: &mut Ctx, id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT
+                CASE
+                    WHEN NOT EXISTS (SELECT 1 FROM users WHERE users.id = deleted.user_id) THEN deleted.user_id
+                    WHEN NOT EXISTS (SELECT 1 FROM teams WHERE teams.id = deleted.team_id) THEN deleted.team_id
+                    WHEN NOT EXISTS (SELECT 1 FROM challenges WHERE challenges.id = deleted.challenge_id) THEN deleted.challenge_id
+                END AS missing
+            FROM deleted_solves AS deleted
+            WHERE deleted.id = $1;
+        "#,
+        id,
+    );
+    query
+        .fetch_optional(ctx)
+        .await
+        .map(|row| row.and_then(|row| row.missing))
+}
+
+/// Moves a solve attempt back out of `deleted_solves`. Scores aren't updated,
+/// see [`update_all_scores`].
+pub async fn restore_solve(ctx: &mut Ctx, id: Uuid) -> Result<Solve, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT restore_solve($1);
+        "#,
+        id,
+    );
+    query.execute(&mut *ctx).await?;
+
+    get_solve(ctx, id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)
+}
@@ -3,6 +3,8 @@ use sqlx::{ query, query_as };
 use uuid::Uuid;
 
 use super::Ctx;
+use crate::payloads::incoming::sql::ScoreboardFilter;
+use crate::sql::CiText;
 use crate::payloads::outgoing::sql::{FromSqlErr, Team, ScoreEntry};
 
 
@@ -30,7 +32,8 @@ pub async fn get_team(ctx: &mut Ctx, id: Uuid) -> Result<Option<Team>, sqlx::Err
         r#"
             SELECT
                 id, name as "name: _", score,
-                last_solve, eligible, affiliation
+                last_solve, eligible, affiliation, division,
+                banned, hidden
             FROM teams WHERE id = $1;
         "#,
         id,
@@ -45,7 +48,8 @@ pub async fn get_team_by_name(ctx: &mut Ctx, name: &str) -> Result<Option<Team>,
         r#"
             SELECT
                 id, name as "name: _", score,
-                last_solve, eligible, affiliation
+                last_solve, eligible, affiliation, division,
+                banned, hidden
             FROM teams WHERE name = $1;
         "#,
         name: String,
@@ -60,20 +64,35 @@ pub async fn get_all_teams(ctx: &mut Ctx) -> Result<Vec<Team>, sqlx::Error> {
         r#"
             SELECT
                 id, name as "name: _", score,
-                last_solve, eligible, affiliation
+                last_solve, eligible, affiliation, division,
+                banned, hidden
             FROM teams;
         "#,
     );
     query.fetch_all(ctx).await
 }
 
+pub async fn get_all_team_ids(ctx: &mut Ctx) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT id FROM teams;
+        "#,
+    );
+    let list = query
+        .fetch_all(ctx).await?
+        .into_iter().map(|row| row.id)
+        .collect();
+    Ok(list)
+}
+
 pub async fn get_team_batch(ctx: &mut Ctx, ids: &[Uuid]) -> Result<Vec<Team>, sqlx::Error> {
         let query = query_as!(
             Team,
             r#"
                 SELECT
                     id, name as "name: _", score,
-                    last_solve, eligible, affiliation
+                    last_solve, eligible, affiliation, division,
+                    banned, hidden
                 FROM teams
                 WHERE id IN (SELECT * FROM unnest($1::uuid[]));
             "#,
@@ -82,12 +101,23 @@ pub async fn get_team_batch(ctx: &mut Ctx, ids: &[Uuid]) -> Result<Vec<Team>, sq
         query.fetch_all(ctx).await
 }
 
-pub async fn get_top_teams(ctx: &mut Ctx, count: u32) -> Result<Vec<Uuid>, sqlx::Error> {
+/// Gets the top teams that match the filter.
+pub async fn get_top_teams(ctx: &mut Ctx, count: u32, filter: &ScoreboardFilter) -> Result<Vec<Uuid>, sqlx::Error> {
     let query = query!(
         r#"
-            SELECT get_top_n_teams($1) as "id!";
+            SELECT id FROM teams
+            WHERE
+                NOT hidden AND NOT banned AND
+                ($2::boolean IS NULL OR eligible = $2) AND
+                ($3::text IS NULL OR lower(affiliation) = lower($3)) AND
+                ($4::text IS NULL OR division = $4)
+            ORDER BY score DESC, last_solve ASC, inserted_at ASC
+            LIMIT $1;
         "#,
         count as i64,
+        filter.eligible,
+        filter.affiliation,
+        filter.division,
     );
     let list = query
         .fetch_all(ctx).await?
@@ -97,44 +127,131 @@ pub async fn get_top_teams(ctx: &mut Ctx, count: u32) -> Result<Vec<Uuid>, sqlx:
 }
 
 
-pub async fn get_team_score_history_batch(ctx: &mut Ctx, team_ids: &[Uuid], start_time: NaiveDateTime) -> Result<Vec<ScoreEntry>, sqlx::Error> {
-    let get_initial_scores_query = query_as!(
-        ScoreEntry,
+/// Gets the teams as they stood at `at_time`, with their score and last solve
+/// from then.
+pub async fn get_team_batch_at(ctx: &mut Ctx, ids: &[Uuid], at_time: NaiveDateTime) -> Result<Vec<Team>, sqlx::Error> {
+    let query = query_as!(
+        Team,
         r#"
             SELECT
-                team.id AS team_id,
-                get_team_score_at(team.id, $2) AS "score!",
-                $2 AS "time!"
-            FROM teams as team
-            WHERE team.id IN (SELECT * FROM unnest($1::uuid[]));
+                id, name as "name: _",
+                standing.score::integer AS "score!",
+                standing.last_solve,
+                eligible, affiliation, division,
+                banned, hidden
+            FROM teams
+                INNER JOIN get_team_standings_at($2) AS standing ON standing.team_id = teams.id
+            WHERE id IN (SELECT * FROM unnest($1::uuid[]));
         "#,
-        team_ids,
-        start_time
+        ids,
+        at_time,
     );
+    query.fetch_all(ctx).await
+}
 
-    let get_score_increments = query_as!(
-        ScoreEntry,
+/// Gets the top teams that match the filter, as the scoreboard stood at
+/// `at_time`.
+pub async fn get_top_teams_at(ctx: &mut Ctx, count: u32, filter: &ScoreboardFilter, at_time: NaiveDateTime) -> Result<Vec<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT id FROM teams
+                INNER JOIN get_team_standings_at($2) AS standing ON standing.team_id = teams.id
+            WHERE
+                NOT hidden AND NOT banned AND
+                ($3::boolean IS NULL OR eligible = $3) AND
+                ($4::text IS NULL OR lower(affiliation) = lower($4)) AND
+                ($5::text IS NULL OR division = $5)
+            ORDER BY
+                standing.score DESC,
+                standing.last_solve ASC,
+                inserted_at ASC
+            LIMIT $1;
+        "#,
+        count as i64,
+        at_time,
+        filter.eligible,
+        filter.affiliation,
+        filter.division,
+    );
+    let list = query
+        .fetch_all(ctx).await?
+        .into_iter().map(|row| row.id)
+        .collect();
+    Ok(list)
+}
+
+
+#[derive(Debug, Clone)]
+pub struct StandingRow {
+    pub team_id: Uuid,
+    pub name: CiText,
+    pub score: i64,
+    pub last_solve: Option<NaiveDateTime>,
+}
+
+/// Gets every team that matches the filter, ranked by score with ties broken
+/// the same way as `get_top_n_teams`. If `at_time` is given, the teams are
+/// ranked as they stood then.
+pub async fn get_standings(ctx: &mut Ctx, filter: &ScoreboardFilter, at_time: Option<NaiveDateTime>) -> Result<Vec<StandingRow>, sqlx::Error> {
+    let query = query_as!(
+        StandingRow,
         r#"
             SELECT
-                solve.team_id AS team_id,
-                (get_team_score_at(solve.team_id, solve.solved_at) + chall.points) AS "score!",
-                solve.solved_at AS "time!"
-            FROM solve_successes AS solve
-            JOIN challenges AS chall ON solve.challenge_id = chall.id
+                team.id AS team_id,
+                team.name AS "name: _",
+                standing.score AS "score!",
+                standing.last_solve
+            FROM teams AS team
+                LEFT JOIN get_team_standings_at($1) AS frozen ON frozen.team_id = team.id
+            CROSS JOIN LATERAL (
+                SELECT
+                    CASE WHEN $1::timestamp IS NULL THEN team.score::bigint ELSE frozen.score END AS score,
+                    CASE WHEN $1::timestamp IS NULL THEN team.last_solve ELSE frozen.last_solve END AS last_solve
+            ) AS standing
             WHERE
-                solve.team_id IN (SELECT * FROM unnest($1::uuid[])) AND
-                solve.solved_at >= $2;
+                NOT team.hidden AND NOT team.banned AND
+                ($2::boolean IS NULL OR team.eligible = $2) AND
+                ($3::text IS NULL OR lower(team.affiliation) = lower($3)) AND
+                ($4::text IS NULL OR team.division = $4)
+            ORDER BY standing.score DESC, standing.last_solve ASC, team.inserted_at ASC;
         "#,
-        team_ids,
-        start_time
+        at_time,
+        filter.eligible,
+        filter.affiliation,
+        filter.division,
     );
+    query.fetch_all(ctx).await
+}
 
-    let initial_scores = get_initial_scores_query.fetch_all(&mut *ctx).await?;
-    let score_increments = get_score_increments.fetch_all(&mut *ctx).await?;
 
-    let scores = initial_scores.into_iter().chain(score_increments.into_iter()).collect::<Vec<_>>();
-    
-    Ok(scores)
+/// Gets the score history of the teams from `start_time`. Each entry is the
+/// team's score once everything in that second has happened, so events in the
+/// same second are never split up or misordered.
+///
+/// If `frozen_at` is given, anything after it is left out and solves are priced
+/// as of then, the same as the frozen scoreboard. Otherwise solves are priced
+/// at the challenges' current value, like `teams.score`. Either way, the last
+/// entry is the team's score on the matching scoreboard.
+pub async fn get_team_score_history_batch(
+    ctx: &mut Ctx,
+    team_ids: &[Uuid],
+    start_time: NaiveDateTime,
+    frozen_at: Option<NaiveDateTime>,
+) -> Result<Vec<ScoreEntry>, sqlx::Error> {
+    let query = query_as!(
+        ScoreEntry,
+        r#"
+            SELECT
+                history.team_id AS "team_id!",
+                history.score AS "score!",
+                history.event_time AS "time!"
+            FROM get_team_score_history($1, $2, $3, $3) AS history;
+        "#,
+        team_ids,
+        start_time,
+        frozen_at,
+    );
+    query.fetch_all(ctx).await
 }
 
 
@@ -144,6 +261,7 @@ pub struct NewTeamInput {
     pub description: String,
     pub eligible: bool,
     pub affiliation: Option<String>,
+    pub division: Option<String>,
     pub hashed_password: String,
 }
 
@@ -151,13 +269,14 @@ pub struct NewTeamInput {
 pub async fn create_team(ctx: &mut Ctx, input: NewTeamInput) -> Result<Team, sqlx::Error> {
     let query = query!(
         r#"
-            INSERT INTO teams (name, description, eligible, affiliation, hashed_password)
-            VALUES ($1, $2, $3, $4, $5);
+            INSERT INTO teams (name, description, eligible, affiliation, division, hashed_password)
+            VALUES ($1, $2, $3, $4, $5, $6);
         "#,
         input.name: String,
         input.description,
         input.eligible,
         input.affiliation,
+        input.division,
         input.hashed_password,
     );
     query
@@ -179,6 +298,7 @@ pub struct TeamInput {
     pub description: Option<String>,
     pub eligible: Option<bool>,
     pub affiliation: Option<Option<String>>,
+    pub division: Option<Option<String>>,
 }
 
 
@@ -235,6 +355,20 @@ pub async fn update_team(ctx: &mut Ctx, input: TeamInput) -> Result<Team, sqlx::
         return Err(sqlx::Error::RowNotFound)
     }
 
+    if let Some(division) = input.division {
+        query!(
+            r#"
+                UPDATE teams
+                SET division = $2
+                WHERE id = $1;
+            "#,
+            input.id,
+            division
+        )
+            .execute(&mut *ctx)
+            .await?;
+    }
+
 
     if let Some(updated_team) = get_team(ctx, input.id).await? {
         Ok(updated_team)
@@ -244,6 +378,73 @@ pub async fn update_team(ctx: &mut Ctx, input: TeamInput) -> Result<Team, sqlx::
 }
 
 
+pub async fn set_team_banned(ctx: &mut Ctx, id: Uuid, banned: bool) -> Result<Option<Team>, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE teams
+            SET banned = $2, updated_at = DEFAULT
+            WHERE id = $1;
+        "#,
+        id,
+        banned,
+    );
+    let affected = query
+        .execute(&mut *ctx)
+        .await?
+        .rows_affected();
+
+    if affected != 1 { return Ok(None) }
+
+    get_team(ctx, id).await
+}
+
+pub async fn set_team_hidden(ctx: &mut Ctx, id: Uuid, hidden: bool) -> Result<Option<Team>, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE teams
+            SET hidden = $2, updated_at = DEFAULT
+            WHERE id = $1;
+        "#,
+        id,
+        hidden,
+    );
+    let affected = query
+        .execute(&mut *ctx)
+        .await?
+        .rows_affected();
+
+    if affected != 1 { return Ok(None) }
+
+    get_team(ctx, id).await
+}
+
+/// Deletes the team, along with its hint unlocks. Its members are left without
+/// a team rather than being deleted too, and its solves should be archived
+/// first.
+pub async fn delete_team(ctx: &mut Ctx, id: Uuid) -> Result<u64, sqlx::Error> {
+    let detach_query = query!(
+        r#"
+            UPDATE users
+            SET team_id = NULL, updated_at = DEFAULT
+            WHERE team_id = $1;
+        "#,
+        id,
+    );
+    detach_query.execute(&mut *ctx).await?;
+
+    let query = query!(
+        r#"
+            DELETE FROM teams WHERE id = $1;
+        "#,
+        id,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+
 pub enum CheckTeamAuthError {
     Sql(sqlx::Error),
     Hashing,
@@ -29,7 +29,7 @@ pub async fn get_user(ctx: &mut Ctx, id: Uuid) -> Result<Option<User>, sqlx::Err
             SELECT
                 id, name as "name: _", email as "email: _",
                 team_id, score, last_solve,
-                admin, eligible
+                admin, eligible, banned
             FROM users WHERE id = $1;
         "#,
         id,
@@ -44,7 +44,7 @@ pub async fn get_user_by_name(ctx: &mut Ctx, name: &str) -> Result<Option<User>,
             SELECT
                 id, name as "name: _", email as "email: _",
                 team_id, score, last_solve,
-                admin, eligible
+                admin, eligible, banned
             FROM users WHERE name = $1;
         "#,
         name: String,
@@ -59,7 +59,7 @@ pub async fn get_all_users(ctx: &mut Ctx) -> Result<Vec<User>, sqlx::Error> {
             SELECT
                 id, name as "name: _", email as "email: _",
                 team_id, score, last_solve,
-                admin, eligible
+                admin, eligible, banned
             FROM users;
         "#,
     );
@@ -331,3 +331,53 @@ pub async fn user_is_on_team(ctx: &mut Ctx, id: Uuid, team_id: Uuid) -> Result<U
     }
 }
 
+
+pub async fn set_user_banned(ctx: &mut Ctx, id: Uuid, banned: bool) -> Result<Option<User>, sqlx::Error> {
+    let query = query!(
+        r#"
+            UPDATE users
+            SET banned = $2, updated_at = DEFAULT
+            WHERE id = $1;
+        "#,
+        id,
+        banned,
+    );
+    let affected = query
+        .execute(&mut *ctx)
+        .await?
+        .rows_affected();
+
+    if affected != 1 { return Ok(None) }
+
+    get_user(ctx, id).await
+}
+
+/// Deletes the user, along with their sign in methods and hint unlocks. Their
+/// solves should be archived first.
+pub async fn delete_user(ctx: &mut Ctx, id: Uuid) -> Result<u64, sqlx::Error> {
+    let query = query!(
+        r#"
+            DELETE FROM users WHERE id = $1;
+        "#,
+        id,
+    );
+    query
+        .execute(ctx)
+        .await
+        .map(|res| res.rows_affected())
+}
+
+/// Gets whichever of the user and the team is banned, if either is.
+pub async fn get_banned(ctx: &mut Ctx, user_id: Uuid, team_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    let query = query!(
+        r#"
+            SELECT id AS "id!" FROM users WHERE id = $1 AND banned
+            UNION ALL
+            SELECT id AS "id!" FROM teams WHERE id = $2 AND banned
+            LIMIT 1;
+        "#,
+        user_id,
+        team_id,
+    );
+    Ok(query.fetch_optional(ctx).await?.map(|row| row.id))
+}
@@ -0,0 +1,158 @@
+//! Dynamic (decaying) challenge scoring.
+//!
+//! A challenge's `points` column is its base value. Its `current_points` column
+//! is what solvers are actually awarded, and is recomputed from the solve count
+//! whenever the solve count or scoring parameters change. Team and user scores
+//! are summed from `current_points` by the database, so whenever a challenge's
+//! value changes, every team and user that solved it gets recomputed too.
+
+use uuid::Uuid;
+
+use crate::logging::*;
+use crate::payloads::incoming::sql::ScoringType;
+
+use super::prepared::challenges::{
+    get_chall_scoring, set_chall_current_points, update_scores_for_chall,
+    ChallScoringRow,
+};
+use super::Ctx;
+
+/// The parameters that determine a challenge's value at a given solve count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoringParams {
+    pub scoring_type: ScoringType,
+    pub points: i32,
+    pub min_points: i32,
+    pub decay: i32,
+}
+
+impl ScoringParams {
+    /// Gets the value of the challenge once `solve_count` teams have solved it.
+    ///
+    /// The first solver always gets the full value, and the value bottoms out
+    /// at `min_points` once `decay` more teams have solved it (so at
+    /// `decay + 1` solves). A `decay` of 0 or less disables decaying entirely.
+    ///
+    /// `get_challenge_points_at` in `schema/functions.sql` does the same for the
+    /// frozen scoreboard, and has to be kept in sync with this.
+    pub fn value(&self, solve_count: i32) -> i32 {
+        let Self { scoring_type, points, min_points, decay } = *self;
+
+        if decay <= 0 || scoring_type == ScoringType::Static {
+            return points;
+        }
+
+        let min_points = min_points.min(points);
+
+        let initial = f64::from(points);
+        // In f64, since the difference can overflow an i32
+        let range = initial - f64::from(min_points);
+        let decay = f64::from(decay);
+        let solves = f64::from(solve_count.saturating_sub(1).max(0));
+
+        let value = match scoring_type {
+            ScoringType::Static => initial,
+            ScoringType::Linear => initial - range * solves / decay,
+            ScoringType::Parabolic => initial - range * (solves * solves) / (decay * decay),
+        };
+
+        (value.ceil() as i32).clamp(min_points, points)
+    }
+}
+
+impl From<ChallScoringRow> for ScoringParams {
+    fn from(row: ChallScoringRow) -> Self {
+        Self {
+            scoring_type: row.scoring_type,
+            points: row.points,
+            min_points: row.min_points,
+            decay: row.decay,
+        }
+    }
+}
+
+/// Recomputes the current value of a challenge from its solve count. If the
+/// value changed, the scores of every team and user that solved it are
+/// recomputed as well.
+///
+/// Returns the new value of the challenge, or `None` if it doesn't exist.
+pub async fn update_chall_value(ctx: &mut Ctx, chall_id: Uuid) -> Result<Option<i32>, sqlx::Error> {
+    let Some(row) = get_chall_scoring(&mut *ctx, chall_id).await? else {
+        return Ok(None);
+    };
+
+    let old_value = row.current_points;
+    let new_value = ScoringParams::from(row).value(row.solve_count);
+
+    if new_value != old_value {
+        debug!("Challenge {chall_id} value changed from {old_value} to {new_value}, recomputing scores");
+
+        set_chall_current_points(&mut *ctx, chall_id, new_value).await?;
+        update_scores_for_chall(ctx, chall_id).await?;
+    }
+
+    Ok(Some(new_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(scoring_type: ScoringType, points: i32, min_points: i32, decay: i32) -> ScoringParams {
+        ScoringParams { scoring_type, points, min_points, decay }
+    }
+
+    #[test]
+    fn static_never_decays() {
+        let params = params(ScoringType::Static, 500, 100, 10);
+
+        assert_eq!(params.value(0), 500);
+        assert_eq!(params.value(1), 500);
+        assert_eq!(params.value(1000), 500);
+    }
+
+    #[test]
+    fn non_positive_decay_disables_decaying() {
+        assert_eq!(params(ScoringType::Linear, 500, 100, 0).value(50), 500);
+        assert_eq!(params(ScoringType::Parabolic, 500, 100, -3).value(50), 500);
+    }
+
+    #[test]
+    fn linear_hits_minimum_after_decay_more_solves() {
+        let params = params(ScoringType::Linear, 500, 100, 10);
+
+        assert_eq!(params.value(0), 500);
+        assert_eq!(params.value(1), 500);
+        assert_eq!(params.value(2), 460);
+        assert_eq!(params.value(10), 140);
+        assert_eq!(params.value(11), 100);
+        assert_eq!(params.value(1000), 100);
+    }
+
+    #[test]
+    fn parabolic_hits_minimum_after_decay_more_solves() {
+        let params = params(ScoringType::Parabolic, 500, 100, 10);
+
+        assert_eq!(params.value(0), 500);
+        assert_eq!(params.value(1), 500);
+        assert_eq!(params.value(2), 496);
+        assert_eq!(params.value(10), 176);
+        assert_eq!(params.value(11), 100);
+        assert_eq!(params.value(1000), 100);
+    }
+
+    #[test]
+    fn minimum_above_points_is_capped_at_points() {
+        assert_eq!(params(ScoringType::Linear, 100, 500, 10).value(0), 100);
+        assert_eq!(params(ScoringType::Linear, 100, 500, 10).value(11), 100);
+        assert_eq!(params(ScoringType::Parabolic, 100, 500, 10).value(5), 100);
+    }
+
+    #[test]
+    fn extreme_minimum_does_not_overflow() {
+        let params = params(ScoringType::Linear, i32::MAX, i32::MIN, 2);
+
+        assert_eq!(params.value(1), i32::MAX);
+        assert_eq!(params.value(3), i32::MIN);
+    }
+}
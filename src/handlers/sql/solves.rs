@@ -3,8 +3,9 @@ use crate::handlers::sql::prepared::users::check_user_auth;
 use crate::logging::*;
 use crate::payloads::*;
 
-use incoming::sql::SolveQuery;
+use incoming::sql::{ FlagType, SolveQuery };
 use outgoing::sql::{FromSql, FromSqlErr};
+use uuid::Uuid;
 
 use super::prepared::solves as queries;
 use queries::{
@@ -13,32 +14,113 @@ use queries::{
     get_all_solves,
     attempt_solve,
 };
-use queries::SolveAttemptInput;
+use queries::{ SolveAttemptInput, FlagSharingInput };
 
-pub async fn handle(mut ctx: super::Ctx, query: SolveQuery) -> Result<FromSql, FromSqlErr> {
+use super::flags;
+use super::prepared::challenges::{ get_chall_flags, chall_unlocked_for_team };
+use super::prepared::teams::get_all_team_ids;
+use super::scoring::update_chall_value;
+use super::check_admin;
+
+/// Rejects the attempt if the user, the team, or the team on this challenge
+/// have made too many recent attempts. See [`crate::env::solve_limits`].
+async fn check_attempt_limits(ctx: &mut super::Ctx, user_id: Uuid, team_id: Uuid, chall_id: Uuid) -> Result<(), FromSqlErr> {
+    use crate::env::solve_limits;
+
+    queries::lock_team_attempts(ctx, team_id).await?;
+
+    let limits = [
+        ("user", solve_limits::user(), Some(user_id), None, None),
+        ("team", solve_limits::team(), None, Some(team_id), None),
+        ("team_challenge", solve_limits::team_chall(), None, Some(team_id), Some(chall_id)),
+    ];
+
+    for (scope, limit, user_id, team_id, chall_id) in limits {
+        let Some(limit) = limit else { continue };
+
+        let retry_after = queries::attempt_limit_retry_after(
+            ctx,
+            user_id, team_id, chall_id,
+            limit.attempts, limit.window_secs,
+        ).await?;
+
+        if let Some(retry_after) = retry_after {
+            info!("Solve attempt rate limited ({scope}), retry after {retry_after}s");
+            return Err(FromSqlErr::RateLimited { scope, retry_after });
+        }
+    }
+    Ok(())
+}
+
+/// Rejects the attempt if it's made outside of the competition. See
+/// [`crate::env::competition`].
+fn check_competition_running() -> Result<(), FromSqlErr> {
+    use crate::env::competition;
+
+    if competition::is_running(competition::now()) {
+        return Ok(());
+    }
+
+    info!("Solve attempt made outside of the competition");
+    Err(FromSqlErr::CompetitionClosed {
+        starts_at: competition::start(),
+        ends_at: competition::end(),
+    })
+}
+
+/// Records that a team submitted a flag generated for another team, and alerts
+/// the admins.
+async fn report_flag_sharing(ctx: &mut super::Ctx, input: FlagSharingInput) -> Result<(), FromSqlErr> {
+    use crate::payloads::incoming::discord::*;
+
+    warn!(
+        "Team {} submitted team {}'s flag for challenge {}",
+        input.team_id, input.owner_team_id, input.chall_id,
+    );
+
+    let message = ToDiscord::Developer(DeveloperDiscordMessage {
+        level: AlertLevel::Warn,
+        message: "Possible flag sharing: a team submitted a flag generated for another team.".into(),
+        data: serde_json::json!({
+            "challenge_id": input.chall_id,
+            "attempt_id": input.attempt_id,
+            "team_id": input.team_id,
+            "user_id": input.user_id,
+            "owner_team_id": input.owner_team_id,
+            "flag_guess": input.flag_guess,
+        }),
+        include_chall_writers: false,
+    });
+
+    queries::record_flag_sharing(ctx, input).await?;
+    crate::handlers::outbox::enqueue(ctx, &message).await?;
+    Ok(())
+}
+
+pub async fn handle(ctx: &mut super::Ctx, query: SolveQuery) -> Result<FromSql, FromSqlErr> {
     trace!("Handling SQL solve req");
 
     let success_res = match query {
         SolveQuery::GetAllSolves => {
             debug!("SQL solve req classified as 'GetAllSolves' req");
-            FromSql::SolveArr(get_all_solves(&mut ctx).await?)
+            FromSql::SolveArr(get_all_solves(ctx).await?)
         },
         SolveQuery::GetAllSolvesByChall { chall_id } => {
             debug!("SQL solve req classified as 'GetAllSolvesByChall<{chall_id}>' req");
-            FromSql::SolveArr(get_solves_by_chall(&mut ctx, chall_id).await?)
+            FromSql::SolveArr(get_solves_by_chall(ctx, chall_id).await?)
         },
         SolveQuery::GetAllSolvesByTeam { team_id } => {
             debug!("SQL solve req classified as 'GetAllSolvesByTeam<{team_id}>' req");
-            FromSql::SolveArr(get_solves_by_team(&mut ctx, team_id).await?)
+            FromSql::SolveArr(get_solves_by_team(ctx, team_id).await?)
         },
         SolveQuery::GetAllSolvesByUser { user_id } => {
             debug!("SQL solve req classified as 'GetAllSolvesByUser<{user_id}>' req");
-            FromSql::SolveArr(get_solves_by_user(&mut ctx, user_id).await?)
+            FromSql::SolveArr(get_solves_by_user(ctx, user_id).await?)
         },
         SolveQuery::GetSolve { id } => {
             debug!("SQL solve req classified as 'GetSolve<{id}>' req");
             
-            if let Some(solve) = get_solve(&mut ctx, id).await? {
+            if let Some(solve) = get_solve(ctx, id).await? {
                 FromSql::Solve(solve)
             } else {
                 return Err(FromSqlErr::DoesNotExist(id))
@@ -53,38 +135,70 @@ pub async fn handle(mut ctx: super::Ctx, query: SolveQuery) -> Result<FromSql, F
 
             use super::prepared::users::{ user_is_on_team, UserIsOnTeamOutcome::* };
 
-            match user_is_on_team(&mut ctx, user_id, team_id).await? {
+            match user_is_on_team(ctx, user_id, team_id).await? {
                 DoesNotExist => return Err(FromSqlErr::DoesNotExist(user_id)),
                 NotOnTeam => return Err(FromSqlErr::Auth),
                 IsOnTeam => (),
             }
 
-            if !check_user_auth(&mut ctx, user_id, user_auth).await? {
+            if !check_user_auth(ctx, user_id, user_auth).await? {
                 return Err(FromSqlErr::Auth)
             }
 
+            super::check_not_banned(ctx, user_id, team_id).await?;
+            check_competition_running()?;
+
+            check_attempt_limits(ctx, user_id, team_id, chall_id).await?;
+
+            let Some(chall_flags) = get_chall_flags(ctx, chall_id).await? else {
+                return Err(FromSqlErr::DoesNotExist(chall_id));
+            };
+            if !chall_unlocked_for_team(ctx, chall_id, team_id).await? {
+                return Err(FromSqlErr::ChallengeLocked(chall_id));
+            }
+            let correct = flags::check_for_team(&chall_flags, team_id, &flag_guess)?;
+
             let solve = attempt_solve(
-                &mut ctx,
-                SolveAttemptInput { user_id, team_id, chall_id, flag_guess },
+                ctx,
+                SolveAttemptInput { user_id, team_id, chall_id, flag_guess: flag_guess.clone(), correct },
             ).await?;
+            crate::metrics::count_solve_attempt(correct);
+
+            if !correct && chall_flags.flag_type == FlagType::PerTeam {
+                let other_teams = get_all_team_ids(ctx).await?
+                    .into_iter()
+                    .filter(|id| *id != team_id);
+
+                if let Some(owner_team_id) = flags::find_owner(&chall_flags, other_teams, &flag_guess)? {
+                    report_flag_sharing(ctx, FlagSharingInput {
+                        chall_id, attempt_id: solve.id,
+                        team_id, user_id, owner_team_id,
+                        flag_guess,
+                    }).await?;
+                }
+            }
+
+            if solve.counted {
+                update_chall_value(ctx, chall_id).await?;
+            }
 
             if solve.correct {
-                if let Some(blood_details) = first_blood_details(&mut ctx, solve.id).await? {
+                if let Some(blood_details) = first_blood_details(ctx, solve.id).await? {
                     use crate::payloads::incoming::discord::*;
-                    use crate::handlers::Handle;
 
-                    info!("First blood on {}! Sending discord message...", blood_details.chall.str());
+                    info!("First blood on {}! Queueing discord message...", blood_details.chall.str());
+                    crate::metrics::count_first_blood();
 
                     let message = ToDiscord::Participant(ParticipantMessage::FirstBlood {
                         chall_name: blood_details.chall.string(),
                         team: blood_details.team.string(),
                         user: blood_details.user.string(),
+                        chall_id: Some(blood_details.chall_id),
+                        points: Some(blood_details.points),
+                        categories: blood_details.categories,
                     });
 
-                    if let Err(e) = message.handle().await {
-                        error!("Sending the discord first blood message failed!");
-                        debug!("Discord err: {e:?}");
-                    }
+                    crate::handlers::outbox::enqueue(ctx, &message).await?;
                 }
             }
 
@@ -94,9 +208,59 @@ pub async fn handle(mut ctx: super::Ctx, query: SolveQuery) -> Result<FromSql, F
             id
         } => {
             debug!("SQL solve req classified as 'ClearAllSolvesForChallenge' req");
-            queries::clear_all_solves_for_challenge(&mut ctx, id).await?;
+            queries::clear_all_solves_for_challenge(ctx, id).await?;
+            update_chall_value(ctx, id).await?;
             FromSql::SolveArr(vec![])
         },
+        SolveQuery::DeleteSolve { admin_id, admin_auth, id } => {
+            debug!("SQL solve req classified as 'DeleteSolve<{id}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            let Some(solve) = get_solve(ctx, id).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+
+            queries::delete_solve(ctx, id).await?;
+            if solve.counted {
+                queries::count_earliest_correct_attempt(ctx, solve.team_id, solve.chall_id).await?;
+            }
+
+            queries::update_all_scores(ctx).await?;
+            update_chall_value(ctx, solve.chall_id).await?;
+            warn!("Solve {id} deleted by {admin_id}");
+
+            let deleted = queries::get_deleted_solve(ctx, id)
+                .await?
+                .ok_or(sqlx::Error::RowNotFound)?;
+            FromSql::DeletedSolve(deleted)
+        },
+        SolveQuery::RestoreSolve { admin_id, admin_auth, id } => {
+            debug!("SQL solve req classified as 'RestoreSolve<{id}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            let Some(deleted) = queries::get_deleted_solve(ctx, id).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+            if let Some(missing_id) = queries::get_deleted_solve_missing_ref(ctx, id).await? {
+                return Err(FromSqlErr::DoesNotExist(missing_id));
+            }
+
+            let solve = queries::restore_solve(ctx, id).await?;
+
+            queries::update_all_scores(ctx).await?;
+            update_chall_value(ctx, deleted.chall_id).await?;
+            warn!("Solve {id} restored by {admin_id}");
+
+            FromSql::Solve(solve)
+        },
+        SolveQuery::GetDeletedSolves { admin_id, admin_auth, filter } => {
+            debug!("SQL solve req classified as 'GetDeletedSolves' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+            FromSql::DeletedSolveArr(queries::get_deleted_solves(ctx, &filter).await?)
+        },
     };
     Ok(success_res)
 }
\ No newline at end of file
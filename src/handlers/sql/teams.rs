@@ -1,7 +1,7 @@
 use crate::payloads::*;
 use crate::logging::*;
 
-use incoming::sql::TeamQuery;
+use incoming::sql::{ TeamQuery, ExportFormat, ScoreboardFilter };
 use outgoing::sql::{FromSql, FromSqlErr};
 
 use super::prepared::teams as queries;
@@ -12,49 +12,214 @@ use queries::{
 };
 use queries::{ TeamInput, NewTeamInput };
 
-pub async fn handle(mut ctx: super::Ctx, query: TeamQuery) -> Result<FromSql, FromSqlErr> {
+use chrono::NaiveDateTime;
+
+use super::check_admin;
+
+/// The most teams a scoreboard query can ask for.
+const MAX_TOP_TEAMS: u32 = 100;
+
+/// The time the public scoreboard shows, if it's frozen. The freeze is set by
+/// [`crate::env::competition`], and lasts until an admin lifts it. Lifts from
+/// before the freeze started (e.g. left over from testing or an earlier
+/// competition) are ignored.
+async fn scoreboard_frozen_at(ctx: &mut super::Ctx) -> Result<Option<NaiveDateTime>, FromSqlErr> {
+    use crate::env::competition;
+
+    let Some(frozen_at) = competition::frozen_at(competition::now()) else {
+        return Ok(None);
+    };
+
+    match super::prepared::competition::get_scoreboard_unfrozen_at(ctx).await? {
+        Some(unfrozen_at) if unfrozen_at >= frozen_at => {
+            trace!("Scoreboard freeze was lifted at {unfrozen_at}");
+            Ok(None)
+        },
+        _ => Ok(Some(frozen_at)),
+    }
+}
+
+/// Gets the top teams matching the filter, as they stood at `frozen_at` if
+/// it's given.
+async fn top_teams(
+    ctx: &mut super::Ctx,
+    limit: u32,
+    filter: &ScoreboardFilter,
+    frozen_at: Option<NaiveDateTime>,
+) -> Result<FromSql, FromSqlErr> {
+    // Cap here to prevent server from being overloaded by a
+    // badly-written client
+    if limit > MAX_TOP_TEAMS {
+        return Err(FromSqlErr::RequestTooBig(limit as u64, MAX_TOP_TEAMS as u64))
+    }
+
+    let teams = if let Some(frozen_at) = frozen_at {
+        debug!("Scoreboard is frozen at {frozen_at}");
+
+        let top_team_ids = queries::get_top_teams_at(ctx, limit, filter, frozen_at).await?;
+        queries::get_team_batch_at(ctx, &top_team_ids, frozen_at).await?
+    } else {
+        let top_team_ids = queries::get_top_teams(ctx, limit, filter).await?;
+        queries::get_team_batch(ctx, &top_team_ids).await?
+    };
+
+    Ok(FromSql::TeamArr(teams))
+}
+
+/// Gets the score history of the top teams matching the filter, leaving out
+/// anything after `frozen_at` if it's given.
+async fn top_teams_history(
+    ctx: &mut super::Ctx,
+    limit: u32,
+    start_time: NaiveDateTime,
+    filter: &ScoreboardFilter,
+    frozen_at: Option<NaiveDateTime>,
+) -> Result<FromSql, FromSqlErr> {
+    // Cap here to prevent server from being overloaded by a
+    // badly-written client
+    if limit > MAX_TOP_TEAMS {
+        return Err(FromSqlErr::RequestTooBig(limit as u64, MAX_TOP_TEAMS as u64))
+    }
+
+    let (top_team_ids, start_time) = if let Some(frozen_at) = frozen_at {
+        debug!("Scoreboard is frozen at {frozen_at}");
+        (queries::get_top_teams_at(ctx, limit, filter, frozen_at).await?, start_time.min(frozen_at))
+    } else {
+        (queries::get_top_teams(ctx, limit, filter).await?, start_time)
+    };
+
+    let team_score_history = queries::get_team_score_history_batch(ctx, &top_team_ids, start_time, frozen_at).await?;
+    Ok(FromSql::TeamScoreHistoryArray(team_score_history))
+}
+
+pub async fn handle(ctx: &mut super::Ctx, query: TeamQuery) -> Result<FromSql, FromSqlErr> {
     trace!("Handling SQL team req");
         
     
     let success_res = match query {
         TeamQuery::GetAllTeams => {
             debug!("SQL team req classified as 'GetAllTeams' req");
-            FromSql::TeamArr(get_all_teams(&mut ctx).await?)
+            FromSql::TeamArr(get_all_teams(ctx).await?)
         },
         TeamQuery::GetTeam { id } => {
             debug!("SQL team req classified as 'GetTeam<{id}>' req");
-            if let Some(team) = get_team(&mut ctx, id).await? {
+            if let Some(team) = get_team(ctx, id).await? {
                 FromSql::Team(team)
             } else {
                 return Err(FromSqlErr::DoesNotExist(id))
             }
         },
-        TeamQuery::GetTopTeams { limit } => {
-            debug!("SQL team req classified as 'GetTopTeams<{limit}>' req");
+        TeamQuery::GetTopTeams { limit, filter } => {
+            debug!("SQL team req classified as 'GetTopTeams<{limit}, {filter:?}>' req");
 
-            // Cap here to prevent server from being overloaded by a
-            // badly-written client
-            if limit > 100 {
-                return Err(FromSqlErr::RequestTooBig(limit as u64, 100))
+            let frozen_at = scoreboard_frozen_at(ctx).await?;
+            top_teams(ctx, limit, &filter, frozen_at).await?
+        },
+        TeamQuery::GetTopTeamsScoreHistory { limit, start_time, filter } => {
+            debug!("SQL team req classified as 'GetTopTeamsScoreHistory<{limit}, {filter:?}>' req");
+
+            let frozen_at = scoreboard_frozen_at(ctx).await?;
+            top_teams_history(ctx, limit, start_time, &filter, frozen_at).await?
+        },
+        TeamQuery::GetLiveTopTeams { admin_id, admin_auth, limit, filter } => {
+            debug!("SQL team req classified as 'GetLiveTopTeams<{limit}, {filter:?}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+            top_teams(ctx, limit, &filter, None).await?
+        },
+        TeamQuery::GetLiveTopTeamsScoreHistory { admin_id, admin_auth, limit, start_time, filter } => {
+            debug!("SQL team req classified as 'GetLiveTopTeamsScoreHistory<{limit}, {filter:?}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+            top_teams_history(ctx, limit, start_time, &filter, None).await?
+        },
+        TeamQuery::ExportScoreboard { admin_id, admin_auth, format, filter } => {
+            debug!("SQL team req classified as 'ExportScoreboard<{format:?}, {filter:?}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            let frozen_at = scoreboard_frozen_at(ctx).await?;
+            let standings = queries::get_standings(ctx, &filter, frozen_at).await?;
+
+            match format {
+                ExportFormat::Ctftime => FromSql::CtftimeScoreboard(super::export::ctftime(standings)),
+                ExportFormat::Csv => FromSql::Csv(super::export::csv(standings)),
             }
+        },
+        TeamQuery::SetBanned { admin_id, admin_auth, id, banned } => {
+            debug!("SQL team req classified as 'SetBanned<{id}, {banned}>' req");
 
-            let top_team_ids = queries::get_top_teams(&mut ctx, limit).await?;
+            check_admin(ctx, admin_id, admin_auth).await?;
 
-            FromSql::TeamArr(queries::get_team_batch(&mut ctx, &top_team_ids).await?)
+            let Some(team) = queries::set_team_banned(ctx, id, banned).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+            info!("Team {id} {} by {admin_id}", if banned { "banned" } else { "unbanned" });
+
+            FromSql::Team(team)
         },
-        TeamQuery::GetTopTeamsScoreHistory { limit, start_time } => {
-            debug!("SQL team req classified as 'GetTopTeamsScoreHistory<{limit}>' req");
+        TeamQuery::SetHidden { admin_id, admin_auth, id, hidden } => {
+            debug!("SQL team req classified as 'SetHidden<{id}, {hidden}>' req");
 
-            // Cap here to prevent server from being overloaded by a
-            // badly-written client
-            if limit > 100 {
-                return Err(FromSqlErr::RequestTooBig(limit as u64, 100))
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            let Some(team) = queries::set_team_hidden(ctx, id, hidden).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+            info!("Team {id} {} by {admin_id}", if hidden { "hidden" } else { "unhidden" });
+
+            FromSql::Team(team)
+        },
+        TeamQuery::Delete { admin_id, admin_auth, id } => {
+            debug!("SQL team req classified as 'Delete<{id}>' req");
+
+            use super::prepared::solves::{ get_solved_chall_ids_by_team, delete_solves_for_team, update_all_scores };
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            let Some(team) = get_team(ctx, id).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+
+            let solved_challs = get_solved_chall_ids_by_team(ctx, id).await?;
+            delete_solves_for_team(ctx, id).await?;
+            queries::delete_team(ctx, id).await?;
+
+            update_all_scores(ctx).await?;
+            for chall_id in solved_challs {
+                super::scoring::update_chall_value(ctx, chall_id).await?;
             }
-            
-            let top_team_ids = queries::get_top_teams(&mut ctx, limit).await?;
+            warn!("Team {id} ({:?}) deleted by {admin_id}", team.name.str());
+
+            FromSql::Team(team)
+        },
+        TeamQuery::UnfreezeScoreboard { admin_id, admin_auth, limit } => {
+            debug!("SQL team req classified as 'UnfreezeScoreboard' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
 
-            let team_score_history = queries::get_team_score_history_batch(&mut ctx, &top_team_ids, start_time).await?;
-            FromSql::TeamScoreHistoryArray(team_score_history)
+            let freeze = crate::env::competition::freeze();
+            let unfrozen_at = super::prepared::competition::unfreeze_scoreboard(ctx, freeze).await?;
+            info!("Scoreboard freeze lifted by {admin_id} (at {unfrozen_at})");
+
+            use crate::payloads::incoming::{ ToFrontend, frontend::SyncType };
+            crate::handlers::outbox::enqueue(ctx, &ToFrontend::Sync(SyncType::All)).await?;
+
+            top_teams(ctx, limit, &ScoreboardFilter::default(), None).await?
+        },
+        TeamQuery::RefreezeScoreboard { admin_id, admin_auth, limit } => {
+            debug!("SQL team req classified as 'RefreezeScoreboard' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            super::prepared::competition::refreeze_scoreboard(ctx).await?;
+            info!("Scoreboard freeze put back by {admin_id}");
+
+            use crate::payloads::incoming::{ ToFrontend, frontend::SyncType };
+            crate::handlers::outbox::enqueue(ctx, &ToFrontend::Sync(SyncType::All)).await?;
+
+            let frozen_at = scoreboard_frozen_at(ctx).await?;
+            top_teams(ctx, limit, &ScoreboardFilter::default(), frozen_at).await?
         },
 
 
@@ -62,11 +227,11 @@ pub async fn handle(mut ctx: super::Ctx, query: TeamQuery) -> Result<FromSql, Fr
             let display_name = shortened(&name, 13);
             debug!("SQL team req classified as 'CheckTeamnameAvailability<`{display_name}`>' req");
 
-            let team = get_team_by_name(&mut ctx, &name).await?;
+            let team = get_team_by_name(ctx, &name).await?;
             FromSql::Availability(team.is_none())
         },
         TeamQuery::CreateNewTeam {
-            name, description, eligible, affiliation,
+            name, description, eligible, affiliation, division,
             password,
             initial_user, user_auth,
         } => {
@@ -84,10 +249,10 @@ pub async fn handle(mut ctx: super::Ctx, query: TeamQuery) -> Result<FromSql, Fr
                 return Err(FromSqlErr::OtherServerError("Failed to hash team password.".into()))
             };
 
-            let team_already_exists = get_team_by_name(&mut ctx, &name).await?.is_some();
+            let team_already_exists = get_team_by_name(ctx, &name).await?.is_some();
             if team_already_exists { return Err(FromSqlErr::NameIsTaken(name)); }
 
-            let Some(user) = super::prepared::users::get_user(&mut ctx, initial_user).await? else {
+            let Some(user) = super::prepared::users::get_user(ctx, initial_user).await? else {
                 warn!("Initial user {initial_user} does not exist, tried to create a team");
                 return Err(FromSqlErr::DoesNotExist(initial_user));
             };
@@ -96,48 +261,190 @@ pub async fn handle(mut ctx: super::Ctx, query: TeamQuery) -> Result<FromSql, Fr
                 return Err(FromSqlErr::OtherServerError(format!("{} already on team", user.id).into()));
             }
 
-            if !super::prepared::users::check_user_auth(&mut ctx, initial_user, user_auth).await? {
+            if !super::prepared::users::check_user_auth(ctx, initial_user, user_auth).await? {
                 warn!("Initial user {initial_user} failed to auth");
                 return Err(FromSqlErr::Auth);
             }
 
 
-            let team = create_team(&mut ctx, NewTeamInput {
+            let team = create_team(ctx, NewTeamInput {
                 name,
                 description,
                 eligible,
                 affiliation,
+                division,
                 hashed_password: hash
             }).await?;
 
 
-            super::prepared::users::set_user_team(&mut ctx, initial_user, team.id).await?;
+            super::prepared::users::set_user_team(ctx, initial_user, team.id).await?;
 
 
-            let Some(team) = get_team(&mut ctx, team.id).await? else {
+            let Some(team) = get_team(ctx, team.id).await? else {
                 error!("Couldn't find team {:?} ({}) which was just created", team.name, team.id);
                 return Err(FromSqlErr::OtherServerError("Failed to create team".into()))
             };
 
             FromSql::Team(team)
         },
-        TeamQuery::UpdateTeam { id, name, description, eligible, affiliation, password } => {
+        TeamQuery::UpdateTeam { id, name, description, eligible, affiliation, division, password } => {
             debug!("SQL team req classified as 'UpdateTeam<{id}>' req");
 
-            if !check_team_auth(&mut ctx, id, password).await? {
+            if !check_team_auth(ctx, id, password).await? {
                 return Err(FromSqlErr::DatabaseError)
             }
 
             FromSql::Team(
-                update_team(&mut ctx, TeamInput {
+                update_team(ctx, TeamInput {
                     id,
                     name,
                     description,
                     eligible,
                     affiliation,
+                    division,
                 }).await?
             )
         },
+        TeamQuery::UnlockHint { team_id, user_id, user_auth, hint_id } => {
+            debug!("SQL team req classified as 'UnlockHint<{team_id} unlocks {hint_id}>' req");
+
+            use super::prepared::users::{ user_is_on_team, UserIsOnTeamOutcome::* };
+            use super::prepared::hints::{ get_hint, unlock_hint };
+
+            match user_is_on_team(ctx, user_id, team_id).await? {
+                DoesNotExist => return Err(FromSqlErr::DoesNotExist(user_id)),
+                NotOnTeam => return Err(FromSqlErr::Auth),
+                IsOnTeam => (),
+            }
+
+            if !super::prepared::users::check_user_auth(ctx, user_id, user_auth).await? {
+                return Err(FromSqlErr::Auth);
+            }
+            super::check_not_banned(ctx, user_id, team_id).await?;
+
+            let Some(hint) = get_hint(ctx, hint_id).await? else {
+                return Err(FromSqlErr::DoesNotExist(hint_id));
+            };
+            super::check_chall_available(ctx, hint.challenge_id, team_id).await?;
+
+            if unlock_hint(ctx, hint_id, team_id, user_id).await? {
+                info!("Team {team_id} unlocked hint {hint_id} for {} points", hint.cost);
+            }
+            FromSql::Hint(hint)
+        },
+        TeamQuery::GetUnlockedHints { team_id, user_id, user_auth } => {
+            debug!("SQL team req classified as 'GetUnlockedHints<{team_id}>' req");
+
+            use super::prepared::users::{ user_is_on_team, UserIsOnTeamOutcome::* };
+
+            match user_is_on_team(ctx, user_id, team_id).await? {
+                DoesNotExist => return Err(FromSqlErr::DoesNotExist(user_id)),
+                NotOnTeam => return Err(FromSqlErr::Auth),
+                IsOnTeam => (),
+            }
+
+            if !super::prepared::users::check_user_auth(ctx, user_id, user_auth).await? {
+                return Err(FromSqlErr::Auth);
+            }
+
+            FromSql::HintArr(super::prepared::hints::get_unlocked_hints(ctx, team_id).await?)
+        },
     };
     Ok(success_res)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::prepared::solves::update_all_scores;
+    use super::super::scoring::update_chall_value;
+    use super::super::testing;
+
+    fn time(s: &str) -> NaiveDateTime {
+        let Ok(time) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") else {
+            panic!("Bad time {s}");
+        };
+        time
+    }
+
+    /// Three teams solve a challenge that decays from 500 to 300 points by the
+    /// third solve, an hour apart from 11:00. Returns the teams in solve order.
+    async fn decayed_solves(ctx: &mut super::super::Ctx) -> Vec<uuid::Uuid> {
+        let chall_id = testing::chall(ctx, "chall", 500).await;
+        testing::decay(ctx, chall_id, 100, 4).await;
+
+        let mut teams = Vec::new();
+        for (i, at) in ["2024-01-01 11:00:00", "2024-01-01 12:00:00", "2024-01-01 13:00:00"].into_iter().enumerate() {
+            let team_id = testing::team(ctx, &format!("team {i}")).await;
+            let user_id = testing::user(ctx, &format!("user {i}"), team_id).await;
+            testing::solve(ctx, user_id, team_id, chall_id, at).await;
+            teams.push(team_id);
+        }
+
+        assert!(update_all_scores(ctx).await.is_ok());
+        assert!(matches!(update_chall_value(ctx, chall_id).await, Ok(Some(300))));
+        teams
+    }
+
+    fn last_scores(history: FromSql) -> Vec<(uuid::Uuid, i64)> {
+        let FromSql::TeamScoreHistoryArray(mut entries) = history else {
+            panic!("Expected a score history");
+        };
+        entries.sort_by_key(|entry| (entry.team_id, entry.time));
+
+        let mut last = Vec::<(uuid::Uuid, i64)>::new();
+        for entry in entries {
+            match last.last_mut() {
+                Some((team_id, score)) if *team_id == entry.team_id => *score = entry.score,
+                _ => last.push((entry.team_id, entry.score)),
+            }
+        }
+        last
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn live_history_ends_at_the_stored_score(pool: sqlx::PgPool) {
+        let mut ctx = testing::setup(&pool).await;
+        let teams = decayed_solves(&mut ctx).await;
+        let filter = ScoreboardFilter::default();
+
+        let Ok(history) = top_teams_history(&mut ctx, 10, time("2024-01-01 00:00:00"), &filter, None).await else {
+            panic!("Couldn't get the score history");
+        };
+        let last = last_scores(history);
+        assert_eq!(last.len(), teams.len());
+
+        for (team_id, score) in last {
+            assert_eq!(score, 300);
+            assert_eq!(score, i64::from(testing::team_score(&mut ctx, team_id).await));
+        }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn frozen_board_and_history_use_prices_from_the_freeze(pool: sqlx::PgPool) {
+        let mut ctx = testing::setup(&pool).await;
+        let teams = decayed_solves(&mut ctx).await;
+        let filter = ScoreboardFilter::default();
+        let frozen_at = time("2024-01-01 12:30:00");
+
+        // By the freeze, two teams had solved it, so it was worth 400 points
+        let Ok(FromSql::TeamArr(board)) = top_teams(&mut ctx, 10, &filter, Some(frozen_at)).await else {
+            panic!("Couldn't get the frozen scoreboard");
+        };
+        let score_of = |team_id| board.iter().find(|team| team.id == team_id).map(|team| team.score);
+        assert_eq!(score_of(teams[0]), Some(400));
+        assert_eq!(score_of(teams[1]), Some(400));
+        assert_eq!(score_of(teams[2]), Some(0));
+
+        let Ok(ranked) = queries::get_top_teams_at(&mut ctx, 10, &filter, frozen_at).await else {
+            panic!("Couldn't rank the frozen scoreboard");
+        };
+        assert_eq!(ranked, teams, "ties go to the team that solved first");
+
+        let Ok(history) = top_teams_history(&mut ctx, 10, time("2024-01-01 00:00:00"), &filter, Some(frozen_at)).await else {
+            panic!("Couldn't get the frozen score history");
+        };
+        let mut last = last_scores(history);
+        last.sort_by_key(|&(team_id, _)| teams.iter().position(|&id| id == team_id));
+        assert_eq!(last, vec![(teams[0], 400), (teams[1], 400), (teams[2], 0)]);
+    }
+}
@@ -0,0 +1,214 @@
+//! Helpers for tests that run against a real database.
+//!
+//! Tests take the pool from `#[sqlx::test(migrations = false)]`, which gives
+//! each test its own empty database, and call [`setup`] to load the schema into
+//! it. Everything then runs in one transaction, the same way requests do, so
+//! `CURRENT_TIMESTAMP` is fixed for the whole test; fixtures that care about
+//! ordering take explicit times instead.
+
+use std::path::Path;
+
+use sqlx::{ query, Executor, PgPool };
+use uuid::Uuid;
+
+use crate::payloads::incoming::sql::Auth;
+
+use super::Ctx;
+
+/// The password of every user made by [`admin`].
+pub const PASSWORD: &str = "hunter2";
+
+/// Loads the schema the way it's deployed: the numbered migrations in order,
+/// then `functions.sql`. `init.sql` only sets up the `arcs` role and database,
+/// so just its extensions are created here.
+pub async fn setup(pool: &PgPool) -> Ctx {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("schema");
+
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        panic!("Couldn't read {}", dir.display());
+    };
+    let mut migrations: Vec<u32> = entries
+        .filter_map(|entry| entry.ok()?.path().file_stem()?.to_str()?.parse().ok())
+        .collect();
+    migrations.sort_unstable();
+
+    let Ok(mut ctx) = pool.begin().await else {
+        panic!("Couldn't start a transaction");
+    };
+
+    let preamble = r#"
+        SET TIME ZONE 'UTC';
+        CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+        CREATE EXTENSION IF NOT EXISTS citext;
+    "#;
+    if let Err(e) = ctx.execute(preamble).await {
+        panic!("Couldn't set up the database: {e}");
+    }
+
+    let files = migrations.iter().map(u32::to_string).chain(["functions".to_string()]);
+    for file in files {
+        let Ok(sql) = std::fs::read_to_string(dir.join(format!("{file}.sql"))) else {
+            panic!("Couldn't read schema/{file}.sql");
+        };
+        if let Err(e) = ctx.execute(sql.as_str()).await {
+            panic!("schema/{file}.sql failed: {e}");
+        }
+    }
+
+    ctx
+}
+
+pub async fn team(ctx: &mut Ctx, name: &str) -> Uuid {
+    let query = query!(
+        r#"
+            INSERT INTO teams (name, description, hashed_password)
+            VALUES ($1::text, '', '')
+            RETURNING id;
+        "#,
+        name,
+    );
+    let Ok(row) = query.fetch_one(ctx).await else {
+        panic!("Couldn't create team {name}");
+    };
+    row.id
+}
+
+pub async fn user(ctx: &mut Ctx, name: &str, team_id: Uuid) -> Uuid {
+    let query = query!(
+        r#"
+            INSERT INTO users (email, name, team_id)
+            VALUES ($1::text || '@example.com', $1::text, $2)
+            RETURNING id;
+        "#,
+        name,
+        team_id,
+    );
+    let Ok(row) = query.fetch_one(ctx).await else {
+        panic!("Couldn't create user {name}");
+    };
+    row.id
+}
+
+/// Makes an admin who logs in with [`PASSWORD`], and returns their id and auth.
+pub async fn admin(ctx: &mut Ctx) -> (Uuid, Auth) {
+    use crate::passwords::*;
+
+    let team_id = team(ctx, "admins").await;
+    let id = user(ctx, "admin", team_id).await;
+
+    let Ok(salt) = salt() else {
+        panic!("Couldn't make a salt");
+    };
+    let Ok(hash) = argon2::hash_encoded(PASSWORD.as_bytes(), &salt, &ARGON2_CONFIG) else {
+        panic!("Couldn't hash the password");
+    };
+
+    let query = query!(
+        r#"
+            WITH admin AS (
+                UPDATE users SET admin = true WHERE id = $1
+            )
+            INSERT INTO auth_name_pass (id, user_id, hashed_password)
+            VALUES ($1, $1, $2);
+        "#,
+        id,
+        hash,
+    );
+    if let Err(e) = query.execute(ctx).await {
+        panic!("Couldn't make the admin: {e}");
+    }
+
+    (id, Auth::Pass { password: PASSWORD.to_string() })
+}
+
+/// Makes a static challenge worth `points`.
+pub async fn chall(ctx: &mut Ctx, name: &str, points: i32) -> Uuid {
+    let query = query!(
+        r#"
+            INSERT INTO challenges (
+                name, description, flag, points, current_points,
+                authors, categories, tags, source_folder
+            )
+            VALUES ($1::text, '', 'flag', $2, $2, '{}', '{}', '{}', $1::text)
+            RETURNING id;
+        "#,
+        name,
+        points,
+    );
+    let Ok(row) = query.fetch_one(ctx).await else {
+        panic!("Couldn't create challenge {name}");
+    };
+    row.id
+}
+
+/// Makes the challenge decay linearly to `min_points` over `decay` solves.
+pub async fn decay(ctx: &mut Ctx, chall_id: Uuid, min_points: i32, decay: i32) {
+    let query = query!(
+        r#"
+            UPDATE challenges
+            SET scoring_type = 'linear', min_points = $2, decay = $3
+            WHERE id = $1;
+        "#,
+        chall_id,
+        min_points,
+        decay,
+    );
+    if let Err(e) = query.execute(ctx).await {
+        panic!("Couldn't make {chall_id} decay: {e}");
+    }
+}
+
+/// Records a correct attempt by the user that counts for their team, at `at`
+/// (e.g. `"2024-01-01 12:00:00"`).
+pub async fn solve(ctx: &mut Ctx, user_id: Uuid, team_id: Uuid, chall_id: Uuid, at: &str) {
+    let query = query!(
+        r#"
+            WITH attempt AS (
+                INSERT INTO solve_attempts (flag_guess, correct, user_id, challenge_id, team_id, inserted_at)
+                VALUES ('flag', true, $1, $3, $2, $4::text::timestamp)
+                RETURNING id
+            )
+            INSERT INTO solve_successes (attempt_id, user_id, challenge_id, team_id, solved_at)
+            SELECT attempt.id, $1, $3, $2, $4::text::timestamp FROM attempt;
+        "#,
+        user_id,
+        team_id,
+        chall_id,
+        at,
+    );
+    if let Err(e) = query.execute(ctx).await {
+        panic!("Couldn't solve {chall_id}: {e}");
+    }
+}
+
+/// Adds a hint to the challenge and returns its id.
+pub async fn hint(ctx: &mut Ctx, chall_id: Uuid, position: i32, cost: i32) -> Uuid {
+    let query = query!(
+        r#"
+            INSERT INTO challenge_hints (challenge_id, position, content, cost)
+            VALUES ($1, $2, 'hint', $3)
+            RETURNING id;
+        "#,
+        chall_id,
+        position,
+        cost,
+    );
+    let Ok(row) = query.fetch_one(ctx).await else {
+        panic!("Couldn't create hint {position} of {chall_id}");
+    };
+    row.id
+}
+
+/// The stored `score` of the team.
+pub async fn team_score(ctx: &mut Ctx, team_id: Uuid) -> i32 {
+    let query = query!(
+        r#"
+            SELECT score FROM teams WHERE id = $1;
+        "#,
+        team_id,
+    );
+    let Ok(row) = query.fetch_one(ctx).await else {
+        panic!("Couldn't get the score of {team_id}");
+    };
+    row.score
+}
@@ -13,6 +13,8 @@ use queries::{
 };
 use queries::{ UserInput, NewUserInput, Auth as SqlAuth };
 
+use super::check_admin;
+
 fn get_create_auth(auth: IncomingAuth) -> Result<SqlAuth, FromSqlErr> {
     use crate::passwords::*;
 
@@ -31,18 +33,18 @@ fn get_create_auth(auth: IncomingAuth) -> Result<SqlAuth, FromSqlErr> {
     Ok(auth_val)
 }
 
-pub async fn handle(mut ctx: super::Ctx, query: UserQuery) -> Result<FromSql, FromSqlErr> {
+pub async fn handle(ctx: &mut super::Ctx, query: UserQuery) -> Result<FromSql, FromSqlErr> {
     trace!("Handling SQL user req");
 
     let success_res = match query {
         UserQuery::GetAllUsers => {
             debug!("SQL user req classified as 'GetAllUsers' req");
-            FromSql::UserArr(get_all_users(&mut ctx).await?)
+            FromSql::UserArr(get_all_users(ctx).await?)
         },
         UserQuery::GetUser { id } => {
             debug!("SQL user req classified as 'GetUser<{id}>' req");
 
-            if let Some(user) = get_user(&mut ctx, id).await? {
+            if let Some(user) = get_user(ctx, id).await? {
                 FromSql::User(user)
             } else {
                 return Err(FromSqlErr::DoesNotExist(id))
@@ -52,7 +54,7 @@ pub async fn handle(mut ctx: super::Ctx, query: UserQuery) -> Result<FromSql, Fr
             let display_name = shortened(&name, 13);
             debug!("SQL user req classified as 'CheckUsernameAvailability<`{display_name}`>' req");
 
-            let user = get_user_by_name(&mut ctx, &name).await?;
+            let user = get_user_by_name(ctx, &name).await?;
             FromSql::Availability(user.is_none())
         },
         UserQuery::CreateNewUser { name, email, eligible, admin, auth } => {
@@ -64,7 +66,7 @@ pub async fn handle(mut ctx: super::Ctx, query: UserQuery) -> Result<FromSql, Fr
             let auth = get_create_auth(auth)?;
 
             FromSql::User(
-                create_user(&mut ctx, NewUserInput {
+                create_user(ctx, NewUserInput {
                     name,
                     email,
                     eligible,
@@ -76,18 +78,11 @@ pub async fn handle(mut ctx: super::Ctx, query: UserQuery) -> Result<FromSql, Fr
         UserQuery::Promote { admin_id, admin_auth, user_to_promote } => {
             debug!("SQL user req classified as 'Promote<{admin_id} promotes user {user_to_promote} to admin>' req");
 
-            if !check_user_auth(&mut ctx, admin_id, admin_auth).await? {
-                return Err(FromSqlErr::Auth);
-            }
-            let admin = get_user(&mut ctx, admin_id).await?.ok_or(sqlx::Error::RowNotFound)?;
-
-            if !admin.admin {
-                return Err(FromSqlErr::Auth);
-            }
+            check_admin(ctx, admin_id, admin_auth).await?;
 
             FromSql::User(
                 update_user(
-                    &mut ctx,
+                    ctx,
                     UserInput {
                         id: user_to_promote,
                         name: None,
@@ -100,15 +95,15 @@ pub async fn handle(mut ctx: super::Ctx, query: UserQuery) -> Result<FromSql, Fr
         UserQuery::UpdateUserAuth { id, old_auth, new_auth } => {
             debug!("SQL user req classified as 'UpdateUserAuth<{id}>' req");
 
-            if !check_user_auth(&mut ctx, id, old_auth).await? {
+            if !check_user_auth(ctx, id, old_auth).await? {
                 return Err(FromSqlErr::Auth)
             }
-            set_auth(&mut ctx, id, get_create_auth(new_auth)?).await?;
-            FromSql::User(get_user(&mut ctx, id).await?.ok_or(sqlx::Error::RowNotFound)?)
+            set_auth(ctx, id, get_create_auth(new_auth)?).await?;
+            FromSql::User(get_user(ctx, id).await?.ok_or(sqlx::Error::RowNotFound)?)
         },
         UserQuery::CheckUserAuth { id, auth } => {
             debug!("SQL user req classified as 'CheckUserAuth<{id}>' req");
-            FromSql::AuthStatus(check_user_auth(&mut ctx, id, auth).await?)
+            FromSql::AuthStatus(check_user_auth(ctx, id, auth).await?)
         },
         UserQuery::JoinTeam { id, auth, team_name, team_pass } => {
             use super::prepared::teams::{ get_team_by_name, check_team_auth };
@@ -116,19 +111,125 @@ pub async fn handle(mut ctx: super::Ctx, query: UserQuery) -> Result<FromSql, Fr
             let display_name = shortened(&team_name, 13);
             debug!("SQL user req classified as 'JoinTeam<{id} joining {display_name}>' req");
 
-            let Some(team) = get_team_by_name(&mut ctx, &team_name).await? else {
+            let Some(team) = get_team_by_name(ctx, &team_name).await? else {
                 return Err(FromSqlErr::NameDoesNotExist(team_name))
             };
 
-            let user_auth = check_user_auth(&mut ctx, id, auth).map_err(FromSqlErr::from).await?;
-            let team_auth = check_team_auth(&mut ctx, team.id, team_pass).map_err(FromSqlErr::from).await?;
+            let user_auth = check_user_auth(ctx, id, auth).map_err(FromSqlErr::from).await?;
+            let team_auth = check_team_auth(ctx, team.id, team_pass).map_err(FromSqlErr::from).await?;
             
             if user_auth && team_auth {
-                FromSql::User(set_user_team(&mut ctx, id, team.id).await?)
+                FromSql::User(set_user_team(ctx, id, team.id).await?)
             } else {
                 return Err(FromSqlErr::Auth)
             }
-        }
+        },
+        UserQuery::SetBanned { admin_id, admin_auth, id, banned } => {
+            debug!("SQL user req classified as 'SetBanned<{id}, {banned}>' req");
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            let Some(user) = queries::set_user_banned(ctx, id, banned).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+            info!("User {id} {} by {admin_id}", if banned { "banned" } else { "unbanned" });
+
+            FromSql::User(user)
+        },
+        UserQuery::Delete { admin_id, admin_auth, id } => {
+            debug!("SQL user req classified as 'Delete<{id}>' req");
+
+            use super::prepared::solves::{
+                get_counted_solves_by_user, delete_solves_for_user, count_earliest_correct_attempt, update_all_scores,
+            };
+
+            check_admin(ctx, admin_id, admin_auth).await?;
+
+            let Some(user) = get_user(ctx, id).await? else {
+                return Err(FromSqlErr::DoesNotExist(id));
+            };
+
+            let counted_solves = get_counted_solves_by_user(ctx, id).await?;
+            delete_solves_for_user(ctx, id).await?;
+            queries::delete_user(ctx, id).await?;
+
+            // A teammate who solved the challenge later keeps it solved for
+            // the team.
+            for &(team_id, chall_id) in &counted_solves {
+                count_earliest_correct_attempt(ctx, team_id, chall_id).await?;
+            }
+
+            update_all_scores(ctx).await?;
+            for (_, chall_id) in counted_solves {
+                super::scoring::update_chall_value(ctx, chall_id).await?;
+            }
+            warn!("User {id} ({:?}) deleted by {admin_id}", user.name.str());
+
+            FromSql::User(user)
+        },
     };
     Ok(success_res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::prepared::{ hints::unlock_hint, solves::update_all_scores };
+    use super::super::testing;
+
+    #[sqlx::test(migrations = false)]
+    async fn deleting_a_user_keeps_their_teams_hint_costs(pool: sqlx::PgPool) {
+        let mut ctx = testing::setup(&pool).await;
+        let (admin_id, admin_auth) = testing::admin(&mut ctx).await;
+
+        let team_id = testing::team(&mut ctx, "team").await;
+        let solver = testing::user(&mut ctx, "solver", team_id).await;
+        let unlocker = testing::user(&mut ctx, "unlocker", team_id).await;
+        let chall_id = testing::chall(&mut ctx, "chall", 100).await;
+        let hint_id = testing::hint(&mut ctx, chall_id, 1, 30).await;
+
+        testing::solve(&mut ctx, solver, team_id, chall_id, "2024-01-01 12:00:00").await;
+        assert!(matches!(unlock_hint(&mut ctx, hint_id, team_id, unlocker).await, Ok(true)));
+        assert!(update_all_scores(&mut ctx).await.is_ok());
+        assert_eq!(testing::team_score(&mut ctx, team_id).await, 70);
+
+        let deleted = handle(&mut ctx, UserQuery::Delete { admin_id, admin_auth, id: unlocker }).await;
+        assert!(matches!(deleted, Ok(FromSql::User(_))));
+
+        assert_eq!(testing::team_score(&mut ctx, team_id).await, 70);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn deleting_a_user_moves_their_teams_last_solve_back(pool: sqlx::PgPool) {
+        use super::super::prepared::teams::get_top_teams;
+        use crate::payloads::incoming::sql::ScoreboardFilter;
+
+        let mut ctx = testing::setup(&pool).await;
+        let (admin_id, admin_auth) = testing::admin(&mut ctx).await;
+
+        let chall_id = testing::chall(&mut ctx, "chall", 100).await;
+        let survey_id = testing::chall(&mut ctx, "survey", 0).await;
+
+        // Both teams have 100 points, but the first only solved the (free)
+        // survey after the second team's last solve.
+        let first = testing::team(&mut ctx, "first").await;
+        let solver = testing::user(&mut ctx, "solver", first).await;
+        let late = testing::user(&mut ctx, "late", first).await;
+        testing::solve(&mut ctx, solver, first, chall_id, "2024-01-01 11:00:00").await;
+        testing::solve(&mut ctx, late, first, survey_id, "2024-01-01 14:00:00").await;
+
+        let second = testing::team(&mut ctx, "second").await;
+        let other = testing::user(&mut ctx, "other", second).await;
+        testing::solve(&mut ctx, other, second, chall_id, "2024-01-01 12:00:00").await;
+
+        assert!(update_all_scores(&mut ctx).await.is_ok());
+        let filter = ScoreboardFilter::default();
+        assert!(matches!(get_top_teams(&mut ctx, 2, &filter).await, Ok(top) if top == [second, first]));
+
+        let deleted = handle(&mut ctx, UserQuery::Delete { admin_id, admin_auth, id: late }).await;
+        assert!(matches!(deleted, Ok(FromSql::User(_))));
+
+        // The tie is now broken by the first team's 11:00 solve
+        assert!(matches!(get_top_teams(&mut ctx, 2, &filter).await, Ok(top) if top == [first, second]));
+    }
+}
@@ -51,8 +51,14 @@
 //!   server.
 //! - [`payloads::outgoing::Outgoing`] is the shape of data returned from the
 //!   webhook server.
+//! - [`payloads::outgoing::error::RequestError`] is returned instead when a
+//!   request can't be handled at all (bad authorization, malformed or
+//!   oversized bodies, unknown fields, ...).
 //! - The command `cargo run --bin generate_meta` will export the JSON schema
-//!   for an incoming payload in `./meta/incoming.schema.json`.
+//!   for an incoming payload in `./meta/incoming.schema.json`, along with the
+//!   outgoing and error payloads.
+//! - The command `cargo run --bin export_scoreboard` will export the
+//!   scoreboard in CTFtime's format (or as CSV with `-- csv`).
 //! 
 
 
@@ -67,9 +73,11 @@ pub mod payloads;
 pub mod handlers;
 
 pub mod env;
+pub mod metrics;
 mod auth;
 
-pub use auth::{ AuthHeader, Token };
+pub use auth::{ AuthError, AuthHeader, RequestParts, SignatureError, SIGNATURE_SCHEME, Token };
+pub use auth::{ reload_secrets, watch_secrets, SecretsError };
 pub use sql::start_db_connection;
 
 #[allow(unused_macros)]
@@ -165,6 +173,7 @@ mod sql {
         PgPool,
         pool::PoolConnection,
         Postgres,
+        Transaction,
     };
     use sqlx::{
         postgres::{PgConnectOptions, PgPoolOptions},
@@ -184,10 +193,10 @@ mod sql {
         pub fn wrap(s: String) -> Self { Self(s) }
     }
 
-    pub async fn connection() -> Result<PoolConnection<Postgres>, Error> {
+    async fn pool() -> &'static PgPool {
         static CONNECTION: OnceCell<PgPool> = OnceCell::const_new();
 
-        let mutex = CONNECTION
+        CONNECTION
             .get_or_init(|| async {
 
                 let connection_options = PgConnectOptions::new()
@@ -204,7 +213,15 @@ mod sql {
 
                 let options = PgPoolOptions::new()
                     .min_connections(4)
-                    .max_connections(8);
+                    .max_connections(8)
+                    // Times are compared against `competition::now()`, which is
+                    // UTC, so `CURRENT_TIMESTAMP` has to be stored as UTC too
+                    .after_connect(|connection, _| Box::pin(async move {
+                        use sqlx::Executor;
+
+                        connection.execute("SET TIME ZONE 'UTC';").await?;
+                        Ok(())
+                    }));
 
                 #[warn(clippy::unwrap_used)]
                 options
@@ -212,13 +229,32 @@ mod sql {
                     .await
                     .unwrap()
             })
-            .await;
+            .await
+    }
 
-        mutex
+    pub async fn connection() -> Result<PoolConnection<Postgres>, Error> {
+        pool()
+            .await
             .acquire()
             .await
     }
 
+    /// Starts a transaction on a connection from the pool. The transaction is
+    /// rolled back if it is dropped without being committed.
+    pub async fn transaction() -> Result<Transaction<'static, Postgres>, Error> {
+        pool()
+            .await
+            .begin()
+            .await
+    }
+
+    /// Gets how many connections the pool has open, and how many of them are
+    /// idle.
+    pub async fn pool_usage() -> (u32, usize) {
+        let pool = pool().await;
+        (pool.size(), pool.num_idle())
+    }
+
     pub async fn start_db_connection() -> Result<(), sqlx::Error> {
         connection().await.map(|_| ())
     }
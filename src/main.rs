@@ -1,14 +1,11 @@
 use arcs_logging_rs::{DEFAULT_LOGGING_TARGETS, set_up_logging};
 
-use webhook_rs::handlers::Handle as _;
-
 use webhook_rs::env;
 
 use webhook_rs::logging::*;
 
 use actix_web::{
     HttpServer, App, Responder,
-    web::Json,
 };
 use webhook_rs::start_db_connection;
 
@@ -45,6 +42,12 @@ async fn main() -> std::io::Result<()> {
             auth: "auth",
         );
     }
+
+    if let Err(e) = webhook_rs::reload_secrets() {
+        error!("{e}");
+        error!("Aborting...");
+        std::process::exit(1);
+    }
     
     if let Err(e) = start_db_connection().await {
         error!("Failed to initialize database connection.");
@@ -53,6 +56,9 @@ async fn main() -> std::io::Result<()> {
         std::process::exit(1);
     }
 
+    actix_web::rt::spawn(webhook_rs::handlers::outbox::run_worker());
+    actix_web::rt::spawn(webhook_rs::watch_secrets());
+
 
     let ip = "0.0.0.0";
     let port = env::port().parse().unwrap();
@@ -60,6 +66,9 @@ async fn main() -> std::io::Result<()> {
     let res = HttpServer::new(|| {
         App::new()
             .service(main_route)
+            .service(healthz)
+            .service(readyz)
+            .service(metrics)
     })
         .bind((ip, port))?
         .run()
@@ -76,27 +85,102 @@ async fn main() -> std::io::Result<()> {
 }
 
 
-use actix_web::{ web::Header, HttpResponse };
-use serde_json::json;
+use actix_web::{ web::{ BytesMut, Payload }, http::header::Header as _, HttpRequest, HttpResponse };
+use futures::StreamExt;
 use webhook_rs::{
-    AuthHeader, Token,
-    payloads::incoming::Incoming,
+    AuthHeader, RequestParts, Token,
+    handlers::{ audit, health, permissions },
+    payloads::{
+        incoming::Incoming,
+        outgoing::error::{ ErrorCode, RequestError },
+    },
 };
 
+/// The largest request body that's accepted, in bytes.
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Reads the whole body, as long as it isn't bigger than [`MAX_BODY_SIZE`].
+async fn read_body(mut payload: Payload) -> Result<BytesMut, RequestError> {
+    let mut body = BytesMut::new();
+
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| RequestError::new(ErrorCode::UnreadableBody, e.to_string()))?;
+
+        if body.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(RequestError::new(
+                ErrorCode::BodyTooLarge,
+                format!("The body is bigger than the limit of {MAX_BODY_SIZE} bytes"),
+            ));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Authenticates, parses and checks the permissions of a request.
+async fn accept(req: &HttpRequest, payload: Payload) -> Result<(Token, Incoming), RequestError> {
+    let authorization = AuthHeader::parse(req).map_err(|_| RequestError::new(
+        ErrorCode::MissingAuthorization,
+        "The request has no authorization header",
+    ))?;
+
+    let body = read_body(payload).await?;
+
+    let parts = RequestParts {
+        method: req.method().as_str(),
+        path: req.path(),
+        body: &body,
+    };
+    let token = authorization.authenticate(&[ Token::Frontend, Token::Deploy ], parts)?;
+
+    let incoming = Incoming::parse(&body)?;
+    permissions::check(token, &incoming)?;
+
+    Ok((token, incoming))
+}
+
 
 #[actix_web::post("/")]
-async fn main_route(json: Json<Incoming>, authorization: Header<AuthHeader>) -> impl Responder {
-    if authorization.0.check_matches(&[ Token::Frontend, Token::Deploy ]) {
-        json
-            .into_inner()
-            .handle()
-            .await
-            .unwrap()
-            .response()
+async fn main_route(req: HttpRequest, payload: Payload) -> impl Responder {
+    let (token, incoming) = match accept(&req, payload).await {
+        Ok(accepted) => accepted,
+        Err(e) => {
+            info!("Rejected request ({:?}): {}", e.code, e.error);
+            return e.response();
+        },
+    };
+
+    let audited = audit::Pending::new(&incoming);
+    let outgoing = incoming.handle_audited(token).await;
+    audited.record(token, &outgoing).await;
+    outgoing.response()
+}
+
+
+/// Liveness: responds as long as the server is running, without checking any
+/// dependencies.
+#[actix_web::get("/healthz")]
+async fn healthz() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness: checks each dependency, responding with 503 if any are down.
+/// The result is reused for a few seconds (see [`health::CACHED_FOR`]).
+#[actix_web::get("/readyz")]
+async fn readyz() -> impl Responder {
+    let readiness = health::readiness().await;
+
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
     } else {
-        // TODO: More accurate error messages
-        HttpResponse::Unauthorized()
-            .json(json!({ "error": "Improper bearer authentication" }))
+        HttpResponse::ServiceUnavailable().json(readiness)
     }
+}
 
+/// Prometheus metrics (see [`webhook_rs::metrics`]).
+#[actix_web::get("/metrics")]
+async fn metrics() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(webhook_rs::metrics::render().await)
 }
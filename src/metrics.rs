@@ -0,0 +1,228 @@
+//! Prometheus metrics, served in the text format by [`render`].
+//!
+//! The metrics are:
+//! - `arcs_queries_total` and `arcs_query_duration_seconds`, for every query
+//!   by target and operation (e.g. `sql.team.create`, see
+//!   [`crate::handlers::permissions`]), with its outcome and status code. SQL
+//!   sequences are counted step by step, each step with the sequence's
+//!   outcome since they succeed or fail together, and timed as a whole
+//! - `arcs_outbound_request_duration_seconds`, for requests to Discord, the
+//!   deploy server and the frontend, with their status code
+//! - `arcs_db_pool_connections`, for how many database connections are open
+//!   and idle
+//! - `arcs_solve_attempts_total` and `arcs_first_bloods_total`, counted once
+//!   the transaction they were made in is committed (see [`Uncommitted`])
+//!
+//! Operation labels come from the query's tags, so there's a bounded number of
+//! them.
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge_vec,
+    Encoder, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use serde::Serialize;
+
+use crate::handlers::{ Handle, OutgoingErr, ResponseFrom };
+use crate::handlers::permissions::{ operation_name, sequence_steps };
+use crate::logging::*;
+
+// Registering only fails for duplicate or invalid names, which are fixed here.
+#[allow(clippy::unwrap_used)]
+mod registered {
+    use super::*;
+
+    lazy_static! {
+        pub static ref QUERIES: IntCounterVec = register_int_counter_vec!(
+            "arcs_queries_total",
+            "Queries handled, by target, operation, outcome and status code.",
+            &["target", "operation", "outcome", "status_code"]
+        ).unwrap();
+
+        pub static ref QUERY_DURATION: HistogramVec = register_histogram_vec!(
+            "arcs_query_duration_seconds",
+            "How long queries took to handle, by target and operation.",
+            &["target", "operation"]
+        ).unwrap();
+
+        pub static ref OUTBOUND_DURATION: HistogramVec = register_histogram_vec!(
+            "arcs_outbound_request_duration_seconds",
+            "How long requests to other services took, by service and status code.",
+            &["service", "status_code"]
+        ).unwrap();
+
+        pub static ref DB_POOL: IntGaugeVec = register_int_gauge_vec!(
+            "arcs_db_pool_connections",
+            "Database connections in the pool, by state.",
+            &["state"]
+        ).unwrap();
+
+        pub static ref SOLVE_ATTEMPTS: IntCounterVec = register_int_counter_vec!(
+            "arcs_solve_attempts_total",
+            "Solve attempts, by whether they were correct.",
+            &["correct"]
+        ).unwrap();
+
+        pub static ref FIRST_BLOODS: IntCounter = register_int_counter!(
+            "arcs_first_bloods_total",
+            "First solves of a challenge."
+        ).unwrap();
+    }
+}
+use registered::*;
+
+/// A query that's timed and counted when it's handled.
+///
+/// This wraps each query of a target, so batches are measured query by query.
+pub (crate) struct Measured<T> {
+    target: &'static str,
+    query: T,
+}
+
+impl<T> Measured<T> {
+    pub (crate) fn new(target: &'static str, query: T) -> Self {
+        Self { target, query }
+    }
+}
+
+#[async_trait]
+impl<T> Handle for Measured<T>
+where
+    T: Handle + Serialize + Send,
+    T::SuccessPayload: Send,
+    T::ErrorPayload: Send,
+{
+    type SuccessPayload = T::SuccessPayload;
+    type ErrorPayload = T::ErrorPayload;
+    async fn handle(self) -> ResponseFrom<Self> {
+        let (operation, counted) = match serde_json::to_value(&self.query) {
+            Ok(value) => {
+                let operation = operation_name(self.target, &value);
+                let counted = match sequence_steps(&value) {
+                    Some(steps) => steps.iter().map(|step| operation_name(self.target, step)).collect(),
+                    None => vec![operation.clone()],
+                };
+                (operation, counted)
+            },
+            Err(_) => (self.target.to_string(), vec![self.target.to_string()]),
+        };
+
+        let started = Instant::now();
+        let result = self.query.handle().await;
+
+        QUERY_DURATION
+            .with_label_values(&[self.target, &operation])
+            .observe(started.elapsed().as_secs_f64());
+
+        let (outcome, status_code) = match &result {
+            Ok(_) => ("success", 200),
+            Err(e) => ("error", e.status_code()),
+        };
+        let status_code = status_code.to_string();
+        for operation in &counted {
+            QUERIES
+                .with_label_values(&[self.target, operation, outcome, &status_code])
+                .inc();
+        }
+
+        result
+    }
+}
+
+/// Records how long a request to another service took.
+pub (crate) fn observe_outbound(service: &str, started: Instant, response: &Result<reqwest::Response, reqwest::Error>) {
+    let status_code = match response {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(_) => "failed".to_string(),
+    };
+
+    OUTBOUND_DURATION
+        .with_label_values(&[service, &status_code])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+/// Counts made while handling a SQL query, which only reach the metrics once
+/// its transaction is committed, so that rolled back solves aren't counted.
+///
+/// They're kept per future rather than per request, so each query of a batch
+/// collects its own.
+#[derive(Debug, Default)]
+pub (crate) struct Uncommitted {
+    correct_attempts: u64,
+    incorrect_attempts: u64,
+    first_bloods: u64,
+}
+
+tokio::task_local! {
+    static UNCOMMITTED: RefCell<Uncommitted>;
+}
+
+impl Uncommitted {
+    /// Runs the future, collecting the counts it makes instead of adding them
+    /// to the metrics.
+    pub (crate) async fn collect<T>(future: impl Future<Output = T>) -> (T, Self) {
+        let collecting = async {
+            let output = future.await;
+            (output, UNCOMMITTED.with(RefCell::take))
+        };
+        UNCOMMITTED.scope(RefCell::default(), collecting).await
+    }
+
+    /// Adds the counts to the metrics.
+    pub (crate) fn commit(self) {
+        SOLVE_ATTEMPTS.with_label_values(&["true"]).inc_by(self.correct_attempts);
+        SOLVE_ATTEMPTS.with_label_values(&["false"]).inc_by(self.incorrect_attempts);
+        FIRST_BLOODS.inc_by(self.first_bloods);
+    }
+
+    /// Makes the counts in `count`, holding them back if they're being
+    /// collected.
+    fn count(count: impl FnOnce(&mut Self)) {
+        let mut counts = Self::default();
+        count(&mut counts);
+
+        let held = UNCOMMITTED.try_with(|uncommitted| {
+            let mut uncommitted = uncommitted.borrow_mut();
+            uncommitted.correct_attempts += counts.correct_attempts;
+            uncommitted.incorrect_attempts += counts.incorrect_attempts;
+            uncommitted.first_bloods += counts.first_bloods;
+        });
+        if held.is_err() {
+            counts.commit();
+        }
+    }
+}
+
+/// Counts a solve attempt.
+pub (crate) fn count_solve_attempt(correct: bool) {
+    Uncommitted::count(|counts| {
+        if correct {
+            counts.correct_attempts += 1;
+        } else {
+            counts.incorrect_attempts += 1;
+        }
+    });
+}
+
+/// Counts a first blood.
+pub (crate) fn count_first_blood() {
+    Uncommitted::count(|counts| counts.first_bloods += 1);
+}
+
+/// Renders every metric in the Prometheus text format.
+pub async fn render() -> String {
+    let (size, idle) = crate::sql::pool_usage().await;
+    DB_POOL.with_label_values(&["open"]).set(i64::from(size));
+    DB_POOL.with_label_values(&["idle"]).set(i64::try_from(idle).unwrap_or(i64::MAX));
+
+    let mut buffer = vec![];
+    if let Err(e) = TextEncoder::new().encode(&prometheus::gather(), &mut buffer) {
+        error!("Failed to encode metrics: {e}");
+    }
+    String::from_utf8_lossy(&buffer).into_owned()
+}
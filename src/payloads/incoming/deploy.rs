@@ -13,7 +13,7 @@ pub enum ChallIdentifier {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__type", rename_all = "snake_case", content = "data")]
+#[serde(tag = "__type", rename_all = "snake_case", content = "data", deny_unknown_fields)]
 pub enum ToDeploy {
     Deploy {
         chall: ChallIdentifier,
@@ -1,4 +1,5 @@
 use std::fmt::Display;
+use uuid::Uuid;
 use {
     serde::{Deserialize, Serialize},
     schemars::JsonSchema,
@@ -25,6 +26,7 @@ impl Display for AlertLevel {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct DeveloperDiscordMessage {
     pub (crate) level: AlertLevel,
     pub (crate) message: String,
@@ -33,14 +35,24 @@ pub struct DeveloperDiscordMessage {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__participant_message_type", rename_all = "snake_case", content = "metadata")]
+#[serde(tag = "__participant_message_type", rename_all = "snake_case", content = "metadata", deny_unknown_fields)]
 pub enum ParticipantMessage {
-    FirstBlood { chall_name: String, team: String, user: String },
+    FirstBlood {
+        chall_name: String,
+        team: String,
+        user: String,
+        #[serde(default)]
+        chall_id: Option<Uuid>,
+        #[serde(default)]
+        points: Option<i32>,
+        #[serde(default)]
+        categories: Vec<String>,
+    },
     Alert { message: String },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__type", rename_all = "snake_case", content = "details")]
+#[serde(tag = "__type", rename_all = "snake_case", content = "details", deny_unknown_fields)]
 pub enum ToDiscord {
     Developer(DeveloperDiscordMessage),
     Participant(ParticipantMessage),
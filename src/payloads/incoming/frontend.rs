@@ -5,7 +5,7 @@ use {
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__sync_type", rename_all = "snake_case", content = "id")]
+#[serde(tag = "__sync_type", rename_all = "snake_case", content = "id", deny_unknown_fields)]
 pub enum SyncType {
     Chall(Uuid),
     AllChalls,
@@ -17,8 +17,8 @@ pub enum SyncType {
     All,
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
-#[serde(tag = "__type", rename_all = "snake_case", content = "data")]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "__type", rename_all = "snake_case", content = "data", deny_unknown_fields)]
 pub enum ToFrontend {
     Sync(SyncType),
 }
@@ -13,9 +13,15 @@ pub mod deploy;
 pub mod sql;
 pub mod frontend;
 
-use serde::Deserialize;
+use std::marker::PhantomData;
+
+use serde::{ Deserialize, Deserializer };
+use serde::de::{ MapAccess, SeqAccess, Visitor };
+use serde::de::value::{ MapAccessDeserializer, SeqAccessDeserializer };
 use schemars::JsonSchema;
 
+use super::outgoing::error::{ ErrorCode, RequestError };
+
 pub use {
     discord::ToDiscord,
     deploy::ToDeploy,
@@ -28,23 +34,158 @@ pub use {
 /// It implements [`Handle`][crate::handlers::Handle] to make it easy to process
 /// & respond to requests.
 /// 
-/// It's built to be as flexible as possible, but there are still some things
-/// that are need to be done— notably allowing batch queries for each of the targets.
+/// Each of the targets can be sent either a single query or an array of
+/// queries (see [`Batchable`]). Unknown fields are rejected everywhere in the
+/// body, not just at the top level, so a misspelled target or parameter isn't
+/// silently ignored.
 #[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Incoming {
     /// Deploy query (create, poll, and stop deployments)
     #[serde(rename = "deploy")]
-    pub (crate) depl: Option<ToDeploy>,
+    pub (crate) depl: Option<Batchable<ToDeploy>>,
 
     /// Discord query (first blood and organizer notifications)
     #[serde(rename = "discord")]
-    pub (crate) disc: Option<ToDiscord>,
+    pub (crate) disc: Option<Batchable<ToDiscord>>,
 
     /// Frontend query (Resync cache)
     #[serde(rename = "frontend")]
-    pub (crate) fron: Option<ToFrontend>,
+    pub (crate) fron: Option<Batchable<ToFrontend>>,
     
     /// SQL query (addition, modification, and deletion of solves, teams, users, challenges, etc.)
     #[serde(rename = "sql")]
-    pub (crate) sqll: Option<ToSql>,
+    pub (crate) sqll: Option<Batchable<ToSql>>,
+}
+
+impl Incoming {
+    /// Parses a request body, pointing any error at the part of the body that
+    /// caused it.
+    pub fn parse(body: &[u8]) -> Result<Self, RequestError> {
+        let mut deserializer = serde_json::Deserializer::from_slice(body);
+
+        let incoming = serde_path_to_error::deserialize(&mut deserializer)?;
+        deserializer
+            .end()
+            .map_err(|e| RequestError::new(ErrorCode::InvalidJson, e.to_string()))?;
+
+        Ok(incoming)
+    }
+}
+
+/// Either a single query or an array of queries for one of the targets.
+/// 
+/// The queries in a batch are handled concurrently, and the response for a
+/// batch is an array of results in the same order as the queries.
+#[derive(Debug, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum Batchable<T> {
+    /// A single query, responded to with a single result.
+    Single(T),
+    /// A list of queries, responded to with a list of results.
+    Batch(Vec<T>),
+}
+
+impl<T> Batchable<T> {
+    /// Iterates over the contained values in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        match self {
+            Self::Single(value) => std::slice::from_ref(value).iter(),
+            Self::Batch(values) => values.iter(),
+        }
+    }
+
+    /// Maps each of the contained values, keeping the single/batch shape.
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Batchable<U> {
+        match self {
+            Self::Single(value) => Batchable::Single(f(value)),
+            Self::Batch(values) => Batchable::Batch(values.into_iter().map(f).collect()),
+        }
+    }
+}
+
+/// Objects are deserialized as [`Batchable::Single`] and arrays as
+/// [`Batchable::Batch`].
+/// 
+/// This is done by hand instead of with `#[serde(untagged)]` so that errors
+/// from the inner query are reported instead of a generic "did not match any
+/// variant" error.
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Batchable<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BatchableVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for BatchableVisitor<T> {
+            type Value = Batchable<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a query object or an array of query objects")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                T::deserialize(MapAccessDeserializer::new(map)).map(Batchable::Single)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Vec::<T>::deserialize(SeqAccessDeserializer::new(seq)).map(Batchable::Batch)
+            }
+        }
+
+        deserializer.deserialize_any(BatchableVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(body: &str) -> Option<ErrorCode> {
+        Incoming::parse(body.as_bytes()).err().map(|e| e.code)
+    }
+
+    fn get_top(params: &str) -> String {
+        format!(r#"{{ "sql": {{ "__type": "team", "details": {{ "__query_name": "get_top", "params": {params} }} }} }}"#)
+    }
+
+    #[test]
+    fn known_fields_are_accepted() {
+        assert_eq!(error_code(&get_top(r#"{ "limit": 10 }"#)), None);
+        assert_eq!(error_code(&get_top(r#"{ "limit": 10, "filter": { "eligible": true } }"#)), None);
+    }
+
+    #[test]
+    fn unknown_top_level_fields_are_rejected() {
+        assert_eq!(error_code(r#"{ "sqll": [] }"#), Some(ErrorCode::UnknownField));
+    }
+
+    #[test]
+    fn unknown_query_params_are_rejected() {
+        assert_eq!(error_code(&get_top(r#"{ "limt": 10 }"#)), Some(ErrorCode::UnknownField));
+        assert_eq!(error_code(&get_top(r#"{ "limit": 10, "eligible": true }"#)), Some(ErrorCode::UnknownField));
+    }
+
+    #[test]
+    fn unknown_filter_fields_are_rejected() {
+        assert_eq!(
+            error_code(&get_top(r#"{ "limit": 10, "filter": { "divison": "open" } }"#)),
+            Some(ErrorCode::UnknownField),
+        );
+    }
+
+    #[test]
+    fn unknown_auth_fields_are_rejected() {
+        let body = r#"{ "sql": { "__type": "user", "details": { "__query_name": "check_auth", "params": {
+            "id": "00000000-0000-0000-0000-000000000000",
+            "auth": { "__type": "pass", "params": { "password": "hunter2", "otp": "123456" } }
+        } } } }"#;
+        assert_eq!(error_code(body), Some(ErrorCode::UnknownField));
+    }
+
+    #[test]
+    fn unknown_fields_in_batches_are_rejected() {
+        let body = r#"{ "sql": [
+            { "__type": "team", "details": { "__query_name": "get_top", "params": { "limit": 10 } } },
+            { "__type": "team", "details": { "__query_name": "get_top", "params": { "limit": 10, "top": 3 } } }
+        ] }"#;
+        assert_eq!(error_code(body), Some(ErrorCode::UnknownField));
+    }
 }
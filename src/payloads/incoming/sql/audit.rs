@@ -0,0 +1,48 @@
+use {
+    serde::{Deserialize, Serialize},
+    schemars::JsonSchema,
+};
+use uuid::Uuid;
+
+use super::Auth;
+use crate::Token;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "audit_outcome", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Error,
+}
+
+/// Narrows down which audit log entries are listed. Entries have to match
+/// every filter that's given.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AuditFilter {
+    /// Only entries for this operation, e.g. `sql.team.create`.
+    pub operation: Option<String>,
+    /// Only entries that affected this user, team, challenge, etc.
+    pub affected_id: Option<Uuid>,
+    /// Only entries made by this admin.
+    pub actor_id: Option<Uuid>,
+    pub token: Option<Token>,
+    pub outcome: Option<AuditOutcome>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "__query_name", rename_all = "snake_case", content = "params", deny_unknown_fields)]
+pub enum AuditQuery {
+    /// Lists audit log entries, newest first. To get the next page, pass the id
+    /// of the last entry of this page as `before`.
+    #[serde(rename = "list")]
+    List {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        before: Option<Uuid>,
+        limit: u32,
+        #[serde(default)]
+        filter: AuditFilter,
+    },
+}
@@ -14,14 +14,63 @@ pub enum LinkType {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Link {
     #[serde(rename = "type")]
     pub link_type: LinkType,
     pub location: String,
 }
 
+/// A hint for a challenge. Hints are hidden until a team unlocks them, which
+/// costs the team `cost` points, which can't be negative. Hints given as just
+/// a string are free.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__query_name", rename_all = "snake_case", content = "params")]
+#[serde(untagged, deny_unknown_fields)]
+pub enum HintInput {
+    Free(String),
+    Priced { content: String, cost: i32 },
+}
+
+impl HintInput {
+    pub fn into_parts(self) -> (String, i32) {
+        match self {
+            Self::Free(content) => (content, 0),
+            Self::Priced { content, cost } => (content, cost),
+        }
+    }
+}
+
+/// How a challenge's point value changes as more teams solve it.
+/// 
+/// Dynamic challenges start at `points` and decay towards `min_points`. The
+/// first solver gets the full value, and the minimum is hit once `decay` more
+/// teams have solved it (so at `decay + 1` solves).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "scoring_type", rename_all = "snake_case")]
+pub enum ScoringType {
+    Static,
+    Linear,
+    Parabolic,
+}
+
+/// How flag guesses are matched against a challenge's flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "flag_type", rename_all = "snake_case")]
+pub enum FlagType {
+    Exact,
+    CaseInsensitive,
+    /// Flags are regex patterns that have to match the whole guess.
+    Regex,
+    /// Flags are templates, where `{}` is replaced with a value derived from
+    /// the team's id and the challenge's flag secret. Each team has to submit
+    /// its own flag.
+    PerTeam,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "__query_name", rename_all = "snake_case", content = "params", deny_unknown_fields)]
 pub enum ChallQuery {
     #[serde(rename = "create")]
     CreateChallenge {
@@ -30,8 +79,14 @@ pub enum ChallQuery {
         name: String,
         description: String,
         points: i32,
+        scoring_type: Option<ScoringType>,
+        min_points: Option<i32>,
+        decay: Option<i32>,
         authors: Vec<String>,
-        hints: Vec<String>,
+        hints: Vec<HintInput>,
+        /// The challenges a team has to solve before this one is unlocked.
+        #[serde(default)]
+        prerequisites: Vec<Uuid>,
         categories: Vec<String>,
         tags: Vec<String>,
         links: Vec<Link>,
@@ -40,6 +95,11 @@ pub enum ChallQuery {
         source_folder: String,
 
         flag: String,
+        flag_type: Option<FlagType>,
+        accepted_flags: Option<Vec<String>>,
+        /// The key per-team flags are generated with. One is generated if the
+        /// challenge needs one and doesn't have one yet.
+        flag_secret: Option<String>,
     },
     #[serde(rename = "update")]
     UpdateChallenge {
@@ -48,19 +108,63 @@ pub enum ChallQuery {
         name: Option<String>,
         description: Option<String>,
         points: Option<i32>,
+        scoring_type: Option<ScoringType>,
+        min_points: Option<i32>,
+        decay: Option<i32>,
         authors: Option<Vec<String>>,
-        hints: Option<Vec<String>>,
+        hints: Option<Vec<HintInput>>,
+        prerequisites: Option<Vec<Uuid>>,
         categories: Option<Vec<String>>,
         tags: Option<Vec<String>>,
         links: Option<Vec<Link>>,
 
         visible: Option<bool>,
         source_folder: Option<String>,
+
+        flag_type: Option<FlagType>,
+        accepted_flags: Option<Vec<String>>,
+        flag_secret: Option<String>,
     },
+    /// Gets the challenge. If `team_id` is given, it's gotten as that team
+    /// sees it, so hidden challenges don't exist and ones with unsolved
+    /// prerequisites are locked.
     #[serde(rename = "get")]
     GetChallenge {
         id: Uuid,
+        #[serde(default)]
+        team_id: Option<Uuid>,
     },
     #[serde(rename = "get_all")]
     GetAllChallenges,
+    /// Gets the visible challenges the team has unlocked, leaving out the ones
+    /// with unsolved prerequisites.
+    #[serde(rename = "get_available")]
+    GetAvailableChallenges {
+        team_id: Uuid,
+    },
+    /// Gets the primary flag of the challenge for the team. This is only
+    /// different between teams for per-team flags.
+    /// 
+    /// Only the deploy server's token may use this (see
+    /// [`crate::handlers::permissions`]).
+    #[serde(rename = "get_team_flag")]
+    GetTeamFlag {
+        id: Uuid,
+        team_id: Uuid,
+    },
+    /// Deletes the challenge, archiving it along with its links and solves.
+    /// Teams that unlocked its hints keep paying for them.
+    #[serde(rename = "delete")]
+    DeleteChallenge {
+        admin_id: Uuid,
+        admin_auth: super::Auth,
+
+        id: Uuid,
+        /// Also removes the challenge's deployment.
+        #[serde(default)]
+        remove_deployment: bool,
+        /// Also tells the frontend to sync the challenge.
+        #[serde(default)]
+        sync_frontend: bool,
+    },
 }
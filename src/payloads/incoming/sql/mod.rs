@@ -1,4 +1,6 @@
+mod audit;
 mod chall;
+mod outbox;
 mod solve;
 mod team;
 mod user;
@@ -8,19 +10,31 @@ use {
     schemars::JsonSchema,
 };
 
-pub use chall::{ ChallQuery, Link, LinkType };
-pub use solve::SolveQuery;
-pub use team::TeamQuery;
+pub use audit::{ AuditQuery, AuditFilter, AuditOutcome };
+pub use chall::{ ChallQuery, FlagType, HintInput, Link, LinkType, ScoringType };
+pub use outbox::{ OutboxQuery, OutboxStatus };
+pub use solve::{ SolveQuery, DeletedSolveFilter };
+pub use team::{ TeamQuery, ExportFormat, ScoreboardFilter };
 pub use user::{ UserQuery, Auth };
 
 
 
 
+/// Every SQL query runs inside a transaction, which is rolled back if the query
+/// fails partway through.
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__type", rename_all = "snake_case", content = "details")]
+#[serde(tag = "__type", rename_all = "snake_case", content = "details", deny_unknown_fields)]
 pub enum ToSql {
     User(UserQuery),
     Team(TeamQuery),
     Chall(ChallQuery),
     Solve(SolveQuery),
+    Outbox(OutboxQuery),
+    Audit(AuditQuery),
+
+    /// An ordered list of queries that are run one after another in a single
+    /// transaction. Either all of them are committed, or none of them are.
+    /// 
+    /// Sequences can't be nested.
+    Sequence(Vec<ToSql>),
 }
@@ -0,0 +1,36 @@
+use {
+    serde::{Deserialize, Serialize},
+    schemars::JsonSchema,
+};
+use uuid::Uuid;
+
+use super::Auth;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "outbox_status", rename_all = "snake_case")]
+pub enum OutboxStatus {
+    Pending,
+    Sent,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "__query_name", rename_all = "snake_case", content = "params", deny_unknown_fields)]
+pub enum OutboxQuery {
+    #[serde(rename = "list")]
+    List {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        status: Option<OutboxStatus>,
+        limit: u32,
+    },
+    #[serde(rename = "replay")]
+    Replay {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        id: Uuid,
+    },
+}
@@ -4,8 +4,20 @@ use {
 };
 use uuid::Uuid;
 
+/// Narrows down which deleted solves are listed. Solves have to match every
+/// filter that's given.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DeletedSolveFilter {
+    pub user_id: Option<Uuid>,
+    pub team_id: Option<Uuid>,
+    pub chall_id: Option<Uuid>,
+    /// Only solves that were (or weren't) correct.
+    pub correct: Option<bool>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__query_name", rename_all = "snake_case", content = "params")]
+#[serde(tag = "__query_name", rename_all = "snake_case", content = "params", deny_unknown_fields)]
 pub enum SolveQuery {
     #[serde(rename = "get_all")]
     GetAllSolves,
@@ -31,4 +43,24 @@ pub enum SolveQuery {
     ClearAllSolvesForChallenge {
         id: Uuid,
     },
+
+    /// Moves a solve to `deleted_solves`. If it counted, the team's next
+    /// correct attempt at the challenge counts instead.
+    #[serde(rename = "delete")]
+    DeleteSolve {
+        admin_id: Uuid, admin_auth: super::Auth,
+        id: Uuid,
+    },
+    /// Moves a solve back out of `deleted_solves`.
+    #[serde(rename = "restore")]
+    RestoreSolve {
+        admin_id: Uuid, admin_auth: super::Auth,
+        id: Uuid,
+    },
+    #[serde(rename = "get_deleted")]
+    GetDeletedSolves {
+        admin_id: Uuid, admin_auth: super::Auth,
+        #[serde(default)]
+        filter: DeletedSolveFilter,
+    },
 }
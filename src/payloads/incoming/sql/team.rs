@@ -7,8 +7,31 @@ use uuid::Uuid;
 
 use super::Auth;
 
+/// The formats a scoreboard can be exported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// CTFtime's scoreboard feed, `{ "standings": [{ "pos", "team", "score" }] }`
+    Ctftime,
+    /// A CSV file with a header row of `pos,team,score,last_solve`
+    Csv,
+}
+
+/// Narrows a scoreboard down to some of the teams, e.g. to rank the teams
+/// competing for a prize. Teams have to match every filter that's given.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ScoreboardFilter {
+    /// Only teams that are (or aren't) eligible for prizes.
+    pub eligible: Option<bool>,
+    /// Only teams with this affiliation, ignoring case.
+    pub affiliation: Option<String>,
+    /// Only teams in this division.
+    pub division: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__query_name", rename_all = "snake_case", content = "params")]
+#[serde(tag = "__query_name", rename_all = "snake_case", content = "params", deny_unknown_fields)]
 pub enum TeamQuery {
     #[serde(rename = "available")]
     CheckTeamnameAvailability {
@@ -20,6 +43,7 @@ pub enum TeamQuery {
         description: String,
         eligible: bool,
         affiliation: Option<String>,
+        division: Option<String>,
         password: String,
 
         initial_user: Uuid,
@@ -32,6 +56,7 @@ pub enum TeamQuery {
         description: Option<String>,
         eligible: Option<bool>,
         affiliation: Option<Option<String>>,
+        division: Option<Option<String>>,
         password: String,
     },
     #[serde(rename = "get")]
@@ -41,13 +66,122 @@ pub enum TeamQuery {
     #[serde(rename = "get_all")]
     GetAllTeams,
     
+    /// Gets the top teams. While the scoreboard is frozen, this is the
+    /// scoreboard as of the freeze.
     #[serde(rename = "get_top")]
     GetTopTeams {
         limit: u32,
+        #[serde(default)]
+        filter: ScoreboardFilter,
     },
+    /// Gets the score history of the top teams. While the scoreboard is
+    /// frozen, anything after the freeze is left out.
     #[serde(rename = "get_top_history")]
     GetTopTeamsScoreHistory {
         limit: u32,
         start_time: NaiveDateTime,
+        #[serde(default)]
+        filter: ScoreboardFilter,
+    },
+    /// Gets the top teams as they currently stand, even while the scoreboard
+    /// is frozen.
+    #[serde(rename = "get_top_live")]
+    GetLiveTopTeams {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        limit: u32,
+        #[serde(default)]
+        filter: ScoreboardFilter,
+    },
+    /// Gets the full score history of the top teams, even while the scoreboard
+    /// is frozen.
+    #[serde(rename = "get_top_history_live")]
+    GetLiveTopTeamsScoreHistory {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        limit: u32,
+        start_time: NaiveDateTime,
+        #[serde(default)]
+        filter: ScoreboardFilter,
+    },
+    /// Exports the whole scoreboard, ranked the same way as `get_top`. While
+    /// the scoreboard is frozen, this is the scoreboard as of the freeze.
+    ///
+    /// This is admin-only, since it isn't limited to the top teams.
+    #[serde(rename = "export")]
+    ExportScoreboard {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        format: ExportFormat,
+        #[serde(default)]
+        filter: ScoreboardFilter,
+    },
+    /// Bans or unbans the team. Banned teams can't submit flags or unlock
+    /// hints, and are left off of the scoreboard.
+    #[serde(rename = "set_banned")]
+    SetBanned {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        id: Uuid,
+        banned: bool,
+    },
+    /// Hides or unhides the team from the scoreboard.
+    #[serde(rename = "set_hidden")]
+    SetHidden {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        id: Uuid,
+        hidden: bool,
+    },
+    /// Deletes the team. Its solves are archived in `deleted_solves`, scores
+    /// are recomputed without them, and its members are left without a team.
+    #[serde(rename = "delete")]
+    Delete {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        id: Uuid,
+    },
+    /// Lifts the scoreboard freeze, making the final scoreboard public, and
+    /// returns the top teams.
+    #[serde(rename = "unfreeze")]
+    UnfreezeScoreboard {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        limit: u32,
+    },
+    /// Puts a lifted scoreboard freeze back in place, and returns the top
+    /// teams as the public scoreboard now shows them.
+    #[serde(rename = "refreeze")]
+    RefreezeScoreboard {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        limit: u32,
+    },
+
+    /// Unlocks a hint for the team, subtracting its cost from the team's
+    /// score. Unlocking a hint the team already unlocked costs nothing, and
+    /// hints of challenges the team can't see yet can't be unlocked.
+    #[serde(rename = "unlock_hint")]
+    UnlockHint {
+        team_id: Uuid,
+        user_id: Uuid,
+        user_auth: Auth,
+        hint_id: Uuid,
+    },
+    /// Gets the hints the team has unlocked. Only members of the team can see
+    /// them.
+    #[serde(rename = "get_unlocked_hints")]
+    GetUnlockedHints {
+        team_id: Uuid,
+        user_id: Uuid,
+        user_auth: Auth,
     },
 }
@@ -5,7 +5,7 @@ use {
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__type", rename_all = "snake_case", content = "params")]
+#[serde(tag = "__type", rename_all = "snake_case", content = "params", deny_unknown_fields)]
 pub enum Auth {
     #[serde(alias = "oauth")]
     OAuth {
@@ -19,7 +19,7 @@ pub enum Auth {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
-#[serde(tag = "__query_name", rename_all = "snake_case", content = "params")]
+#[serde(tag = "__query_name", rename_all = "snake_case", content = "params", deny_unknown_fields)]
 pub enum UserQuery {
     #[serde(rename = "available")]
     CheckUsernameAvailability {
@@ -63,5 +63,25 @@ pub enum UserQuery {
         id: Uuid,
     },
     #[serde(rename = "get_all")]
-    GetAllUsers
+    GetAllUsers,
+    /// Bans or unbans the user. Banned users can't submit flags or unlock
+    /// hints.
+    #[serde(rename = "set_banned")]
+    SetBanned {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        id: Uuid,
+        banned: bool,
+    },
+    /// Deletes the user. Their solves are archived in `deleted_solves`, and
+    /// scores are recomputed without them. Hints they unlocked stay unlocked
+    /// and paid for by their team.
+    #[serde(rename = "delete")]
+    Delete {
+        admin_id: Uuid,
+        admin_auth: Auth,
+
+        id: Uuid,
+    },
 }
\ No newline at end of file
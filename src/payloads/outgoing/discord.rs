@@ -1,31 +1,42 @@
 use serde::{Serialize, Deserialize};
+use uuid::Uuid;
 
 use crate::handlers::OutgoingErr;
 
-// TODO: Figure this out
-#[derive(Debug, Clone, Serialize)]
-#[serde(into = "&'static str")]
-pub struct FromDiscord;
-
-impl From<FromDiscord> for &'static str {
-    fn from(_: FromDiscord) -> Self { "success" }
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+#[serde(tag = "__type", rename_all = "snake_case", content = "details")]
+pub enum FromDiscord {
+    /// The message was sent to discord right away, possibly after waiting for
+    /// a short rate limit to reset.
+    Sent {
+        delayed_secs: f64,
+        bucket: Option<BucketState>,
+    },
+    /// The message couldn't be sent right away, and is queued in the outbox to
+    /// be retried.
+    Queued {
+        outbox_id: Uuid,
+        bucket: Option<BucketState>,
+    },
 }
 
-impl schemars::JsonSchema for FromDiscord {
-    fn schema_name() -> String {
-        "FromDiscord".to_string()
-    }
-
-    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
-        gen.subschema_for::<&'static str>()
-    }
+/// The last known state of discord's rate limit bucket for a webhook.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct BucketState {
+    pub limit: Option<u32>,
+    pub remaining: Option<u32>,
+    /// The number of seconds until the bucket resets.
+    pub reset_after: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct FromDiscordErr {
     pub (crate) status_code: u16,
     pub (crate) status_message: String,
-    pub (crate) body: Vec<u8>
+    pub (crate) body: Vec<u8>,
+    /// How long discord asked us to wait before trying again, in seconds.
+    #[serde(default)]
+    pub (crate) retry_after: Option<f64>,
 }
 
 
@@ -41,6 +52,7 @@ impl OutgoingErr for FromDiscordErr {
             "code": code,
             "status_message": status_message,
             "body": body,
+            "retry_after": self.retry_after,
         }))
     }
     fn status_code(&self) -> u16 {
@@ -0,0 +1,168 @@
+//! The response to a request that couldn't be handled at all, e.g. because its
+//! `Authorization` header is missing or its body isn't a valid
+//! [`Incoming`][crate::payloads::incoming::Incoming].
+//!
+//! These are returned instead of an [`Outgoing`][super::Outgoing], with a
+//! non-2xx status code and a body like:
+//! ```json
+//! {
+//!     "code": "missing_field",
+//!     "error": "missing field `name` at line 1 column 62",
+//!     "pointer": "/sql/params/name"
+//! }
+//! ```
+
+use actix_web::HttpResponse;
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::error::Category;
+use serde_path_to_error::{ Path, Segment };
+
+use crate::{ AuthError, SignatureError };
+
+/// A stable code for each kind of error. New codes may be added, but existing
+/// ones won't change meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// There's no `Authorization` header. (401)
+    MissingAuthorization,
+    /// The `Authorization` header isn't a bearer token or a signature. (401)
+    MalformedAuthorization,
+    /// The bearer token doesn't match any allowed token. (401)
+    InvalidToken,
+    /// A bearer token was sent, but only signed requests are accepted. (401)
+    SignatureRequired,
+    /// The signature's timestamp is too far from now. (401)
+    StaleSignature,
+    /// The signature is malformed or doesn't match any allowed token. (401)
+    InvalidSignature,
+    /// The signature's nonce was already used. (401)
+    ReusedNonce,
+
+    /// The caller isn't allowed to use one of the queries. (403)
+    Forbidden,
+
+    /// The body is bigger than the server accepts. (413)
+    BodyTooLarge,
+    /// The body couldn't be read. (400)
+    UnreadableBody,
+    /// The body isn't valid JSON. (400)
+    InvalidJson,
+    /// A field isn't part of the payload. (400)
+    UnknownField,
+    /// A required field is missing. (400)
+    MissingField,
+    /// A field has the wrong type or an invalid value. (400)
+    InvalidValue,
+}
+
+impl ErrorCode {
+    /// The status code of the response.
+    pub fn status_code(self) -> StatusCode {
+        match self {
+            Self::MissingAuthorization | Self::MalformedAuthorization | Self::InvalidToken |
+            Self::SignatureRequired | Self::StaleSignature | Self::InvalidSignature |
+            Self::ReusedNonce => StatusCode::UNAUTHORIZED,
+
+            Self::Forbidden => StatusCode::FORBIDDEN,
+
+            Self::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+
+            Self::UnreadableBody | Self::InvalidJson | Self::UnknownField |
+            Self::MissingField | Self::InvalidValue => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// An error that stopped a request from being handled.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RequestError {
+    /// What kind of error this is.
+    pub code: ErrorCode,
+    /// A human-readable description of the error.
+    pub error: String,
+    /// A JSON pointer (RFC 6901) to the part of the body that caused the
+    /// error, if it was caused by the body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+}
+
+impl RequestError {
+    /// Creates an error that isn't caused by any specific part of the body.
+    pub fn new(code: ErrorCode, error: impl Into<String>) -> Self {
+        Self { code, error: error.into(), pointer: None }
+    }
+
+    /// Points the error at a part of the body.
+    pub fn at(self, pointer: impl Into<String>) -> Self {
+        Self { pointer: Some(pointer.into()), ..self }
+    }
+
+    /// Converts the error into a response with the code's status code.
+    pub fn response(&self) -> HttpResponse {
+        HttpResponse::build(self.code.status_code()).json(self)
+    }
+}
+
+/// Escapes a key for use in a JSON pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Converts a path from [`serde_path_to_error`] into a JSON pointer. Enum
+/// variants aren't keys in the JSON, so they're skipped.
+fn pointer(path: &Path) -> String {
+    path.iter().fold(String::new(), |pointer, segment| match segment {
+        Segment::Seq { index } => format!("{pointer}/{index}"),
+        Segment::Map { key } => format!("{pointer}/{}", escape(key)),
+        Segment::Enum { .. } | Segment::Unknown => pointer,
+    })
+}
+
+/// Gets the field named in serde's `unknown field` and `missing field`
+/// messages, which is the first thing in backticks.
+fn named_field(message: &str) -> Option<&str> {
+    let (_, rest) = message.split_once('`')?;
+    let (field, _) = rest.split_once('`')?;
+    Some(field)
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for RequestError {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let pointer = pointer(e.path());
+        let inner = e.into_inner();
+        let message = inner.to_string();
+
+        let code = match inner.classify() {
+            Category::Io => ErrorCode::UnreadableBody,
+            Category::Syntax | Category::Eof => return Self::new(ErrorCode::InvalidJson, message),
+            Category::Data if message.starts_with("unknown field") => ErrorCode::UnknownField,
+            Category::Data if message.starts_with("missing field") => ErrorCode::MissingField,
+            Category::Data => ErrorCode::InvalidValue,
+        };
+
+        // The path stops at the object holding an unknown or missing field
+        let pointer = match (code, named_field(&message)) {
+            (ErrorCode::UnknownField | ErrorCode::MissingField, Some(field)) => format!("{pointer}/{}", escape(field)),
+            _ => pointer,
+        };
+
+        Self::new(code, message).at(pointer)
+    }
+}
+
+impl From<AuthError> for RequestError {
+    fn from(e: AuthError) -> Self {
+        let code = match e {
+            AuthError::Missing => ErrorCode::MalformedAuthorization,
+            AuthError::BadToken => ErrorCode::InvalidToken,
+            AuthError::SignatureRequired => ErrorCode::SignatureRequired,
+            AuthError::Signature(SignatureError::Stale) => ErrorCode::StaleSignature,
+            AuthError::Signature(SignatureError::ReusedNonce) => ErrorCode::ReusedNonce,
+            AuthError::Signature(SignatureError::Malformed | SignatureError::BadSignature) => ErrorCode::InvalidSignature,
+        };
+        Self::new(code, e.message())
+    }
+}
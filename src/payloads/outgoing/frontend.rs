@@ -1,5 +1,6 @@
 use reqwest::StatusCode;
 use serde::Serialize;
+use uuid::Uuid;
 
 use crate::{handlers::OutgoingErr, payloads::incoming::frontend::SyncType};
 
@@ -7,12 +8,16 @@ use crate::{handlers::OutgoingErr, payloads::incoming::frontend::SyncType};
 #[serde(tag = "__type", rename_all = "snake_case", content = "details")]
 pub enum FromFrontend {
     Synced(SyncType),
+    /// The sync couldn't be sent right away, and is queued in the outbox to be
+    /// retried.
+    Queued { sync_type: SyncType, outbox_id: Uuid },
 }
 
 #[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
 #[serde(tag = "__type", rename_all = "snake_case", content = "info")]
 pub enum FromFrontendErr {
-    FailedToSync(SyncType),
+    /// The frontend responded to the sync with a non-2xx status code.
+    FailedToSync { sync_type: SyncType, code: u16 },
     WebhookServerError(String),
 }
 
@@ -24,7 +29,7 @@ impl OutgoingErr for FromFrontendErr {
         use serde_json::json;
         
         match self {
-            Self::FailedToSync(sync_type) => {
+            Self::FailedToSync { sync_type, code } => {
                 let (sync_type, id) = match sync_type {
                     SyncType::User(id)          => ("user", Some(id)),
                     SyncType::Team(id)          => ("team", Some(id)),
@@ -48,7 +53,8 @@ impl OutgoingErr for FromFrontendErr {
 
                 Ok(json!({
                     "message": "failed to sync",
-                    "sync_type": sync_type
+                    "sync_type": sync_type,
+                    "code": code
                 }))
             },
             Self::WebhookServerError(reason) => {
@@ -2,6 +2,7 @@ pub mod discord;
 pub mod deploy;
 pub mod sql;
 pub mod frontend;
+pub mod error;
 
 use actix_web::HttpResponse;
 use reqwest::StatusCode;
@@ -12,6 +13,7 @@ use crate::handlers::{ResponseFrom, OutgoingErr};
 use crate::logging::*;
 
 use super::incoming::{
+    Batchable,
     ToSql,
     ToDiscord,
     ToFrontend,
@@ -20,50 +22,39 @@ use super::incoming::{
 
 #[derive(Debug, Clone)]
 pub struct Outgoing {
-    pub (crate) depl: Option<ResponseFrom<ToDeploy>>,
-    pub (crate) disc: Option<ResponseFrom<ToDiscord>>,
-    pub (crate) fron: Option<ResponseFrom<ToFrontend>>,
-    pub (crate) sqll: Option<ResponseFrom<ToSql>>,
+    pub (crate) depl: Option<Batchable<ResponseFrom<ToDeploy>>>,
+    pub (crate) disc: Option<Batchable<ResponseFrom<ToDiscord>>>,
+    pub (crate) fron: Option<Batchable<ResponseFrom<ToFrontend>>>,
+    pub (crate) sqll: Option<Batchable<ResponseFrom<ToSql>>>,
+}
+
+fn get_code<T, Err: OutgoingErr>(res: &Result<T, Err>) -> u16 {
+    match res {
+        Ok(_) => 200,
+        Err(e) => e.status_code(),
+    }
 }
 
 impl Outgoing {
-    fn get_code<T, Err: OutgoingErr>(opt_res: &Option<Result<T, Err>>) -> Option<u16> {
-        match opt_res {
-            Some(Ok(_)) => {
-                Some(200)
-            },
-            Some(Err(e)) => {
-                let status_code = e.status_code();
-                Some(status_code)
-            }
-            None => None,
-        }
+    fn get_codes<T, Err: OutgoingErr>(opt_res: &Option<Batchable<Result<T, Err>>>) -> Vec<u16> {
+        opt_res
+            .iter()
+            .flat_map(Batchable::iter)
+            .map(get_code)
+            .collect()
     }
 
     pub fn response(self) -> HttpResponse {
-        let mut bad_status_code_list = vec![];
-        
-
-        if let Some(deploy_code) = Self::get_code(&self.depl) {
-            if !(200..300).contains(&deploy_code) {
-                bad_status_code_list.push(deploy_code);
-            }
-        };
-        if let Some(discord_code) = Self::get_code(&self.disc) {
-            if !(200..300).contains(&discord_code) {
-                bad_status_code_list.push(discord_code);
-            }
-        };
-        if let Some(frontend_code) = Self::get_code(&self.fron) {
-            if !(200..300).contains(&frontend_code) {
-                bad_status_code_list.push(frontend_code);
-            }
-        };
-        if let Some(sql_code) = Self::get_code(&self.sqll) {
-            if !(200..300).contains(&sql_code) {
-                bad_status_code_list.push(sql_code);
-            }
-        };
+        let bad_status_code_list: Vec<u16> = [
+            Self::get_codes(&self.depl),
+            Self::get_codes(&self.disc),
+            Self::get_codes(&self.fron),
+            Self::get_codes(&self.sqll),
+        ]
+            .into_iter()
+            .flatten()
+            .filter(|code| !(200..300).contains(code))
+            .collect();
 
         if bad_status_code_list.is_empty() {
             info!("Response had no errors");
@@ -100,7 +91,7 @@ mod json_schema {
     use super::*;
 
     macro_rules! result_enum {
-        (enum $name:ident { Ok($success_type:path), Err($err_type:path) }) => {
+        (enum $name:ident, $item_name:ident { Ok($success_type:path), Err($err_type:path) }) => {
             #[derive(serde::Serialize, schemars::JsonSchema)]
             #[serde(tag = "ok", content = "data")]
             #[allow(unused, clippy::large_enum_variant)]
@@ -110,21 +101,71 @@ mod json_schema {
                 #[serde(rename = "err")]
                 Err($err_type),
             }
+
+            /// A single result in a batch response, along with its own status
+            /// code.
+            #[derive(serde::Serialize, schemars::JsonSchema)]
+            #[serde(tag = "ok")]
+            #[allow(unused, clippy::large_enum_variant)]
+            pub(super) enum $item_name {
+                #[serde(rename = "success")]
+                Ok { status: u16, data: $success_type },
+                #[serde(rename = "err")]
+                Err { status: u16, data: $err_type },
+            }
+
+            impl From<Result<$success_type, $err_type>> for $name {
+                fn from(res: Result<$success_type, $err_type>) -> Self {
+                    match res {
+                        Ok(ok) => Self::Ok(ok),
+                        Err(err) => Self::Err(err),
+                    }
+                }
+            }
+
+            impl From<Result<$success_type, $err_type>> for $item_name {
+                fn from(res: Result<$success_type, $err_type>) -> Self {
+                    let status = get_code(&res);
+                    match res {
+                        Ok(data) => Self::Ok { status, data },
+                        Err(data) => Self::Err { status, data },
+                    }
+                }
+            }
         };
     }
 
-    result_enum!(enum DeployResult { Ok(deploy::FromDeploy), Err(deploy::FromDeployErr) });
-    result_enum!(enum DiscordResult { Ok(discord::FromDiscord), Err(discord::FromDiscordErr) });
-    result_enum!(enum FrontendResult { Ok(frontend::FromFrontend), Err(frontend::FromFrontendErr) });
-    result_enum!(enum SqlResult { Ok(sql::FromSql), Err(sql::FromSqlErr) });
+    result_enum!(enum DeployResult, DeployBatchItem { Ok(deploy::FromDeploy), Err(deploy::FromDeployErr) });
+    result_enum!(enum DiscordResult, DiscordBatchItem { Ok(discord::FromDiscord), Err(discord::FromDiscordErr) });
+    result_enum!(enum FrontendResult, FrontendBatchItem { Ok(frontend::FromFrontend), Err(frontend::FromFrontendErr) });
+    result_enum!(enum SqlResult, SqlBatchItem { Ok(sql::FromSql), Err(sql::FromSqlErr) });
+
+    /// Mirrors the shape of the incoming [`Batchable`] query.
+    #[derive(Serialize, schemars::JsonSchema)]
+    #[serde(untagged)]
+    #[allow(unused)]
+    pub enum Batched<Single, Item> {
+        Single(Single),
+        Batch(Vec<Item>),
+    }
+
+    impl<T, Single, Item> From<Batchable<T>> for Batched<Single, Item>
+    where Single: From<T>, Item: From<T> {
+        fn from(batchable: Batchable<T>) -> Self {
+            match batchable {
+                Batchable::Single(res) => Self::Single(res.into()),
+                Batchable::Batch(list) => Self::Batch(list.into_iter().map(Into::into).collect()),
+            }
+        }
+    }
     
     #[derive(Serialize, schemars::JsonSchema)]
     #[allow(unused)]
     pub struct OutgoingSchemaShape {
-        pub(super) deploy: Option<DeployResult>,
-        pub(super) discord: Option<DiscordResult>,
-        pub(super) frontend: Option<FrontendResult>,
-        pub(super) sql: Option<SqlResult>,
+        pub(super) deploy: Option<Batched<DeployResult, DeployBatchItem>>,
+        pub(super) discord: Option<Batched<DiscordResult, DiscordBatchItem>>,
+        pub(super) frontend: Option<Batched<FrontendResult, FrontendBatchItem>>,
+        pub(super) sql: Option<Batched<SqlResult, SqlBatchItem>>,
     }
     
     impl schemars::JsonSchema for Outgoing {
@@ -148,22 +189,10 @@ mod json_schema {
     impl From<Outgoing> for OutgoingSchemaShape {
         fn from(out: Outgoing) -> Self {
             Self {
-                deploy: out.depl.map(|res| match res {
-                    Ok(ok) => DeployResult::Ok(ok),
-                    Err(err) => DeployResult::Err(err),
-                }),
-                discord: out.disc.map(|res| match res {
-                    Ok(ok) => DiscordResult::Ok(ok),
-                    Err(err) => DiscordResult::Err(err),
-                }),
-                frontend: out.fron.map(|res| match res {
-                    Ok(ok) => FrontendResult::Ok(ok),
-                    Err(err) => FrontendResult::Err(err),
-                }),
-                sql: out.sqll.map(|res| match res {
-                    Ok(ok) => SqlResult::Ok(ok),
-                    Err(err) => SqlResult::Err(err),
-                }),
+                deploy: out.depl.map(Into::into),
+                discord: out.disc.map(Into::into),
+                frontend: out.fron.map(Into::into),
+                sql: out.sqll.map(Into::into),
             }
         }
     }
@@ -17,15 +17,31 @@ pub enum FromSql {
     Team(Team),
     TeamArr(Vec<Team>),
     TeamScoreHistoryArray(Vec<ScoreEntry>),
+    CtftimeScoreboard(CtftimeScoreboard),
+    Csv(String),
     
     User(User),
     UserArr(Vec<User>),
     
     Solve(Solve),
     SolveArr(Vec<Solve>),
+    DeletedSolve(DeletedSolve),
+    DeletedSolveArr(Vec<DeletedSolve>),
+
+    Hint(Hint),
+    HintArr(Vec<Hint>),
+
+    OutboxEntry(OutboxEntry),
+    OutboxEntryArr(Vec<OutboxEntry>),
+
+    AuditEntryArr(Vec<AuditEntry>),
+
+    Flag(String),
 
     Availability(bool),
     AuthStatus(bool),
+
+    Sequence(Vec<FromSql>),
 }
 
 #[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
@@ -37,6 +53,12 @@ pub enum FromSqlErr {
     NameDoesNotExist(String),
     NameIsTaken(String),
     RequestTooBig(u64, u64),
+    BadRequest(Cow<'static, str>),
+    SequenceStep { index: usize, error: Box<FromSqlErr> },
+    RateLimited { scope: &'static str, retry_after: f64 },
+    ChallengeLocked(Uuid),
+    Banned(Uuid),
+    CompetitionClosed { starts_at: Option<chrono::NaiveDateTime>, ends_at: Option<chrono::NaiveDateTime> },
 }
 
 impl From<sqlx::Error> for FromSqlErr {
@@ -76,6 +98,33 @@ impl OutgoingErr for FromSqlErr {
                 "size": size,
                 "limit": limit,
             })),
+            Self::BadRequest(description) => Ok(serde_json::json!({
+                "err": "Bad request.",
+                "info": description,
+            })),
+            Self::SequenceStep { index, error } => Ok(serde_json::json!({
+                "err": "A query in the sequence failed, so none of the sequence was committed.",
+                "index": index,
+                "cause": (*error).body()?,
+            })),
+            Self::RateLimited { scope, retry_after } => Ok(serde_json::json!({
+                "err": "Too many attempts, try again later.",
+                "scope": scope,
+                "retry_after": retry_after,
+            })),
+            Self::ChallengeLocked(id) => Ok(serde_json::json!({
+                "err": "This challenge is locked until its prerequisites are solved.",
+                "id": id,
+            })),
+            Self::Banned(id) => Ok(serde_json::json!({
+                "err": "This account is banned.",
+                "id": id,
+            })),
+            Self::CompetitionClosed { starts_at, ends_at } => Ok(serde_json::json!({
+                "err": "Flags are only accepted while the competition is running.",
+                "starts_at": starts_at,
+                "ends_at": ends_at,
+            })),
         }
     }
     fn status_code(&self) -> u16 {
@@ -83,12 +132,15 @@ impl OutgoingErr for FromSqlErr {
             Self::OtherServerError(_) | Self::DatabaseError => 500,
             Self::RequestTooBig(_, _) => 413,
             Self::DoesNotExist(_) | Self::NameDoesNotExist(_) => 404,
-            Self::Auth => 403,
-            Self::NameIsTaken(_) => 400,
+            Self::Auth | Self::Banned(_) | Self::ChallengeLocked(_) | Self::CompetitionClosed { .. } => 403,
+            Self::NameIsTaken(_) | Self::BadRequest(_) => 400,
+            Self::SequenceStep { error, .. } => error.status_code(),
+            Self::RateLimited { .. } => 429,
         }
     }
 }
 
-pub use types::{ Chall, Solve, Team, ScoreEntry, User };
+pub use types::{ Chall, Hint, HintSummary, Solve, DeletedSolve, Team, ScoreEntry, User, OutboxEntry, OutboxTarget };
+pub use types::{ AuditEntry, CtftimeScoreboard, CtftimeStanding };
 
 
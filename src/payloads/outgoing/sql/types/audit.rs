@@ -0,0 +1,66 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::payloads::incoming::sql::AuditOutcome;
+use crate::Token;
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct SerializableAuditEntry {
+    pub id: Uuid,
+
+    pub token: Token,
+    pub operation: String,
+    pub params: serde_json::Value,
+
+    pub actor_id: Option<Uuid>,
+    pub affected_ids: Vec<Uuid>,
+
+    pub outcome: AuditOutcome,
+    pub status_code: i16,
+
+    pub inserted_at: u64,
+}
+impl From<AuditEntry> for SerializableAuditEntry {
+    fn from(AuditEntry {
+        id,
+        token, operation, params,
+        actor_id, affected_ids,
+        outcome, status_code,
+        inserted_at,
+    }: AuditEntry) -> Self {
+        SerializableAuditEntry {
+            id,
+            token, operation, params,
+            actor_id, affected_ids,
+            outcome, status_code,
+            inserted_at: inserted_at.and_utc().timestamp() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(into = "SerializableAuditEntry")]
+pub struct AuditEntry {
+    pub id: Uuid,
+
+    pub token: Token,
+    pub operation: String,
+    pub params: serde_json::Value,
+
+    pub actor_id: Option<Uuid>,
+    pub affected_ids: Vec<Uuid>,
+
+    pub outcome: AuditOutcome,
+    pub status_code: i16,
+
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+impl schemars::JsonSchema for AuditEntry {
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        SerializableAuditEntry::json_schema(gen)
+    }
+    fn schema_name() -> String {
+        "AuditEntry".to_string()
+    }
+}
@@ -2,6 +2,8 @@ use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 use crate::sql::CiText;
 
+use super::hint::HintSummary;
+
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct Links {
     nc: Vec<String>,
@@ -16,8 +18,11 @@ pub struct SerializableChall {
     pub name: CiText,
     pub description: String,
     pub points: i32,
+    pub current_points: i32,
     pub authors: Vec<String>,
-    pub hints: Vec<String>,
+    pub hints: Vec<HintSummary>,
+    /// The challenges a team has to solve before this one is unlocked.
+    pub prerequisites: Vec<Uuid>,
     pub categories: Vec<String>,
     pub tags: Vec<String>,
     pub solve_count: i32,
@@ -29,8 +34,8 @@ pub struct SerializableChall {
 }
 impl From<SerializableChall> for Chall {
     fn from(SerializableChall {
-        id, name, description, points,
-        authors, hints, categories, tags,
+        id, name, description, points, current_points,
+        authors, hints, prerequisites, categories, tags,
         solve_count, visible, source_folder,
         links: Links {
             nc: links_nc,
@@ -39,9 +44,14 @@ impl From<SerializableChall> for Chall {
             static_links: links_static,
         },
     }: SerializableChall) -> Self {
+        let (hint_ids, hint_costs) = hints
+            .into_iter()
+            .map(|HintSummary { id, cost }| (id, cost))
+            .unzip();
+
         Chall {
-            id, name, description, points,
-            authors, hints, categories, tags,
+            id, name, description, points, current_points,
+            authors, hint_ids, hint_costs, prerequisites, categories, tags,
             solve_count, visible, source_folder,
             links_nc, links_web, links_admin, links_static,
         }
@@ -49,14 +59,20 @@ impl From<SerializableChall> for Chall {
 }
 impl From<Chall> for SerializableChall {
     fn from(Chall {
-        id, name, description, points,
-        authors, hints, categories, tags,
+        id, name, description, points, current_points,
+        authors, hint_ids, hint_costs, prerequisites, categories, tags,
         solve_count, visible, source_folder,
         links_nc: nc, links_web: web, links_admin: admin, links_static: static_links,
     }: Chall) -> Self {
+        let hints = hint_ids
+            .into_iter()
+            .zip(hint_costs)
+            .map(|(id, cost)| HintSummary { id, cost })
+            .collect();
+
         SerializableChall {
-            id, name, description, points,
-            authors, hints, categories, tags,
+            id, name, description, points, current_points,
+            authors, hints, prerequisites, categories, tags,
             solve_count, visible, source_folder,
             links: Links { nc, web, admin, static_links },
         }
@@ -70,8 +86,11 @@ pub struct Chall {
     pub name: CiText,
     pub description: String,
     pub points: i32,
+    pub current_points: i32,
     pub authors: Vec<String>,
-    pub hints: Vec<String>,
+    pub hint_ids: Vec<Uuid>,
+    pub hint_costs: Vec<i32>,
+    pub prerequisites: Vec<Uuid>,
     pub categories: Vec<String>,
     pub tags: Vec<String>,
     pub solve_count: i32,
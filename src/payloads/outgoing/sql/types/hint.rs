@@ -0,0 +1,18 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+/// An unlocked hint, including its content.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct Hint {
+    pub id: Uuid,
+    pub challenge_id: Uuid,
+    pub content: String,
+    pub cost: i32,
+}
+
+/// What everyone can see about a hint before it's unlocked.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct HintSummary {
+    pub id: Uuid,
+    pub cost: i32,
+}
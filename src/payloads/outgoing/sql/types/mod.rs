@@ -1,11 +1,19 @@
+mod audit;
 mod chall;
+mod hint;
+mod outbox;
+mod scoreboard;
 mod team;
 mod user;
 mod solve;
 
 pub use {
+    audit::AuditEntry,
     chall::Chall,
-    solve::Solve,
+    hint::{ Hint, HintSummary },
+    outbox::{ OutboxEntry, OutboxTarget },
+    scoreboard::{ CtftimeScoreboard, CtftimeStanding },
+    solve::{ Solve, DeletedSolve },
     team::{ Team, ScoreEntry },
     user::User,
 };
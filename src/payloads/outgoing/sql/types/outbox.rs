@@ -0,0 +1,68 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+
+use crate::payloads::incoming::sql::OutboxStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "outbox_target", rename_all = "snake_case")]
+pub enum OutboxTarget {
+    Discord,
+    Frontend,
+    Deploy,
+}
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct SerializableOutboxEntry {
+    pub id: Uuid,
+    pub target: OutboxTarget,
+    pub payload: serde_json::Value,
+
+    pub status: OutboxStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+
+    pub next_attempt_at: u64,
+    pub sent_at: Option<u64>,
+    pub inserted_at: u64,
+}
+impl From<OutboxEntry> for SerializableOutboxEntry {
+    fn from(OutboxEntry {
+        id, target, payload,
+        status, attempts, last_error,
+        next_attempt_at, sent_at, inserted_at,
+    }: OutboxEntry) -> Self {
+        SerializableOutboxEntry {
+            id, target, payload,
+            status, attempts, last_error,
+            next_attempt_at: next_attempt_at.and_utc().timestamp() as u64,
+            sent_at: sent_at.map(|dt| dt.and_utc().timestamp() as u64),
+            inserted_at: inserted_at.and_utc().timestamp() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(into = "SerializableOutboxEntry")]
+pub struct OutboxEntry {
+    pub id: Uuid,
+    pub target: OutboxTarget,
+    pub payload: serde_json::Value,
+
+    pub status: OutboxStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+
+    pub next_attempt_at: chrono::NaiveDateTime,
+    pub sent_at: Option<chrono::NaiveDateTime>,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+impl schemars::JsonSchema for OutboxEntry {
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        SerializableOutboxEntry::json_schema(gen)
+    }
+    fn schema_name() -> String {
+        "OutboxEntry".to_string()
+    }
+}
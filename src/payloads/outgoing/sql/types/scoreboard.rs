@@ -0,0 +1,15 @@
+use serde::{Serialize, Deserialize};
+
+/// A scoreboard in the format of CTFtime's scoreboard feed.
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CtftimeScoreboard {
+    pub standings: Vec<CtftimeStanding>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct CtftimeStanding {
+    /// 1-based position on the scoreboard. Ties are already broken.
+    pub pos: u32,
+    pub team: String,
+    pub score: i64,
+}
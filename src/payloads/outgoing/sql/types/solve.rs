@@ -45,3 +45,51 @@ impl schemars::JsonSchema for Solve {
         "Solve".to_string()
     }
 }
+
+#[derive(Debug, Clone, Serialize, schemars::JsonSchema)]
+pub struct SerializableDeletedSolve {
+    pub id: Uuid,
+
+    pub user_id: Uuid,
+    pub team_id: Uuid,
+    pub chall_id: Uuid,
+
+    pub flag_guess: String,
+    pub correct: bool,
+    pub time: u64,
+    pub deleted_at: u64,
+}
+impl From<DeletedSolve> for SerializableDeletedSolve {
+    fn from(DeletedSolve { id, user_id, team_id, chall_id, flag_guess, correct, time, deleted_at }: DeletedSolve) -> Self {
+        Self {
+            id, user_id, team_id, chall_id, flag_guess, correct,
+            time: time.and_utc().timestamp() as u64,
+            deleted_at: deleted_at.and_utc().timestamp() as u64,
+        }
+    }
+}
+
+/// A solve attempt that was moved to `deleted_solves`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(into = "SerializableDeletedSolve")]
+pub struct DeletedSolve {
+    pub id: Uuid,
+
+    pub user_id: Uuid,
+    pub team_id: Uuid,
+    pub chall_id: Uuid,
+
+    pub flag_guess: String,
+    pub correct: bool,
+    pub time: chrono::NaiveDateTime,
+    pub deleted_at: chrono::NaiveDateTime,
+}
+
+impl schemars::JsonSchema for DeletedSolve {
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        SerializableDeletedSolve::json_schema(gen)
+    }
+    fn schema_name() -> String {
+        "DeletedSolve".to_string()
+    }
+}
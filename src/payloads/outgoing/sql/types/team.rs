@@ -11,11 +11,15 @@ pub struct SerializableTeam {
     pub last_solve: Option<u64>,
     pub eligible: bool,
     pub affiliation: Option<String>,
+    pub division: Option<String>,
+    pub banned: bool,
+    pub hidden: bool,
 }
 impl From<Team> for SerializableTeam {
-    fn from(Team { id, name, score, last_solve, eligible, affiliation }: Team) -> Self {
+    fn from(Team { id, name, score, last_solve, eligible, affiliation, division, banned, hidden }: Team) -> Self {
         SerializableTeam {
-            id, name, eligible, affiliation,
+            id, name, eligible, affiliation, division,
+            banned, hidden,
             score,
             last_solve: last_solve.map(|dt| dt.and_utc().timestamp() as u64),
         }
@@ -31,6 +35,9 @@ pub struct Team {
     pub last_solve: Option<chrono::NaiveDateTime>,
     pub eligible: bool,
     pub affiliation: Option<String>,
+    pub division: Option<String>,
+    pub banned: bool,
+    pub hidden: bool,
 }
 
 
@@ -15,17 +15,18 @@ pub struct SerializableUser {
     
     pub eligible: bool,
     pub admin: bool,
+    pub banned: bool,
 }
 impl From<User> for SerializableUser {
     fn from(User {
         id, email, name,
         team_id, score, last_solve,
-        eligible, admin,
+        eligible, admin, banned,
     }: User) -> Self {
         SerializableUser {
             id, email, name,
             team_id, score,
-            eligible, admin,
+            eligible, admin, banned,
             last_solve: last_solve.map(|dt| dt.and_utc().timestamp() as u64),
         }
     }
@@ -45,6 +46,7 @@ pub struct User {
     
     pub eligible: bool,
     pub admin: bool,
+    pub banned: bool,
 }
 
 impl schemars::JsonSchema for User {